@@ -170,3 +170,31 @@ fn smoke_bench_3(b: &mut Bencher) {
         }
     });
 }
+
+#[bench]
+fn mpsc_burst_bench(b: &mut Bencher) {
+    let (tx, rx) = may::sync::mpsc::channel();
+    b.iter(|| {
+        for i in 0..1000 {
+            tx.send(i).unwrap();
+        }
+        for _ in 0..1000 {
+            rx.recv().unwrap();
+        }
+    });
+}
+
+#[bench]
+fn mpsc_burst_reserve_bench(b: &mut Bencher) {
+    let (tx, rx) = may::sync::mpsc::channel();
+    b.iter(|| {
+        // the sends below would not allocate
+        tx.reserve(1000);
+        for i in 0..1000 {
+            tx.send(i).unwrap();
+        }
+        for _ in 0..1000 {
+            rx.recv().unwrap();
+        }
+    });
+}
@@ -21,11 +21,11 @@ fn main() {
 
     let id = select!(
         _ = listener.accept() => println!("got connected"),
-        _ = coroutine::sleep(Duration::from_millis(1000)) => {},
         _ = rx1.recv() => println!("rx1 received"),
-        a = rx2.recv() => println!("rx2 received, a={:?}", a)
+        a = rx2.recv() => println!("rx2 received, a={:?}", a),
+        default(Duration::from_millis(1000)) => println!("timeout")
     );
 
-    assert_eq!(id, 3);
+    assert_eq!(id, 2);
     assert_eq!(rx1.recv(), Ok(42));
 }
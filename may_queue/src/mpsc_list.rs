@@ -3,6 +3,7 @@ use std::ptr;
 use std::sync::atomic::{AtomicPtr, Ordering};
 use std::thread;
 
+use crossbeam::queue::SegQueue;
 use crossbeam::utils::CachePadded;
 
 struct Node<T> {
@@ -19,12 +20,21 @@ impl<T> Node<T> {
     }
 }
 
+// a pre-allocated node for push
+struct NodePtr<T>(*mut Node<T>);
+
+unsafe impl<T: Send> Send for NodePtr<T> {}
+
+// the lock-free pool of the pre-allocated nodes, created by `reserve`
+type NodeCache<T> = SegQueue<NodePtr<T>>;
+
 /// The multi-producer single-consumer structure. This is not cloneable, but it
 /// may be safely shared so long as it is guaranteed that there is only one
 /// popper at a time (many pushers are allowed).
 pub struct Queue<T> {
     head: CachePadded<AtomicPtr<Node<T>>>,
     tail: UnsafeCell<*mut Node<T>>,
+    cache: AtomicPtr<NodeCache<T>>,
 }
 
 unsafe impl<T: Send> Send for Queue<T> {}
@@ -38,12 +48,54 @@ impl<T> Queue<T> {
         Queue {
             head: AtomicPtr::new(stub).into(),
             tail: UnsafeCell::new(stub),
+            cache: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// pre-allocate nodes so that the next `additional` pushes don't allocate
+    ///
+    /// this is only a hint, the queue would allocate new nodes as usual
+    /// when the reserved ones are used up
+    pub fn reserve(&self, additional: usize) {
+        let mut cache = self.cache.load(Ordering::Acquire);
+        if cache.is_null() {
+            let new = Box::into_raw(Box::new(NodeCache::new()));
+            cache = match self.cache.compare_exchange(
+                ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new,
+                Err(old) => {
+                    let _: Box<NodeCache<T>> = unsafe { Box::from_raw(new) };
+                    old
+                }
+            };
+        }
+
+        let cache = unsafe { &*cache };
+        for _ in cache.len()..additional {
+            cache.push(NodePtr(unsafe { Node::new(None) }));
+        }
+    }
+
+    // get a node from the reserved cache or allocate a new one
+    #[inline]
+    fn alloc_node(&self, t: T) -> *mut Node<T> {
+        let cache = self.cache.load(Ordering::Acquire);
+        if !cache.is_null() {
+            if let Some(NodePtr(node)) = unsafe { &*cache }.pop() {
+                unsafe { (*node).value = Some(t) };
+                return node;
+            }
         }
+        unsafe { Node::new(Some(t)) }
     }
 
     pub fn push(&self, t: T) {
         unsafe {
-            let node = Node::new(Some(t));
+            let node = self.alloc_node(t);
             let prev = self.head.swap(node, Ordering::AcqRel);
             (*prev).next.store(node, Ordering::Release);
         }
@@ -111,6 +163,14 @@ impl<T> Drop for Queue<T> {
         while self.pop().is_some() {}
         // release the stub
         let _: Box<Node<T>> = unsafe { Box::from_raw(*self.tail.get()) };
+        // release the reserved nodes
+        let cache = *self.cache.get_mut();
+        if !cache.is_null() {
+            let cache = unsafe { Box::from_raw(cache) };
+            while let Some(NodePtr(node)) = cache.pop() {
+                let _: Box<Node<T>> = unsafe { Box::from_raw(node) };
+            }
+        }
     }
 }
 
@@ -132,6 +192,24 @@ mod tests {
         assert_eq!(q.is_empty(), true);
     }
 
+    #[test]
+    fn test_reserve() {
+        let q: Queue<usize> = Queue::new();
+        q.reserve(2);
+        q.reserve(1);
+        let cache = unsafe { &*q.cache.load(Ordering::Acquire) };
+        assert_eq!(cache.len(), 2);
+        q.push(1);
+        q.push(2);
+        q.push(3);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), Some(2));
+        assert_eq!(q.pop(), Some(3));
+        // the unused reserved nodes are released in drop
+        q.reserve(10);
+    }
+
     #[test]
     fn test() {
         let nthreads = 8;
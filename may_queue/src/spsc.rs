@@ -1,3 +1,5 @@
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
 use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
 
 use crate::block_node::*;
@@ -160,6 +162,177 @@ impl<T> Drop for Queue<T> {
     }
 }
 
+struct Slot<T> {
+    // the push index that the slot waits for, plus one after it's filled
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// fixed capacity spsc ring buffer
+///
+/// each slot carries a sequence number so that the producer and the
+/// consumer never touch the same slot at the same time. in overwrite mode
+/// the producer pops the oldest element itself when the ring is full, so
+/// it never fails to push, and the consumer always gets the newest `cap`
+/// elements.
+pub struct BoundedQueue<T> {
+    // use for pop
+    head: CachePadded<AtomicUsize>,
+    // use for push
+    tail: CachePadded<AtomicUsize>,
+    buffer: Box<[Slot<T>]>,
+    overwrite: bool,
+}
+
+unsafe impl<T: Send> Send for BoundedQueue<T> {}
+unsafe impl<T: Send> Sync for BoundedQueue<T> {}
+
+/// create a spsc ring buffer that holds at most `cap` elements
+///
+/// `push` fails when the ring is full
+pub fn bounded<T>(cap: usize) -> BoundedQueue<T> {
+    BoundedQueue::new(cap, false)
+}
+
+/// create a spsc ring buffer that drops the oldest element when it's full
+///
+/// `push` never fails, the producer never stalls
+pub fn bounded_overwrite<T>(cap: usize) -> BoundedQueue<T> {
+    BoundedQueue::new(cap, true)
+}
+
+impl<T> BoundedQueue<T> {
+    fn new(cap: usize, overwrite: bool) -> Self {
+        assert!(cap > 0, "zero capacity ring buffer");
+        let buffer = (0..cap)
+            .map(|i| Slot {
+                seq: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+        BoundedQueue {
+            head: AtomicUsize::new(0).into(),
+            tail: AtomicUsize::new(0).into(),
+            buffer,
+            overwrite,
+        }
+    }
+
+    /// push a value to the ring, should only be called by the producer
+    ///
+    /// if the ring is full, return the value back, or drop the oldest
+    /// element in overwrite mode
+    pub fn push(&self, v: T) -> Result<(), T> {
+        let cap = self.buffer.len();
+        let pos = self.tail.load(Ordering::Relaxed);
+        let slot = &self.buffer[pos % cap];
+        loop {
+            let seq = slot.seq.load(Ordering::Acquire);
+            if seq == pos {
+                break;
+            }
+            if !self.overwrite {
+                return Err(v);
+            }
+            // race with the consumer for the oldest element only, if the
+            // consumer wins the slot is free soon and nothing is dropped
+            let oldest = pos.wrapping_sub(cap);
+            if seq == oldest.wrapping_add(1)
+                && self
+                    .head
+                    .compare_exchange(oldest, seq, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+            {
+                let old = unsafe { (*slot.value.get()).as_ptr().read() };
+                slot.seq.store(pos, Ordering::Release);
+                drop(old);
+            } else {
+                // the consumer is freeing the slot
+                std::hint::spin_loop();
+            }
+        }
+
+        unsafe { (*slot.value.get()).as_mut_ptr().write(v) };
+        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+        self.tail.store(pos.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// pop from the ring, if it's empty return None
+    ///
+    /// should only be called by the consumer
+    pub fn pop(&self) -> Option<T> {
+        let cap = self.buffer.len();
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.buffer[pos % cap];
+            let seq = slot.seq.load(Ordering::Acquire);
+            let filled = pos.wrapping_add(1);
+            if seq == filled {
+                // the producer may take the element in overwrite mode
+                match self.head.compare_exchange_weak(
+                    pos,
+                    filled,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let v = unsafe { (*slot.value.get()).as_ptr().read() };
+                        slot.seq.store(pos.wrapping_add(cap), Ordering::Release);
+                        return Some(v);
+                    }
+                    Err(head) => pos = head,
+                }
+            } else if (seq.wrapping_sub(filled) as isize) < 0 {
+                return None;
+            } else {
+                pos = self.head.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// the max number of elements the ring could hold
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// get the number of elements in the ring
+    #[inline]
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Acquire);
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(head).min(self.buffer.len())
+    }
+
+    /// return true if there is no element in the ring
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// return true if the ring is full
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.buffer.len()
+    }
+}
+
+impl<T> std::fmt::Debug for BoundedQueue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("BoundedQueue")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .field("overwrite", &self.overwrite)
+            .finish()
+    }
+}
+
+impl<T> Drop for BoundedQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,6 +374,97 @@ mod tests {
             assert_eq!(*item, i);
         }
     }
+
+    #[test]
+    fn bounded_full() {
+        let q = bounded(4);
+        for i in 0..4 {
+            assert_eq!(q.push(i), Ok(()));
+        }
+        assert!(q.is_full());
+        assert_eq!(q.push(4), Err(4));
+        assert_eq!(q.pop(), Some(0));
+        assert_eq!(q.push(4), Ok(()));
+        for i in 1..5 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.pop(), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn bounded_overwrite_oldest() {
+        let cap = 8;
+        let q = bounded_overwrite(cap);
+        for i in 0..cap + 5 {
+            assert_eq!(q.push(i), Ok(()));
+        }
+        assert_eq!(q.len(), cap);
+        assert!(q.is_full());
+        // only the newest cap elements remain
+        for i in 5..cap + 5 {
+            assert_eq!(q.pop(), Some(i));
+        }
+        assert_eq!(q.pop(), None);
+    }
+
+    #[test]
+    fn bounded_overwrite_race() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let total = 100_000;
+        let q = Arc::new(bounded_overwrite(16));
+        let producer = {
+            let q = q.clone();
+            thread::spawn(move || {
+                for i in 0..total {
+                    q.push(i).unwrap();
+                }
+            })
+        };
+        // the received elements are in order, some are dropped
+        let mut last = None;
+        while last != Some(total - 1) {
+            if let Some(v) = q.pop() {
+                assert!(last.map_or(true, |last| v > last));
+                last = Some(v);
+            }
+        }
+        producer.join().unwrap();
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn bounded_overwrite_window() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let cap = 8;
+        let total = 1_000_000;
+        let q = Arc::new(bounded_overwrite(cap));
+        let producer = {
+            let q = q.clone();
+            thread::spawn(move || {
+                for i in 0..total {
+                    q.push(i).unwrap();
+                }
+            })
+        };
+        // an element is only dropped by the push that overwrites it, so
+        // when `v - 1` is skipped the producer must have reached `v - 1 + cap`
+        let mut last = None;
+        while last != Some(total - 1) {
+            if let Some(v) = q.pop() {
+                if last.map_or(v > 0, |last| v > last + 1) {
+                    let tail = q.tail.load(Ordering::Acquire);
+                    assert!(tail >= v - 1 + cap, "gap before {} at tail {}", v, tail);
+                }
+                last = Some(v);
+            }
+        }
+        producer.join().unwrap();
+    }
 }
 
 #[cfg(all(nightly, test))]
@@ -0,0 +1,66 @@
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use crate::coroutine_impl::is_coroutine;
+use crate::sync::mpmc;
+use crate::sync::mpsc::channel;
+
+// the number of threads that run the blocking functions
+const BLOCKING_THREADS: usize = 8;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// the job queue of the blocking pool, the threads are started on first use
+fn pool() -> &'static Mutex<mpmc::Sender<Job>> {
+    static POOL: OnceLock<Mutex<mpmc::Sender<Job>>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let (tx, rx) = mpmc::channel::<Job>();
+        for i in 0..BLOCKING_THREADS {
+            let rx = rx.clone();
+            thread::Builder::new()
+                .name(format!("may-blocking-{}", i))
+                .spawn(move || {
+                    while let Ok(job) = rx.recv() {
+                        // a panicked job must not take the thread down
+                        panic::catch_unwind(AssertUnwindSafe(job)).ok();
+                    }
+                })
+                .expect("failed to spawn blocking thread");
+        }
+        Mutex::new(tx)
+    })
+}
+
+/// run the blocking io function without stalling the worker
+///
+/// in coroutine context the function runs on the blocking pool and the
+/// coroutine is parked until it returns, in thread context it runs directly
+pub(crate) fn run_blocking<F, T>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    if !is_coroutine() {
+        return f();
+    }
+
+    let (tx, rx) = channel();
+    let job: Job = Box::new(move || {
+        tx.send(f()).ok();
+    });
+    if pool().lock().unwrap().send(job).is_err() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            "blocking pool is closed",
+        ));
+    }
+    // the sender is dropped without sending only when the function panics
+    rx.recv().unwrap_or_else(|_| {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "blocking function panicked",
+        ))
+    })
+}
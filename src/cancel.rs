@@ -97,6 +97,11 @@ impl<T: CancelIo> CancelImpl<T> {
         }
     }
 
+    // set the cancel bit for the running coroutine itself
+    pub fn set_canceled(&self) {
+        self.state.fetch_or(1, Ordering::Release);
+    }
+
     // async cancel for a coroutine
     pub unsafe fn cancel(&self) {
         self.state.fetch_or(1, Ordering::Release);
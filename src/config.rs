@@ -1,16 +1,61 @@
 //! `May` Configuration interface
 //!
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::coroutine_impl::CoroutineId;
+use crate::watchdog::{set_watchdog, WatchdogAction};
 
 // default stack size, in usize
 // windows has a minimal size as 0x4a8!!!!
 const DEFAULT_STACK_SIZE: usize = 0x1000;
 const DEFAULT_POOL_CAPACITY: usize = 100;
+// default preempt interval, in nanos
+const DEFAULT_PREEMPT_INTERVAL: u64 = 10_000_000;
 
 static WORKERS: AtomicUsize = AtomicUsize::new(0);
 static STACK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_STACK_SIZE);
 static POOL_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_POOL_CAPACITY);
+static TICK: AtomicPtr<Tick> = AtomicPtr::new(ptr::null_mut());
+static EXIT_HOOK: AtomicPtr<ExitHook> = AtomicPtr::new(ptr::null_mut());
+static PREEMPT_INTERVAL: AtomicU64 = AtomicU64::new(DEFAULT_PREEMPT_INTERVAL);
+static POLLER_PANIC: AtomicUsize = AtomicUsize::new(PollerPanicPolicy::Abort as usize);
+
+/// what to do when an io poller (worker) thread panics
+///
+/// the coroutines parked on IO would never be waked up if the poller
+/// thread is dead, so it's never left silently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollerPanicPolicy {
+    /// restart the event loop on the same thread, the coroutine that is
+    /// running or subscribing when the panic happens is lost
+    Restart,
+    /// print the error and abort the process, this is the default
+    Abort,
+}
+
+// the periodic callback that run on each worker
+pub(crate) struct Tick {
+    pub interval: Duration,
+    pub f: Box<dyn Fn(usize) + Send + Sync>,
+}
+
+// get the registered tick callback
+#[inline]
+pub(crate) fn get_tick() -> Option<&'static Tick> {
+    unsafe { TICK.load(Ordering::Acquire).as_ref() }
+}
+
+// the observer that run when any coroutine exits
+pub(crate) type ExitHook = Box<dyn Fn(CoroutineId) + Send + Sync>;
+
+// get the registered coroutine exit hook
+#[inline]
+pub(crate) fn get_exit_hook() -> Option<&'static ExitHook> {
+    unsafe { EXIT_HOOK.load(Ordering::Acquire).as_ref() }
+}
 
 /// `May` Configuration type
 pub struct Config;
@@ -85,4 +130,100 @@ impl Config {
     pub fn get_stack_size(&self) -> usize {
         STACK_SIZE.load(Ordering::Acquire)
     }
+
+    /// set the run time after which `maybe_yield` would yield the coroutine
+    ///
+    /// if you pass zero to it, will use internal default which is 10ms
+    pub fn set_preempt_interval(&self, interval: Duration) -> &Self {
+        info!("set preempt interval={:?}", interval);
+        let nanos = match interval.as_nanos() as u64 {
+            0 => DEFAULT_PREEMPT_INTERVAL,
+            n => n,
+        };
+        PREEMPT_INTERVAL.store(nanos, Ordering::Relaxed);
+        self
+    }
+
+    /// get the run time after which `maybe_yield` would yield the coroutine
+    pub fn get_preempt_interval(&self) -> Duration {
+        Duration::from_nanos(PREEMPT_INTERVAL.load(Ordering::Relaxed))
+    }
+
+    /// set the policy when an io poller (worker) thread panics
+    pub fn on_poller_panic(&self, policy: PollerPanicPolicy) -> &Self {
+        info!("set poller panic policy={:?}", policy);
+        POLLER_PANIC.store(policy as usize, Ordering::Relaxed);
+        self
+    }
+
+    /// get the policy when an io poller (worker) thread panics
+    pub fn get_poller_panic(&self) -> PollerPanicPolicy {
+        match POLLER_PANIC.load(Ordering::Relaxed) {
+            x if x == PollerPanicPolicy::Restart as usize => PollerPanicPolicy::Restart,
+            _ => PollerPanicPolicy::Abort,
+        }
+    }
+
+    /// register a callback that each worker would call every `interval`
+    ///
+    /// the callback is invoked with the worker id from within the worker loop,
+    /// between running coroutines. it's useful for periodic maintenance like
+    /// flushing metrics without spawning a ticker coroutine per worker.
+    ///
+    /// the callback runs on the worker thread directly, not in a coroutine,
+    /// it must be fast and never block, or all the coroutines on that worker
+    /// would be delayed. the interval is not precise when the worker is busy.
+    ///
+    /// a later call would replace the previous callback
+    pub fn on_tick<F>(&self, interval: Duration, f: F) -> &Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        info!("set tick interval={:?}", interval);
+        let tick = Box::new(Tick {
+            interval,
+            f: Box::new(f),
+        });
+        // the old one may still be used by the workers, just leak it
+        TICK.swap(Box::into_raw(tick), Ordering::AcqRel);
+        self
+    }
+
+    /// register a global observer that runs whenever a coroutine exits
+    ///
+    /// the callback is invoked exactly once for each coroutine with its id,
+    /// no matter it returns normally, panics or is canceled. it runs on the
+    /// worker thread after the coroutine is done, out of any coroutine
+    /// context, so it must be fast and never block. a panic in the callback
+    /// is caught and logged.
+    ///
+    /// a later call would replace the previous callback
+    pub fn on_coroutine_exit<F>(&self, f: F) -> &Self
+    where
+        F: Fn(CoroutineId) + Send + Sync + 'static,
+    {
+        let hook: Box<ExitHook> = Box::new(Box::new(f));
+        // the old one may still be used by the workers, just leak it
+        EXIT_HOOK.swap(Box::into_raw(hook), Ordering::AcqRel);
+        self
+    }
+
+    /// watch the coroutines that live longer than `max_age`
+    ///
+    /// a low frequency watchdog thread scans the live coroutines, each one
+    /// older than `max_age` is logged once with its id, name and age, and
+    /// canceled if the action is `Cancel`. only the coroutines spawned after
+    /// this call are watched.
+    ///
+    /// it's best-effort, the scan interval is half of `max_age` bounded in
+    /// 50ms to 10s, so a coroutine may be flagged late. the live coroutines
+    /// are tracked in a global table once enabled, that adds a small cost
+    /// to each spawn and exit.
+    ///
+    /// a later call would replace the previous setting
+    pub fn coroutine_watchdog(&self, max_age: Duration, action: WatchdogAction) -> &Self {
+        info!("set coroutine watchdog max_age={:?}, {:?}", max_age, action);
+        set_watchdog(max_age, action);
+        self
+    }
 }
@@ -1,10 +1,15 @@
 // re-export coroutine interface
 pub use crate::cancel::trigger_cancel_panic;
 pub use crate::coroutine_impl::{
-    current, is_coroutine, park, park_timeout, spawn, Builder, Coroutine,
+    critical, current, is_canceled, is_coroutine, no_migrate, park, park_timeout, prewarm_stacks,
+    spawn, Builder, Coroutine, CoroutineId,
 };
-pub use crate::join::JoinHandle;
+pub use crate::join::{join_all, try_join_all, JoinHandle};
 pub use crate::park::ParkError;
+pub use crate::park_reason::ParkReason;
+pub use crate::random::{random, random_range, Random, RandomRange};
+pub use crate::scheduler::{scheduler_stats, SchedulerStats};
 pub use crate::scoped::scope;
-pub use crate::sleep::sleep;
-pub use crate::yield_now::yield_now;
+pub use crate::sleep::{sleep, sleep_until};
+pub use crate::watchdog::dump_live;
+pub use crate::yield_now::{maybe_yield, yield_now};
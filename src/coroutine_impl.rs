@@ -1,15 +1,19 @@
 use std::fmt;
 use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use crate::cancel::Cancel;
-use crate::config::config;
+use crate::config::{config, get_exit_hook};
 use crate::join::{make_join_handle, Join, JoinHandle};
 use crate::local::get_co_local_data;
 use crate::local::CoroutineLocal;
 use crate::park::Park;
-use crate::scheduler::get_scheduler;
+use crate::park_reason::ParkReason;
+use crate::scheduler::{get_scheduler, get_worker_id};
+use crate::yield_now::reset_slice;
 use crossbeam::atomic::AtomicCell;
 use generator::{Generator, Gn};
 
@@ -62,6 +66,17 @@ impl Done {
         let local = unsafe { Box::from_raw(get_co_local(&co)) };
         let name = local.get_co().name();
 
+        crate::watchdog::unregister(local.get_co().id());
+        get_scheduler().co_completed();
+
+        // notify the exit observer, it runs out of the coroutine context
+        if let Some(hook) = get_exit_hook() {
+            let id = local.get_co().id();
+            if panic::catch_unwind(AssertUnwindSafe(|| hook(id))).is_err() {
+                error!("coroutine exit hook panicked, id={}", id);
+            }
+        }
+
         // recycle the coroutine
         let (size, used) = co.stack_usage();
         if used == size {
@@ -78,6 +93,8 @@ impl Done {
 
         if size == config().get_stack_size() {
             get_scheduler().pool.put(co);
+        } else {
+            get_scheduler().pool.put_sized(co, size);
         }
     }
 }
@@ -102,12 +119,46 @@ fn get_co_local(co: &CoroutineImpl) -> *mut CoroutineLocal {
 /// Coroutine
 /// /////////////////////////////////////////////////////////////////////////////
 
+/// A unique identifier of a coroutine
+///
+/// the id is never reused for the life of the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CoroutineId(u64);
+
+impl CoroutineId {
+    // generate a new unique id
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        CoroutineId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// return the raw id value
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for CoroutineId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 /// The internal representation of a `Coroutine` handle
 struct Inner {
+    id: CoroutineId,
     name: Option<String>,
     stack_size: usize,
     park: Park,
     cancel: Cancel,
+    // the worker id that the coroutine is pinned to
+    pinned: AtomicUsize,
+    // confined to the first n workers, 0 means no limit
+    worker_group: usize,
+    // the nested depth of critical sections
+    critical: AtomicUsize,
+    // the encoded park reason, 0 if not parked
+    park_reason: AtomicU64,
 }
 
 #[derive(Clone)]
@@ -120,13 +171,23 @@ unsafe impl Send for Coroutine {}
 
 impl Coroutine {
     // Used only internally to construct a coroutine object without spawning
-    fn new(name: Option<String>, stack_size: usize) -> Coroutine {
+    fn new(name: Option<String>, stack_size: usize, worker_group: usize) -> Coroutine {
+        // spread the coroutines in the group by pinning them
+        let pinned = match worker_group {
+            0 => NOT_PINNED,
+            n => crate::random::random_range(0..n),
+        };
         Coroutine {
             inner: Arc::new(Inner {
+                id: CoroutineId::new(),
                 name,
                 stack_size,
                 park: Park::new(),
                 cancel: Cancel::new(),
+                pinned: AtomicUsize::new(pinned),
+                worker_group,
+                critical: AtomicUsize::new(0),
+                park_reason: AtomicU64::new(0),
             }),
         }
     }
@@ -151,11 +212,24 @@ impl Coroutine {
         self.inner.cancel.cancel();
     }
 
+    /// Gets the coroutine unique id.
+    pub fn id(&self) -> CoroutineId {
+        self.inner.id
+    }
+
     /// Gets the coroutine name.
     pub fn name(&self) -> Option<&str> {
         self.inner.name.as_deref()
     }
 
+    /// Gets what the coroutine is parked on, None if it's not parked
+    ///
+    /// only the blocking calls of the channels, mutexes, io, sleep and
+    /// join are recorded. the reason is cleared when the call returns
+    pub fn park_reason(&self) -> Option<ParkReason> {
+        ParkReason::decode(self.inner.park_reason.load(Ordering::Relaxed))
+    }
+
     /// Get the internal cancel
     #[cfg(unix)]
     pub(crate) fn get_cancel(&self) -> &Cancel {
@@ -173,6 +247,9 @@ impl fmt::Debug for Coroutine {
 // Builder
 ////////////////////////////////////////////////////////////////////////////////
 
+// set once any coroutine is confined to a worker group
+static WORKER_GROUP_SET: AtomicBool = AtomicBool::new(false);
+
 /// Coroutine factory, which can be used in order to configure the properties of
 /// a new coroutine.
 ///
@@ -220,6 +297,8 @@ pub struct Builder {
     name: Option<String>,
     // The size of the stack for the spawned coroutine
     stack_size: Option<usize>,
+    // confine the coroutine to the first n workers
+    worker_group: Option<usize>,
 }
 
 impl Builder {
@@ -229,22 +308,37 @@ impl Builder {
         Builder {
             name: None,
             stack_size: None,
+            worker_group: None,
         }
     }
 
-    /// Names the thread-to-be. Currently the name is used for identification
-    /// only in panic messages.
+    /// Names the coroutine-to-be. the name is returned by `Coroutine::name`,
+    /// and a panic of the coroutine is reported as
+    /// `coroutine '<name>' panicked at <location>` in place of the panic hook
+    /// that was set before the runtime started. a hook set after that still
+    /// sees the panic first.
     pub fn name(mut self, name: String) -> Builder {
         self.name = Some(name);
         self
     }
 
     /// Sets the size of the stack for the new coroutine.
+    ///
+    /// the size is in `usize` words like `Config::set_stack_size`, a deep
+    /// recursive task could use a larger stack than the default one.
     pub fn stack_size(mut self, size: usize) -> Builder {
         self.stack_size = Some(size);
         self
     }
 
+    // confine the coroutine and all its descendants to the first n workers
+    #[cfg(feature = "test-util")]
+    pub(crate) fn worker_group(mut self, n: usize) -> Builder {
+        WORKER_GROUP_SET.store(true, Ordering::Relaxed);
+        self.worker_group = Some(n);
+        self
+    }
+
     /// Spawns a new coroutine, and returns a join handle for it.
     /// The join handle can be used to block on
     /// termination of the child coroutine, including recovering its panics.
@@ -256,14 +350,28 @@ impl Builder {
         static DONE: Done = Done {};
 
         let sched = get_scheduler();
-        let Builder { name, stack_size } = self;
+        let Builder {
+            name,
+            stack_size,
+            worker_group,
+        } = self;
+        // inherit the worker group from the parent coroutine
+        let worker_group = worker_group.unwrap_or_else(|| {
+            if !WORKER_GROUP_SET.load(Ordering::Relaxed) {
+                return 0;
+            }
+            match get_co_local_data() {
+                Some(local) => unsafe { local.as_ref() }.get_co().inner.worker_group,
+                None => 0,
+            }
+        });
         let stack_size = stack_size.unwrap_or_else(|| config().get_stack_size());
         let _co = if stack_size == config().get_stack_size() {
             let co = sched.pool.get();
             co.prefetch();
             Some(co)
         } else {
-            None
+            sched.pool.get_sized(stack_size)
         };
 
         // create a join resource, shared by waited coroutine and *this* coroutine
@@ -286,6 +394,10 @@ impl Builder {
             // set the return packet
             their_packet.swap(Some(f()));
 
+            // the local data is dropped when the join returns
+            if let Some(local) = get_co_local_data() {
+                unsafe { local.as_ref() }.clear_local_data();
+            }
             their_join.trigger();
             subscriber
         };
@@ -298,7 +410,9 @@ impl Builder {
             Gn::new_opt(stack_size, closure)
         };
 
-        let handle = Coroutine::new(name, stack_size);
+        let handle = Coroutine::new(name, stack_size, worker_group);
+        crate::watchdog::register(&handle);
+        sched.co_spawned();
         // create the local storage
         let local = CoroutineLocal::new(handle.clone(), join.clone());
         // attache the local storage to the coroutine
@@ -440,6 +554,28 @@ where
     Builder::new().spawn(f).unwrap()
 }
 
+/// pre-allocate `count` coroutine stacks of `size` into the pool
+///
+/// the first spawns would reuse the warm stacks instead of paying the
+/// mmap and guard page setup, which avoids the latency spikes of the
+/// initial burst. it's supposed to be called at startup before traffic
+/// arrives. the `size` is in `usize` words like `Builder::stack_size`,
+/// pass 0 to use the default stack size. stacks of other sizes are only
+/// reused by the coroutines spawned with exactly that size by
+/// `Builder::stack_size`. the pool capacity of the size grows by `count`
+/// so that the stacks are kept after reused.
+///
+/// return how many stacks are actually allocated, which is less than
+/// `count` if the allocation fails
+pub fn prewarm_stacks(count: usize, size: usize) -> usize {
+    let size = if size == 0 {
+        config().get_stack_size()
+    } else {
+        size
+    };
+    get_scheduler().pool.prewarm(count, size)
+}
+
 /// Gets a handle to the coroutine that invokes it.
 /// it will panic if you call it in a thead context
 #[inline]
@@ -459,6 +595,15 @@ pub fn is_coroutine() -> bool {
     get_co_local_data().is_some()
 }
 
+/// if the current coroutine is canceled
+///
+/// a canceled coroutine panics at its next blocking call, a pure-CPU loop
+/// could check it to exit cooperatively. always false in thread context
+#[inline]
+pub fn is_canceled() -> bool {
+    is_coroutine() && current_cancel_data().is_canceled()
+}
+
 /// get current coroutine cancel registration
 /// panic in a thread context
 #[inline]
@@ -475,6 +620,117 @@ pub(crate) fn co_cancel_data(co: &CoroutineImpl) -> &'static Cancel {
     &local.get_co().inner.cancel
 }
 
+/// the coroutine is not pinned to any worker
+pub(crate) const NOT_PINNED: usize = !0;
+
+// return the worker id that the coroutine is pinned to
+#[inline]
+pub(crate) fn co_pinned_worker(co: &CoroutineImpl) -> usize {
+    let local = unsafe { &*get_co_local(co) };
+    local.get_co().inner.pinned.load(Ordering::Relaxed)
+}
+
+// restore the pinned worker when leaving the `no_migrate` scope
+struct PinGuard<'a> {
+    pinned: &'a AtomicUsize,
+    old: usize,
+}
+
+impl<'a> Drop for PinGuard<'a> {
+    fn drop(&mut self) {
+        self.pinned.store(self.old, Ordering::Relaxed);
+    }
+}
+
+/// run the closure without migrating the current coroutine to other workers
+///
+/// the coroutine is pinned to the current worker thread during the closure,
+/// even if it yields inside, it would always be resumed on the same worker.
+/// this is useful for thread-affine resources like some FFI contexts.
+///
+/// pinned coroutines can't be stolen by other workers, excessive use of it
+/// would hurt the load balancing of the scheduler.
+///
+/// it just runs the closure if not called in a coroutine that is running on
+/// a worker thread
+pub fn no_migrate<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let id = get_worker_id();
+    let local = match get_co_local_data() {
+        Some(local) if id != !1 => local,
+        _ => return f(),
+    };
+    let pinned = &unsafe { &*local.as_ptr() }.get_co().inner.pinned;
+    let old = pinned.swap(id, Ordering::Relaxed);
+    let _guard = PinGuard { pinned, old };
+    f()
+}
+
+// leave the critical section
+struct CriticalGuard<'a>(&'a AtomicUsize);
+
+impl<'a> Drop for CriticalGuard<'a> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// run the closure as a critical section of the current coroutine
+///
+/// within the section the cooperative preemption by `maybe_yield` is
+/// suppressed, so the coroutine would not be re-queued for running too long
+/// before the end of the section. this could reduce the jitter of holding a
+/// hot lock.
+///
+/// an explicit `yield_now` and blocking operations like IO, sleep or
+/// waiting for a lock would still yield the coroutine, there is no way to
+/// avoid that.
+///
+/// be careful that a section blocks with a hot lock held would stall all
+/// the other coroutines waiting on the lock, and a section that busy loops
+/// on a condition without yielding, which another coroutine on the same
+/// worker would satisfy, is a deadlock.
+///
+/// it just runs the closure if not called in a coroutine context
+pub fn critical<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let local = match get_co_local_data() {
+        Some(local) => local,
+        None => return f(),
+    };
+    let critical = &unsafe { &*local.as_ptr() }.get_co().inner.critical;
+    critical.fetch_add(1, Ordering::Relaxed);
+    let _guard = CriticalGuard(critical);
+    f()
+}
+
+// the park reason slot of the current coroutine
+#[inline]
+pub(crate) fn current_park_reason() -> Option<&'static AtomicU64> {
+    // the coroutine handle outlives its running code
+    get_co_local_data().map(|local| &unsafe { &*local.as_ptr() }.get_co().inner.park_reason)
+}
+
+// if the current coroutine is running in a critical section
+#[inline]
+pub(crate) fn in_critical() -> bool {
+    match get_co_local_data() {
+        None => false,
+        Some(local) => {
+            unsafe { local.as_ref() }
+                .get_co()
+                .inner
+                .critical
+                .load(Ordering::Relaxed)
+                != 0
+        }
+    }
+}
+
 // windows use delay drop instead
 #[cfg(unix)]
 pub(crate) fn co_get_handle(co: &CoroutineImpl) -> Coroutine {
@@ -495,6 +751,13 @@ fn park_timeout_impl(dur: Option<Duration>) {
 }
 
 /// block the current coroutine until it's get unparked
+///
+/// it has the same token semantics as `std::thread::park`, an `unpark`
+/// before `park` is remembered and the `park` returns immediately. the
+/// worker thread is not blocked, it runs other coroutines meanwhile.
+/// the caller should re-check its condition in a loop, because a token
+/// left by an earlier `unpark` could wake it up spuriously. it does
+/// nothing in thread context
 pub fn park() {
     park_timeout_impl(None);
 }
@@ -507,6 +770,13 @@ pub fn park_timeout(dur: Duration) {
 /// run the coroutine
 #[inline]
 pub(crate) fn run_coroutine(mut co: CoroutineImpl) {
+    // a pinned coroutine can only run on its own worker
+    let pinned = co_pinned_worker(&co);
+    if pinned != NOT_PINNED && pinned != get_worker_id() {
+        return get_scheduler().schedule_pinned(pinned, co);
+    }
+
+    reset_slice();
     match co.resume() {
         Some(ev) => ev.subscribe(co),
         None => {
@@ -517,6 +787,7 @@ pub(crate) fn run_coroutine(mut co: CoroutineImpl) {
             if let Some(panic) = co.get_panic_data() {
                 join.set_panic_data(panic);
             }
+            local.clear_local_data();
             // trigger the join here
             join.trigger();
             Done::drop_coroutine(co);
@@ -3,17 +3,98 @@ use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use crate::cancel::Cancel;
+use crate::cancel::trigger_cancel_panic;
 use crate::coroutine_impl::{
-    current_cancel_data, run_coroutine, Coroutine, CoroutineImpl, EventSource,
+    current_cancel_data, is_coroutine, run_coroutine, Coroutine, CoroutineImpl, EventSource,
 };
 use crate::join::JoinHandle;
-use crate::scoped::spawn_unsafe;
+use crate::local::set_select_arm;
+use crate::scoped::{spawn_unsafe, spawn_unsafe_local};
 use crate::sync::Mutex;
 use crate::sync::{AtomicOption, Blocker};
-use crate::yield_now::yield_with;
+use crate::yield_now::{yield_plain, yield_with};
 
 use crossbeam::queue::SegQueue as Queue;
+use crossbeam::utils::Backoff;
+
+// the claim is not held by anyone
+const CLAIM_FREE: usize = usize::MAX;
+// the low bit of the claim state is set when the holder has won
+const CLAIM_WON: usize = 1;
+// the claim id of the select caller, used by the default arms
+const CLAIM_CALLER: usize = usize::MAX >> 2;
+
+/// the claim of an exclusive select, shared by all the select coroutines
+///
+/// a select coroutine holds the claim only when it's running, and gives it
+/// up when blocked. the first one that finishes its top half wins the claim,
+/// and the others are canceled when they try to take it back, so at most one
+/// of them could consume anything from the waited objects
+#[derive(Clone, Copy)]
+pub(crate) struct SelectClaim {
+    // the claim state that lives in the cqueue
+    state: *const AtomicUsize,
+    // id of the select coroutine
+    id: usize,
+}
+
+unsafe impl Send for SelectClaim {}
+
+impl SelectClaim {
+    #[inline]
+    fn state(&self) -> &AtomicUsize {
+        // the cqueue outlives all the select coroutines
+        unsafe { &*self.state }
+    }
+
+    /// give up the claim before the select coroutine blocks
+    #[inline]
+    pub(crate) fn release(&self) {
+        let busy = self.id << 1;
+        self.state()
+            .compare_exchange(busy, CLAIM_FREE, Ordering::AcqRel, Ordering::Relaxed)
+            .ok();
+    }
+
+    /// take the claim back, return false if another one has won
+    pub(crate) fn acquire(&self) -> bool {
+        let busy = self.id << 1;
+        let backoff = Backoff::new();
+        loop {
+            match self.state().compare_exchange_weak(
+                CLAIM_FREE,
+                busy,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return true,
+                Err(CLAIM_FREE) => {}
+                // already held or won by self
+                Err(s) if s >> 1 == self.id => return true,
+                Err(s) if s & CLAIM_WON != 0 => return false,
+                // another one is running, it would release it soon
+                Err(_) if is_coroutine() => yield_plain(),
+                Err(_) => backoff.snooze(),
+            }
+        }
+    }
+
+    /// the select coroutine finished its top half while holding the claim
+    #[inline]
+    fn win(&self) {
+        self.state()
+            .store(self.id << 1 | CLAIM_WON, Ordering::Release);
+    }
+}
+
+// release the claim when the select coroutine exits, even by a panic
+struct ClaimGuard(SelectClaim);
+
+impl Drop for ClaimGuard {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
 
 /// This enumeration is the list of the possible reasons that `poll`
 /// could not return Event when called.
@@ -106,6 +187,9 @@ impl<'a> EventSender<'a> {
     pub fn send(&self, extra: usize) {
         let cancel = current_cancel_data();
         cancel.check_cancel();
+        if let Some(claim) = crate::local::select_claim() {
+            claim.win();
+        }
         self.extra.store(extra, Ordering::Relaxed);
         yield_with(self);
     }
@@ -124,10 +208,6 @@ impl<'a> EventSource for EventSender<'a> {
             w.unpark();
         }
     }
-
-    fn yield_back(&self, _cancel: &'static Cancel) {
-        // ignore the cancel to let the bottom half get processed
-    }
 }
 
 impl<'a> Drop for EventSender<'a> {
@@ -161,6 +241,14 @@ pub struct Cqueue {
     total: AtomicUsize,
     // panic status
     is_panicking: AtomicBool,
+    // the ready events reported by `ready` but not completed yet
+    peeked: Mutex<Vec<Event>>,
+    // run the added select coroutines in the caller context first
+    run_inline: AtomicBool,
+    // only one select coroutine could run its top half at a time
+    exclusive: AtomicBool,
+    // the claim state of the exclusive select coroutines
+    claim: AtomicUsize,
 }
 
 impl Cqueue {
@@ -177,7 +265,30 @@ impl Cqueue {
             extra: 0.into(),
             cqueue: self,
         };
-        let h = unsafe { spawn_unsafe(move || f(sender)) };
+        let claim = if self.exclusive.load(Ordering::Relaxed) {
+            Some(SelectClaim {
+                state: &self.claim,
+                id: sender.id,
+            })
+        } else {
+            None
+        };
+        let arm = move || {
+            set_select_arm(claim);
+            let _guard = claim.map(ClaimGuard);
+            // another one has already won, don't run the top half at all
+            if let Some(claim) = claim {
+                if !claim.acquire() {
+                    trigger_cancel_panic();
+                }
+            }
+            f(sender)
+        };
+        let h = if self.run_inline.load(Ordering::Relaxed) {
+            unsafe { spawn_unsafe_local(arm) }
+        } else {
+            unsafe { spawn_unsafe(arm) }
+        };
         let co = h.coroutine().clone();
         self.cnt.fetch_add(1, Ordering::Relaxed);
 
@@ -186,6 +297,43 @@ impl Cqueue {
         Selector { co }
     }
 
+    /// run the select coroutines that are added later in the caller context
+    /// until they block, instead of scheduling them to the workers
+    ///
+    /// so the select coroutines that are ready at once have generated their
+    /// events when `add` returns, and `ready_tokens` is a nonblocking probe
+    /// of all of them
+    pub fn set_run_inline(&self, inline: bool) {
+        self.run_inline.store(inline, Ordering::Relaxed);
+    }
+
+    /// make the select coroutines that are added later exclusive
+    ///
+    /// only one of them could run its top half at a time, and once one of
+    /// them finished the top half, the others are canceled before they could
+    /// consume anything. this is what `select!` uses, so a losing arm never
+    /// takes data from the object it's waiting on
+    pub fn set_exclusive(&self, exclusive: bool) {
+        self.exclusive.store(exclusive, Ordering::Relaxed);
+    }
+
+    /// stop the exclusive select coroutines for the caller
+    ///
+    /// return false if one of them has already finished its top half, then
+    /// its event should be polled. otherwise none of them could finish the
+    /// top half any more, this is used by the `default` arms of `select!`
+    pub fn claim_default(&self) -> bool {
+        let claim = SelectClaim {
+            state: &self.claim,
+            id: CLAIM_CALLER,
+        };
+        if !claim.acquire() {
+            return false;
+        }
+        claim.win();
+        true
+    }
+
     /// register a select coroutine with the cqueue
     /// should use `cqueue_add` and `cqueue_add_oneshot` macros to
     /// create select coroutines correctly
@@ -204,11 +352,11 @@ impl Cqueue {
         }
 
         use generator::Error;
-        match self.selectors.lock().unwrap()[id]
+        // don't hold the lock when resume the panic
+        let handle = self.selectors.lock().unwrap()[id]
             .take()
-            .expect("join handler not set")
-            .join()
-        {
+            .expect("join handler not set");
+        match handle.join() {
             Ok(_) => {}
             Err(panic) => {
                 if let Some(err) = panic.downcast_ref::<Error>() {
@@ -223,27 +371,24 @@ impl Cqueue {
         }
     }
 
-    /// poll an event that is ready to process
-    /// when the event is returned the bottom half is already run
-    /// the API is "completion" mode
-    /// if any panic in select coroutine detected during the poll
-    /// it will propagate the panic to the caller
-    pub fn poll(&self, timeout: Option<Duration>) -> Result<Event, PollError> {
-        macro_rules! run_ev {
-            ($ev:ident) => {{
-                if $ev.kind == EventKind::Done {
-                    self.check_panic($ev.id);
-                    continue;
-                }
-                $ev.continue_bottom();
-                return Ok($ev);
-            }};
+    // pop a ready event from the queue, the done events are consumed
+    fn pop_ready(&self) -> Option<Event> {
+        while let Some(ev) = self.ev_queue.pop() {
+            if ev.kind == EventKind::Done {
+                self.check_panic(ev.id);
+                continue;
+            }
+            return Some(ev);
         }
+        None
+    }
 
+    // wait for a ready event, the bottom half is not run
+    fn wait_ready(&self, timeout: Option<Duration>) -> Result<Event, PollError> {
         let deadline = timeout.map(|dur| Instant::now() + dur);
         loop {
-            match self.ev_queue.pop() {
-                Some(mut ev) => run_ev!(ev),
+            match self.pop_ready() {
+                Some(ev) => return Ok(ev),
                 None => {
                     if self.cnt.load(Ordering::Relaxed) == 0 {
                         return Err(PollError::Finished);
@@ -255,16 +400,16 @@ impl Cqueue {
             // register the waiter
             self.to_wake.swap(cur.clone(), Ordering::Release);
             // re-check the queue
-            match self.ev_queue.pop() {
+            match self.pop_ready() {
                 None => {
                     cur.park(timeout).ok();
                 }
-                Some(mut ev) => {
+                Some(ev) => {
                     if let Some(w) = self.to_wake.take(Ordering::Relaxed) {
                         w.unpark();
                     }
                     cur.park(timeout).ok();
-                    run_ev!(ev);
+                    return Ok(ev);
                 }
             }
 
@@ -275,12 +420,89 @@ impl Cqueue {
             }
         }
     }
+
+    /// poll an event that is ready to process
+    /// when the event is returned the bottom half is already run
+    /// the API is "completion" mode
+    /// if any panic in select coroutine detected during the poll
+    /// it will propagate the panic to the caller
+    pub fn poll(&self, timeout: Option<Duration>) -> Result<Event, PollError> {
+        // the events reported by `ready` go first
+        let peeked = {
+            let mut peeked = self.peeked.lock().unwrap();
+            if peeked.is_empty() {
+                None
+            } else {
+                Some(peeked.remove(0))
+            }
+        };
+
+        let mut ev = match peeked {
+            Some(ev) => ev,
+            None => self.wait_ready(timeout)?,
+        };
+        ev.continue_bottom();
+        Ok(ev)
+    }
+
+    /// wait for a ready select coroutine without running its bottom half
+    ///
+    /// return the token of the earliest ready one, all the other select
+    /// coroutines that are ready at the moment can be found by `ready_tokens`.
+    /// the event is not consumed, call `complete` with the token to run the
+    /// bottom half, or `poll` would run it in the ready order.
+    ///
+    /// a select coroutine is ready once its top half is done, so it would
+    /// never become not ready again. but other select coroutines may become
+    /// ready right after the call, a custom prioritization should re-check
+    /// `ready_tokens` before each `complete`
+    pub fn ready(&self, timeout: Option<Duration>) -> Result<usize, PollError> {
+        if let Some(ev) = self.peeked.lock().unwrap().first() {
+            return Ok(ev.token);
+        }
+        let ev = self.wait_ready(timeout)?;
+        let token = ev.token;
+        self.peeked.lock().unwrap().push(ev);
+        Ok(token)
+    }
+
+    /// return the tokens of all the ready select coroutines without blocking
+    ///
+    /// the bottom halves are not run, see `ready` for details
+    pub fn ready_tokens(&self) -> Vec<usize> {
+        // don't hold the lock when pop, it may resume a panic
+        while let Some(ev) = self.pop_ready() {
+            self.peeked.lock().unwrap().push(ev);
+        }
+        let peeked = self.peeked.lock().unwrap();
+        peeked.iter().map(|ev| ev.token).collect()
+    }
+
+    /// run the bottom half of the ready select coroutine with the token
+    ///
+    /// return `None` if there is no such ready select coroutine reported
+    /// by `ready` or `ready_tokens`
+    pub fn complete(&self, token: usize) -> Option<Event> {
+        let mut ev = {
+            let mut peeked = self.peeked.lock().unwrap();
+            let i = peeked.iter().position(|ev| ev.token == token)?;
+            peeked.remove(i)
+        };
+        ev.continue_bottom();
+        Some(ev)
+    }
 }
 
 impl Drop for Cqueue {
     // this would cancel all unfinished select coroutines
     // and wait until all of them return back
     fn drop(&mut self) {
+        // the cqueue is dropped during unwinding, the select coroutines must
+        // still be cleaned up, but their panics can't be propagated any more
+        if std::thread::panicking() {
+            self.is_panicking.store(true, Ordering::Relaxed);
+        }
+
         // first cancel all the select coroutines if they are running
         self.selectors
             .lock()
@@ -296,7 +518,7 @@ impl Drop for Cqueue {
         //     return;
         // }
 
-        // run the rest event
+        // resume the rest events, the canceled ones unwind without the bottom half
         loop {
             match self.poll(None) {
                 Ok(_) => {}
@@ -323,6 +545,10 @@ where
         selectors: Mutex::new(Vec::new()),
         total: AtomicUsize::new(0),
         is_panicking: AtomicBool::new(false),
+        peeked: Mutex::new(Vec::new()),
+        run_inline: AtomicBool::new(false),
+        exclusive: AtomicBool::new(false),
+        claim: AtomicUsize::new(CLAIM_FREE),
     };
     f(&cqueue)
 }
@@ -0,0 +1,166 @@
+//! Filesystem primitives
+//!
+//! regular files are always ready for read and write, so the io on them is
+//! done directly. only the potentially slow disk sync and the positional
+//! io are offloaded to a small pool of blocking threads so that the worker
+//! can keep running other coroutines.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::blocking::run_blocking;
+#[cfg(unix)]
+use crate::coroutine_impl::is_coroutine;
+
+/// A reference to an open file on the filesystem
+///
+/// a thin wrapper of `std::fs::File` with coroutine friendly sync methods
+pub struct File {
+    inner: fs::File,
+}
+
+impl File {
+    /// open a file in read-only mode, see `std::fs::File::open`
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        fs::File::open(path).map(File::from_std)
+    }
+
+    /// open a file in write-only mode, see `std::fs::File::create`
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        fs::File::create(path).map(File::from_std)
+    }
+
+    /// convert a `std::fs::File` to a `File`
+    pub fn from_std(file: fs::File) -> File {
+        File { inner: file }
+    }
+
+    /// return the inner `std::fs::File`
+    pub fn into_std(self) -> fs::File {
+        self.inner
+    }
+
+    /// get a reference to the inner `std::fs::File`
+    pub fn get_ref(&self) -> &fs::File {
+        &self.inner
+    }
+
+    /// sync all the data and metadata to disk, see `std::fs::File::sync_all`
+    ///
+    /// in coroutine context the current coroutine is parked until the sync
+    /// is done, while the worker keeps running other coroutines
+    pub fn sync_all(&self) -> io::Result<()> {
+        let file = self.inner.try_clone()?;
+        run_blocking(move || file.sync_all())
+    }
+
+    /// sync the data to disk without the metadata, see `std::fs::File::sync_data`
+    ///
+    /// in coroutine context the current coroutine is parked until the sync
+    /// is done, while the worker keeps running other coroutines
+    pub fn sync_data(&self) -> io::Result<()> {
+        let file = self.inner.try_clone()?;
+        run_blocking(move || file.sync_data())
+    }
+
+    /// read from the given offset, see `std::os::unix::fs::FileExt::read_at`
+    ///
+    /// the file cursor used by `read` is not changed, so it's safe for
+    /// concurrent reads from multiple coroutines. return `Ok(0)` if the
+    /// offset is past the end of file. in coroutine context the current
+    /// coroutine is parked until the read is done
+    #[cfg(unix)]
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        use std::os::unix::fs::FileExt;
+
+        if !is_coroutine() {
+            return self.inner.read_at(buf, offset);
+        }
+        let file = self.inner.try_clone()?;
+        let len = buf.len();
+        let data = run_blocking(move || {
+            let mut data = vec![0; len];
+            let n = file.read_at(&mut data, offset)?;
+            data.truncate(n);
+            Ok(data)
+        })?;
+        buf[..data.len()].copy_from_slice(&data);
+        Ok(data.len())
+    }
+
+    /// write at the given offset, see `std::os::unix::fs::FileExt::write_at`
+    ///
+    /// the file cursor used by `write` is not changed. in coroutine context
+    /// the current coroutine is parked until the write is done
+    #[cfg(unix)]
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> io::Result<usize> {
+        use std::os::unix::fs::FileExt;
+
+        if !is_coroutine() {
+            return self.inner.write_at(buf, offset);
+        }
+        let file = self.inner.try_clone()?;
+        let data = buf.to_vec();
+        run_blocking(move || file.write_at(&data, offset))
+    }
+
+    /// return the metadata of the file, see `std::fs::File::metadata`
+    pub fn metadata(&self) -> io::Result<fs::Metadata> {
+        self.inner.metadata()
+    }
+
+    /// truncate or extend the file, see `std::fs::File::set_len`
+    pub fn set_len(&self, size: u64) -> io::Result<()> {
+        self.inner.set_len(size)
+    }
+}
+
+impl From<fs::File> for File {
+    fn from(file: fs::File) -> Self {
+        File::from_std(file)
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for File {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawHandle for File {
+    fn as_raw_handle(&self) -> std::os::windows::io::RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
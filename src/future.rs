@@ -0,0 +1,126 @@
+//! Bridge to the `std::future::Future` based ecosystem
+//!
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::cancel::trigger_cancel_panic;
+use crate::park::ParkError;
+use crate::sync::Blocker;
+
+// wake up the parked coroutine or thread
+struct BlockerWaker(Arc<Blocker>);
+
+impl Wake for BlockerWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// run the future to completion on the current coroutine
+///
+/// the coroutine is parked while the future is pending, and is scheduled
+/// again when the future's waker is waked. it also works in thread context
+/// by parking the thread.
+///
+/// this is not an executor, the future is only polled by the current
+/// coroutine. so the future must not assume a particular reactor, e.g. a
+/// future that needs the tokio runtime would never be waked up.
+///
+/// # Examples
+///
+/// ```rust
+/// use may::future::block_on_future;
+///
+/// let h = may::go!(|| block_on_future(async { 1 + 2 }));
+/// assert_eq!(h.join().unwrap(), 3);
+/// ```
+pub fn block_on_future<F: Future>(f: F) -> F::Output {
+    let mut f = f;
+    // the future is shadowed and never moved again
+    let mut f = unsafe { Pin::new_unchecked(&mut f) };
+
+    // the same waker is used for all the polls, a stale wake only causes
+    // an extra poll
+    let blocker = Blocker::current();
+    let waker = Waker::from(Arc::new(BlockerWaker(blocker.clone())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(ret) = f.as_mut().poll(&mut cx) {
+            return ret;
+        }
+        // a wake before park would make the park return immediately
+        if blocker.park(None) == Err(ParkError::Canceled) {
+            trigger_cancel_panic();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    // a future that is ready after waked by another thread
+    struct Delay {
+        polls: Arc<AtomicUsize>,
+        started: bool,
+        done: Arc<AtomicBool>,
+    }
+
+    impl Future for Delay {
+        type Output = usize;
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+            let polls = self.polls.fetch_add(1, Ordering::Relaxed) + 1;
+            if self.done.load(Ordering::Acquire) {
+                return Poll::Ready(polls);
+            }
+            if !self.started {
+                self.started = true;
+                let waker = cx.waker().clone();
+                let done = self.done.clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(50));
+                    done.store(true, Ordering::Release);
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }
+    }
+
+    fn delay() -> Delay {
+        Delay {
+            polls: Arc::new(AtomicUsize::new(0)),
+            started: false,
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    #[test]
+    fn park_until_waked() {
+        // no busy polling, only polled again after waked
+        let h = go!(|| block_on_future(delay()));
+        assert_eq!(h.join().unwrap(), 2);
+        // thread context
+        assert_eq!(block_on_future(delay()), 2);
+    }
+
+    #[test]
+    fn async_block() {
+        let h = go!(|| block_on_future(async {
+            let a = delay().await;
+            let b = async { 10 }.await;
+            a + b
+        }));
+        assert_eq!(h.join().unwrap(), 12);
+    }
+}
@@ -2,7 +2,7 @@ use std::io;
 use std::sync::atomic::Ordering;
 
 use super::sys::{Selector, SysEvent};
-use crate::scheduler::WORKER_ID;
+use crate::scheduler::{run_tick, WORKER_ID};
 
 /// Single threaded IO event loop.
 pub struct EventLoop {
@@ -28,6 +28,11 @@ impl EventLoop {
         // wake up every 1 second
         let mut next_expire = Some(1_000_000_000);
         loop {
+            // wake up in time for the next tick
+            if let Some(tick) = run_tick(id) {
+                let tick = tick.as_nanos().min(u64::MAX as u128) as u64;
+                next_expire = next_expire.map(|t| t.min(tick));
+            }
             next_expire = match self.selector.select(id, &mut events_buf, next_expire) {
                 Ok(v) => v.or(Some(1_000_000_000)),
                 Err(e) => {
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::io::{self, IoSlice, Read, Write};
+
+/// default max frame size, 16MB
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Length-prefixed framing over a stream
+///
+/// each frame is a 4-byte big-endian length followed by the payload. the
+/// frames larger than the max frame size are rejected before allocating
+/// the buffer, so a malicious length can't cause a huge allocation.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::Cursor;
+/// use may::io::Framed;
+///
+/// let mut w = Framed::new(Vec::new());
+/// w.write_frame(b"hello").unwrap();
+/// assert_eq!(w.get_ref(), b"\x00\x00\x00\x05hello");
+///
+/// let mut r = Framed::new(Cursor::new(w.into_inner()));
+/// assert_eq!(r.read_frame().unwrap(), b"hello");
+/// ```
+pub struct Framed<S> {
+    stream: S,
+    max_frame_size: usize,
+}
+
+impl<S> Framed<S> {
+    /// create a framed stream with the default max frame size
+    pub fn new(stream: S) -> Self {
+        Framed {
+            stream,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    /// set the max frame size for both read and write
+    pub fn set_max_frame_size(&mut self, size: usize) {
+        self.max_frame_size = size;
+    }
+
+    /// return the max frame size
+    pub fn max_frame_size(&self) -> usize {
+        self.max_frame_size
+    }
+
+    /// get a reference to the inner stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// get a mutable reference to the inner stream
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// return the inner stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Read> Framed<S> {
+    /// read a whole frame, park the coroutine as needed
+    ///
+    /// return `InvalidData` if the frame length exceeds the max frame size,
+    /// and `UnexpectedEof` if the stream is closed in the middle of a frame
+    pub fn read_frame(&mut self) -> io::Result<Vec<u8>> {
+        let mut len = [0; 4];
+        self.stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > self.max_frame_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame size {} exceeds the max {}", len, self.max_frame_size),
+            ));
+        }
+        let mut buf = vec![0; len];
+        self.stream.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl<S: Write> Framed<S> {
+    /// write a whole frame, park the coroutine as needed
+    ///
+    /// the length and the payload are written together by a vectored write
+    /// so that they usually go out in one segment
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        if frame.len() > self.max_frame_size || frame.len() > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame size {} exceeds the max {}",
+                    frame.len(),
+                    self.max_frame_size
+                ),
+            ));
+        }
+        let len = (frame.len() as u32).to_be_bytes();
+
+        // write all the two slices, advance them on partial writes
+        let mut written = 0;
+        let total = len.len() + frame.len();
+        while written < total {
+            let ret = if written < len.len() {
+                let bufs = [IoSlice::new(&len[written..]), IoSlice::new(frame)];
+                self.stream.write_vectored(&bufs)
+            } else {
+                self.stream.write(&frame[written - len.len()..])
+            };
+            match ret {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => written += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.stream.flush()
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Framed<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Framed")
+            .field("stream", &self.stream)
+            .field("max_frame_size", &self.max_frame_size)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn round_trip() {
+        let mut w = Framed::new(Vec::new());
+        w.write_frame(b"hello").unwrap();
+        w.write_frame(b"").unwrap();
+        w.write_frame(&[7; 1000]).unwrap();
+
+        let mut r = Framed::new(Cursor::new(w.into_inner()));
+        assert_eq!(r.read_frame().unwrap(), b"hello");
+        assert_eq!(r.read_frame().unwrap(), b"");
+        assert_eq!(r.read_frame().unwrap(), vec![7; 1000]);
+        let err = r.read_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn max_frame_size() {
+        let mut w = Framed::new(Vec::new());
+        w.set_max_frame_size(4);
+        let err = w.write_frame(b"hello").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(w.get_ref().is_empty());
+
+        // a huge length is rejected before reading the payload
+        let mut r = Framed::new(Cursor::new(vec![0xff, 0xff, 0xff, 0xff, 1]));
+        let err = r.read_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let mut r = Framed::new(Cursor::new(b"\x00\x00\x00\x05hel".to_vec()));
+        let err = r.read_frame().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -12,18 +12,31 @@ pub(crate) mod sys;
 pub mod co_io_err;
 
 mod event_loop;
+mod framed;
+mod prepend;
 
 use std::io;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::coroutine_impl::is_coroutine;
+use crate::coroutine_impl::current_cancel_data;
+use crate::local::get_co_local_data;
 
 pub(crate) use self::event_loop::EventLoop;
+pub use self::framed::{Framed, DEFAULT_MAX_FRAME_SIZE};
+pub use self::prepend::Prepend;
+#[cfg(unix)]
+pub use self::sys::async_fd::AsyncFd;
+#[cfg(unix)]
+pub use self::sys::cancel::IoToken;
 pub use self::sys::co_io::CoIo;
 #[cfg(unix)]
 pub use self::sys::wait_io::WaitIo;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) use self::sys::watch_urgent;
 pub(crate) use self::sys::{add_socket, cancel, net, IoData, Selector};
+#[cfg(unix)]
+pub(crate) use self::sys::{bytes_readable, get_sockopt, set_sockopt};
 
 pub trait AsIoData {
     fn as_io_data(&self) -> &IoData;
@@ -73,14 +86,25 @@ impl IoContext {
         F: FnOnce(bool) -> io::Result<()>,
     {
         // thread context
-        if !is_coroutine() {
-            {
+        let local = match get_co_local_data() {
+            Some(local) => local,
+            None => {
                 if self.blocked_io.load(Ordering::Relaxed) {
                     f(false)?;
                     self.blocked_io.store(false, Ordering::Relaxed);
                 }
                 return Ok(false);
             }
+        };
+
+        // a canceled select arm should not issue any new io request
+        // or the data consumed by the early nonblocking try would be lost
+        // but don't panic again if we are already unwinding
+        if unsafe { local.as_ref() }.is_select_arm() {
+            let cancel = current_cancel_data();
+            if cancel.is_canceled() && !std::thread::panicking() {
+                cancel.check_cancel();
+            }
         }
 
         // for coroutine context
@@ -95,6 +119,11 @@ impl IoContext {
     pub fn set_nonblocking(&self, nonblocking: bool) {
         self.nonblocking.store(nonblocking, Ordering::Relaxed);
     }
+
+    // set the nonblocking mode and return the old one
+    pub fn swap_nonblocking(&self, nonblocking: bool) -> bool {
+        self.nonblocking.swap(nonblocking, Ordering::Relaxed)
+    }
 }
 
 // an option type that implement deref
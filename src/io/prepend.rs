@@ -0,0 +1,187 @@
+use std::fmt;
+use std::io::{self, BufRead, IoSlice, Read, Write};
+
+/// A reader that serves the pushed back bytes before the inner reader
+///
+/// this is useful to hand a logically rewound stream to a sub-parser after
+/// peeking a prefix. once the prepended bytes are exhausted all the reads
+/// are delegated to the inner reader, so a coroutine stream would still
+/// yield as usual. writes are always passed through to the inner stream.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::{Cursor, Read};
+/// use may::io::Prepend;
+///
+/// let mut r = Prepend::new(b"hello ".to_vec(), Cursor::new(b"world"));
+/// let mut s = String::new();
+/// r.read_to_string(&mut s).unwrap();
+/// assert_eq!(s, "hello world");
+/// ```
+pub struct Prepend<R> {
+    buf: Vec<u8>,
+    pos: usize,
+    inner: R,
+}
+
+impl<R> Prepend<R> {
+    /// create a reader that serves `prefix` first and then `inner`
+    pub fn new(prefix: Vec<u8>, inner: R) -> Self {
+        Prepend {
+            buf: prefix,
+            pos: 0,
+            inner,
+        }
+    }
+
+    /// push back more bytes in front of the unread ones
+    pub fn push_back(&mut self, bytes: &[u8]) {
+        if self.pos >= bytes.len() {
+            // reuse the consumed space
+            self.pos -= bytes.len();
+            self.buf[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        } else {
+            self.buf.splice(..self.pos, bytes.iter().copied());
+            self.pos = 0;
+        }
+    }
+
+    /// return the prepended bytes that are not read yet
+    pub fn prepended(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// get a reference to the inner reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// get a mutable reference to the inner reader
+    ///
+    /// reading from it directly would skip the unread prepended bytes
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// return the unread prepended bytes and the inner reader
+    pub fn into_parts(mut self) -> (Vec<u8>, R) {
+        self.buf.drain(..self.pos);
+        (self.buf, self.inner)
+    }
+}
+
+impl<R: Read> Read for Prepend<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let prepended = self.prepended();
+        if prepended.is_empty() {
+            return self.inner.read(buf);
+        }
+        let n = prepended.len().min(buf.len());
+        buf[..n].copy_from_slice(&prepended[..n]);
+        self.advance(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Prepend<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos < self.buf.len() {
+            return Ok(&self.buf[self.pos..]);
+        }
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if self.pos < self.buf.len() {
+            self.advance(amt);
+        } else {
+            self.inner.consume(amt);
+        }
+    }
+}
+
+impl<R> Prepend<R> {
+    // consume the prepended bytes, release the buffer when all are read
+    fn advance(&mut self, amt: usize) {
+        self.pos += amt;
+        if self.pos >= self.buf.len() {
+            self.buf = Vec::new();
+            self.pos = 0;
+        }
+    }
+}
+
+impl<R: Write> Write for Prepend<R> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.inner.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Prepend<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Prepend")
+            .field("prepended", &self.prepended().len())
+            .field("inner", &self.inner)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_prefix_then_inner() {
+        let mut r = Prepend::new(b"abc".to_vec(), Cursor::new(b"def".to_vec()));
+        let mut buf = [0; 2];
+        assert_eq!(r.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf, b"ab");
+        assert_eq!(r.prepended(), b"c");
+        // never mix the prefix and the inner in one read
+        assert_eq!(r.read(&mut buf).unwrap(), 1);
+        assert_eq!(&buf[..1], b"c");
+        assert_eq!(r.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf, b"de");
+        assert_eq!(r.read(&mut buf).unwrap(), 1);
+        // inner EOF
+        assert_eq!(r.read(&mut buf).unwrap(), 0);
+        assert_eq!(r.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn push_back() {
+        let mut r = Prepend::new(Vec::new(), Cursor::new(b"world".to_vec()));
+        let mut buf = [0; 5];
+        r.read_exact(&mut buf).unwrap();
+        r.push_back(b"world");
+        r.push_back(b"hello ");
+        let mut s = String::new();
+        r.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "hello world");
+
+        let mut r = Prepend::new(b"xxabc".to_vec(), Cursor::new(Vec::new()));
+        r.read_exact(&mut buf[..2]).unwrap();
+        // reuse the consumed space
+        r.push_back(b"yy");
+        let (rest, _) = r.into_parts();
+        assert_eq!(rest, b"yyabc");
+    }
+
+    #[test]
+    fn buf_read() {
+        let r = Prepend::new(b"a\nb".to_vec(), Cursor::new(b"c\nd".to_vec()));
+        let lines: Vec<_> = r.lines().map(|l| l.unwrap()).collect();
+        // the line continues from the prefix to the inner
+        assert_eq!(lines, ["a", "bc", "d"]);
+    }
+}
@@ -0,0 +1,178 @@
+//! # Readiness Wrapper for raw fds
+//! `AsyncFd` registers an arbitrary fd to the selector so that a coroutine
+//! can park until the fd is readable or writable
+//!
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::Ordering;
+
+use crate::coroutine_impl::{co_get_handle, is_coroutine, CoroutineImpl, EventSource};
+use crate::io as io_impl;
+use crate::park_reason::{self, ParkReason};
+use crate::yield_now::yield_with;
+
+struct FdWait<'a> {
+    io_data: &'a io_impl::IoData,
+}
+
+impl<'a> EventSource for FdWait<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
+        let io_data = (*self.io_data).clone();
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if io_data.io_flag.load(Ordering::Acquire) {
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
+
+// poll the fd for the events, return true if any event is ready
+fn poll_fd(fd: RawFd, events: libc::c_short, timeout: libc::c_int) -> io::Result<bool> {
+    let mut fds = libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    };
+    loop {
+        match unsafe { libc::poll(&mut fds, 1, timeout) } {
+            n if n >= 0 => return Ok(n > 0),
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+/// A wrapper that drives the readiness of a raw fd by the coroutine runtime
+///
+/// it's used to integrate fds like eventfd, timerfd, inotify or the sockets
+/// of a third-party library. the fd is registered to the selector on
+/// creation and deregistered on drop, but it's not owned, closing the fd
+/// is up to the caller after the wrapper is dropped.
+///
+/// the wrapper only waits for the readiness, the actual io is done on the
+/// fd by the caller, which should be in nonblocking mode so that a spurious
+/// wakeup would not block the worker thread.
+///
+/// the hangup and error conditions are reported as ready, the following
+/// io on the fd would see them.
+#[derive(Debug)]
+pub struct AsyncFd {
+    io: io_impl::IoData,
+}
+
+impl AsyncFd {
+    /// register the fd to the coroutine runtime
+    pub fn new(fd: RawFd) -> io::Result<Self> {
+        struct Fd(RawFd);
+        impl AsRawFd for Fd {
+            fn as_raw_fd(&self) -> RawFd {
+                self.0
+            }
+        }
+
+        let io = io_impl::add_socket(&Fd(fd))?;
+        Ok(AsyncFd { io })
+    }
+
+    /// park until the fd is readable
+    pub fn wait_readable(&self) -> io::Result<()> {
+        self.wait(libc::POLLIN)
+    }
+
+    /// park until the fd is writable
+    pub fn wait_writable(&self) -> io::Result<()> {
+        self.wait(libc::POLLOUT)
+    }
+
+    fn wait(&self, events: libc::c_short) -> io::Result<()> {
+        let fd = self.io.fd;
+        if !is_coroutine() {
+            // block the thread
+            poll_fd(fd, events, -1)?;
+            return Ok(());
+        }
+
+        loop {
+            // the selector event only tells there is something new on the
+            // fd, so check the direction by polling after clear the flag
+            self.io.reset();
+            if poll_fd(fd, events, 0)? {
+                return Ok(());
+            }
+            // an event comes after the check
+            if self.io.io_flag.load(Ordering::Acquire) {
+                continue;
+            }
+            let _reason = park_reason::record(match events {
+                libc::POLLIN => ParkReason::IoRead(park_reason::detail(|| fd as u64)),
+                _ => ParkReason::IoWrite(park_reason::detail(|| fd as u64)),
+            });
+            yield_with(&FdWait { io_data: &self.io });
+        }
+    }
+}
+
+impl io_impl::AsIoData for AsyncFd {
+    fn as_io_data(&self) -> &io_impl::IoData {
+        &self.io
+    }
+}
+
+impl AsRawFd for AsyncFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.fd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn pipe_readable() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let (rd, wr) = (fds[0], fds[1]);
+        unsafe { libc::fcntl(rd, libc::F_SETFL, libc::O_NONBLOCK) };
+
+        let h = go!(move || {
+            let fd = AsyncFd::new(rd).unwrap();
+            fd.wait_readable().unwrap();
+            let mut buf = [0u8; 8];
+            let n = unsafe { libc::read(rd, buf.as_mut_ptr() as *mut _, buf.len()) };
+            buf[..n as usize].to_vec()
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert!(!h.is_done());
+
+        go!(move || {
+            let fd = AsyncFd::new(wr).unwrap();
+            fd.wait_writable().unwrap();
+            assert_eq!(unsafe { libc::write(wr, b"hi".as_ptr() as *const _, 2) }, 2);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(h.join().unwrap(), b"hi");
+
+        unsafe {
+            libc::close(rd);
+            libc::close(wr);
+        }
+    }
+}
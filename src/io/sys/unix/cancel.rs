@@ -1,10 +1,14 @@
+use std::fmt;
+use std::io;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use super::EventData;
 use crate::cancel::CancelIo;
+use crate::io::AsIoData;
 use crate::scheduler::get_scheduler;
 use crate::sync::AtomicOption;
+use crate::yield_now::set_co_para;
 
 pub struct CancelIoImpl(AtomicOption<Arc<EventData>>);
 
@@ -31,3 +35,41 @@ impl CancelIo for CancelIoImpl {
         }
     }
 }
+
+/// a handle to cancel the io operation that a coroutine is parked on
+///
+/// it's created from an io object, and could be passed to any other
+/// coroutine or thread, e.g. a central deadline manager. `cancel` wakes
+/// up the coroutine that is parked on the io object, the blocked
+/// operation returns an error with the "Canceled" message, the same as
+/// the coroutine is canceled, but the coroutine itself keeps running.
+#[derive(Clone)]
+pub struct IoToken(Arc<EventData>);
+
+impl IoToken {
+    /// create a token for the io object
+    pub fn new<T: AsIoData + ?Sized>(io: &T) -> Self {
+        IoToken((*io.as_io_data()).clone())
+    }
+
+    /// cancel the io operation that is parked on the io object
+    ///
+    /// return false if there is no coroutine parked on it at the moment,
+    /// an operation that parks later would not be affected
+    pub fn cancel(&self) -> bool {
+        match self.0.co.take(Ordering::Acquire) {
+            Some(mut co) => {
+                set_co_para(&mut co, io::Error::new(io::ErrorKind::Other, "Canceled"));
+                get_scheduler().schedule(co);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl fmt::Debug for IoToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "IoToken {{ fd: {} }}", self.0.fd)
+    }
+}
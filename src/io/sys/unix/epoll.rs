@@ -180,6 +180,23 @@ impl Selector {
             .map(|_| io_data)
     }
 
+    // also watch the exceptional condition like the TCP urgent data
+    pub fn watch_urgent(&self, io_data: &IoData) -> io::Result<()> {
+        let mut info = EpollEvent::new(
+            EpollFlags::EPOLLIN
+                | EpollFlags::EPOLLOUT
+                | EpollFlags::EPOLLRDHUP
+                | EpollFlags::EPOLLPRI
+                | EpollFlags::EPOLLET,
+            io_data.as_ref() as *const _ as _,
+        );
+
+        let fd = io_data.fd;
+        let id = fd as usize % self.vec.len();
+        let epfd = unsafe { self.vec.get_unchecked(id) }.epfd;
+        epoll_ctl(epfd, EpollOp::EpollCtlMod, fd, &mut info).map_err(from_nix_error)
+    }
+
     #[inline]
     pub fn del_fd(&self, io_data: &IoData) {
         use std::ops::Deref;
@@ -14,6 +14,7 @@ mod select;
 #[path = "kqueue.rs"]
 mod select;
 
+pub mod async_fd;
 pub mod cancel;
 pub mod co_io;
 pub mod net;
@@ -27,6 +28,7 @@ use std::sync::Arc;
 use std::{fmt, io, ptr};
 
 use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
+use crate::net::limit;
 use crate::scheduler::get_scheduler;
 use crate::sync::AtomicOption;
 use crate::timeout_list::{TimeOutList, TimeoutHandle};
@@ -36,13 +38,22 @@ pub use self::select::{Selector, SysEvent};
 
 #[inline]
 pub fn add_socket<T: AsRawFd + ?Sized>(t: &T) -> io::Result<IoData> {
+    // the count is released when the io data is dropped
+    limit::acquire_socket()?;
     get_scheduler().get_selector().add_fd(IoData::new(t))
 }
 
+// wake up the io by the exceptional condition like the TCP urgent data
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn watch_urgent(io: &IoData) -> io::Result<()> {
+    get_scheduler().get_selector().watch_urgent(io)
+}
+
 #[inline]
 fn del_socket(io: &IoData) {
     // transfer the io to the selector
     get_scheduler().get_selector().del_fd(io);
+    limit::release_socket();
 }
 
 // deal with the io result
@@ -64,6 +75,51 @@ fn from_nix_error(err: nix::Error) -> ::std::io::Error {
     }
 }
 
+// set a raw socket option
+pub fn set_sockopt<T>(fd: RawFd, level: libc::c_int, name: libc::c_int, val: T) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &val as *const T as *const libc::c_void,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// get a raw socket option, the value is zeroed before the call
+pub fn get_sockopt<T>(fd: RawFd, level: libc::c_int, name: libc::c_int) -> io::Result<T> {
+    let mut val: T = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<T>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            level,
+            name,
+            &mut val as *mut T as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(val)
+}
+
+// number of bytes that can be read without blocking, by FIONREAD
+pub fn bytes_readable(fd: RawFd) -> io::Result<usize> {
+    let mut n: libc::c_int = 0;
+    if unsafe { libc::ioctl(fd, libc::FIONREAD, &mut n) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
 fn timeout_handler(data: TimerData) {
     if data.event_data.is_null() {
         return;
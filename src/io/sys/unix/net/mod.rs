@@ -1,9 +1,17 @@
 mod socket_read;
+mod socket_read_vectored;
+mod socket_recv;
+mod socket_send;
 mod socket_write;
 mod socket_write_vectored;
 mod tcp_listener_accpet;
 mod tcp_stream_connect;
 mod udp_recv_from;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod udp_recv_msg;
+mod udp_recv_ring;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod udp_send_msg;
 mod udp_send_to;
 mod unix_listener_accpet;
 mod unix_recv_from;
@@ -11,11 +19,19 @@ mod unix_send_to;
 mod unix_stream_connect;
 
 pub use self::socket_read::SocketRead;
+pub use self::socket_read_vectored::SocketReadVectored;
+pub use self::socket_recv::{recv, SocketRecv};
+pub use self::socket_send::{send, SocketSend};
 pub use self::socket_write::SocketWrite;
 pub use self::socket_write_vectored::SocketWriteVectored;
 pub use self::tcp_listener_accpet::TcpListenerAccept;
 pub use self::tcp_stream_connect::TcpStreamConnect;
 pub use self::udp_recv_from::UdpRecvFrom;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use self::udp_recv_msg::{recv_msg, UdpRecvMsg};
+pub use self::udp_recv_ring::{recv_ring, UdpRecvRing};
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use self::udp_send_msg::{send_msg, UdpSendMsg};
 pub use self::udp_send_to::UdpSendTo;
 pub use self::unix_listener_accpet::UnixListenerAccept;
 pub use self::unix_recv_from::UnixRecvFrom;
@@ -0,0 +1,97 @@
+use std::io::{self, IoSliceMut};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::super::{co_io_result, IoData};
+use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
+use crate::io::AsIoData;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
+use crate::scheduler::get_scheduler;
+use crate::yield_now::yield_with;
+
+pub struct SocketReadVectored<'a, 'b> {
+    io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
+    bufs: &'a mut [IoSliceMut<'b>],
+    socket: &'a std::net::TcpStream,
+    timeout: Option<Duration>,
+}
+
+impl<'a, 'b> SocketReadVectored<'a, 'b> {
+    pub fn new<T: AsIoData>(
+        s: &'a T,
+        socket: &'a std::net::TcpStream,
+        bufs: &'a mut [IoSliceMut<'b>],
+        timeout: Option<Duration>,
+    ) -> Self {
+        let fd = s.as_io_data().fd;
+        SocketReadVectored {
+            io_data: s.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64))),
+            bufs,
+            socket,
+            timeout,
+        }
+    }
+
+    pub fn done(&mut self) -> io::Result<usize> {
+        use std::io::Read;
+
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match (&*self.socket).read_vectored(self.bufs) {
+                Ok(n) => return Ok(n),
+                Err(e) => {
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64)));
+            yield_with(self);
+        }
+    }
+}
+
+impl<'a, 'b> EventSource for SocketReadVectored<'a, 'b> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
+        let io_data = (*self.io_data).clone();
+
+        if let Some(dur) = self.timeout {
+            get_scheduler()
+                .get_selector()
+                .add_io_timer(self.io_data, dur);
+        }
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if io_data.io_flag.load(Ordering::Acquire) {
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::super::{co_io_result, IoData};
+use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
+use crate::io::AsIoData;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
+use crate::scheduler::get_scheduler;
+use crate::yield_now::yield_with;
+
+// recv with the raw flags
+pub fn recv(fd: RawFd, buf: &mut [u8], flags: libc::c_int) -> io::Result<usize> {
+    let n = unsafe { libc::recv(fd, buf.as_mut_ptr() as *mut _, buf.len(), flags) };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
+pub struct SocketRecv<'a> {
+    io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
+    buf: &'a mut [u8],
+    flags: libc::c_int,
+    timeout: Option<Duration>,
+}
+
+impl<'a> SocketRecv<'a> {
+    pub fn new<T: AsIoData>(
+        s: &'a T,
+        buf: &'a mut [u8],
+        flags: libc::c_int,
+        timeout: Option<Duration>,
+    ) -> Self {
+        let fd = s.as_io_data().fd;
+        SocketRecv {
+            io_data: s.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64))),
+            buf,
+            flags,
+            timeout,
+        }
+    }
+
+    pub fn done(&mut self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match recv(self.io_data.fd, self.buf, self.flags) {
+                Ok(n) => return Ok(n),
+                Err(e) => {
+                    // raw_os_error is faster than kind
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64)));
+            yield_with(self);
+        }
+    }
+}
+
+impl<'a> EventSource for SocketRecv<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
+        let io_data = (*self.io_data).clone();
+
+        if let Some(dur) = self.timeout {
+            get_scheduler()
+                .get_selector()
+                .add_io_timer(self.io_data, dur);
+        }
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if io_data.io_flag.load(Ordering::Acquire) {
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
@@ -5,20 +5,25 @@ use std::time::Duration;
 use super::super::{co_io_result, from_nix_error, IoData};
 use crate::coroutine_impl::{CoroutineImpl, EventSource};
 use crate::io::AsIoData;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 use nix::unistd::write;
 
 pub struct SocketWrite<'a> {
     io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     buf: &'a [u8],
     timeout: Option<Duration>,
 }
 
 impl<'a> SocketWrite<'a> {
     pub fn new<T: AsIoData>(s: &'a T, buf: &'a [u8], timeout: Option<Duration>) -> Self {
+        let fd = s.as_io_data().fd;
         SocketWrite {
             io_data: s.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64))),
             buf,
             timeout,
         }
@@ -47,6 +52,9 @@ impl<'a> SocketWrite<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
@@ -5,11 +5,14 @@ use std::time::Duration;
 use super::super::{co_io_result, IoData};
 use crate::coroutine_impl::{CoroutineImpl, EventSource};
 use crate::io::AsIoData;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 
 pub struct SocketWriteVectored<'a> {
     io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     bufs: &'a [IoSlice<'a>],
     socket: &'a std::net::TcpStream,
     timeout: Option<Duration>,
@@ -22,8 +25,10 @@ impl<'a> SocketWriteVectored<'a> {
         bufs: &'a [IoSlice<'a>],
         timeout: Option<Duration>,
     ) -> Self {
+        let fd = s.as_io_data().fd;
         SocketWriteVectored {
             io_data: s.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64))),
             bufs,
             socket,
             timeout,
@@ -56,6 +61,9 @@ impl<'a> SocketWriteVectored<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
@@ -1,23 +1,33 @@
 use std::net::SocketAddr;
 use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 use std::{self, io};
 
 use super::super::{add_socket, co_io_result, IoData};
 use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::net::{TcpListener, TcpStream};
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
+use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 
 pub struct TcpListenerAccept<'a> {
     io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     socket: &'a std::net::TcpListener,
+    // the accept may yield more than once, the timer is set to the time left
+    deadline: Option<Instant>,
 }
 
 impl<'a> TcpListenerAccept<'a> {
-    pub fn new(socket: &'a TcpListener) -> io::Result<Self> {
+    pub fn new(socket: &'a TcpListener, timeout: Option<Duration>) -> io::Result<Self> {
+        let fd = socket.as_io_data().fd;
         Ok(TcpListenerAccept {
             io_data: socket.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64))),
             socket: socket.inner(),
+            deadline: timeout.and_then(|dur| Instant::now().checked_add(dur)),
         })
     }
 
@@ -49,6 +59,9 @@ impl<'a> TcpListenerAccept<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
@@ -59,7 +72,13 @@ impl<'a> EventSource for TcpListenerAccept<'a> {
         let handle = co_get_handle(&co);
         let cancel = handle.get_cancel();
         let io_data = (*self.io_data).clone();
-        // if there is no timer we don't need to call add_io_timer
+        // if there is no timeout we don't need to call add_io_timer
+        if let Some(deadline) = self.deadline {
+            let left = deadline.saturating_duration_since(Instant::now());
+            get_scheduler()
+                .get_selector()
+                .add_io_timer(self.io_data, left);
+        }
         self.io_data.co.swap(co, Ordering::Release);
 
         // there is event happened
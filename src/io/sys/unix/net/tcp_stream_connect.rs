@@ -1,22 +1,30 @@
 use std::io;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::atomic::Ordering;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use super::super::{add_socket, co_io_result, IoData};
 use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
 use crate::io::OptionCell;
 use crate::net::TcpStream;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 use socket2::Socket;
 
 pub struct TcpStreamConnect {
     io_data: OptionCell<IoData>,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     stream: OptionCell<Socket>,
-    timeout: Option<Duration>,
+    // the connect may yield more than once, the timer is set to the time left
+    deadline: Option<Instant>,
     addr: SocketAddr,
     is_connected: bool,
+    // the data to send in the SYN
+    fast_open: Option<Vec<u8>>,
+    // how many bytes of the fast open data are already sent
+    fast_open_sent: usize,
 }
 
 impl TcpStreamConnect {
@@ -34,18 +42,125 @@ impl TcpStreamConnect {
                     Ok((stream, addr))
                 })
             })
-            .and_then(|(stream, addr)| {
-                // before yield we must set the socket to nonblocking mode and registe to selector
-                stream.set_nonblocking(true)?;
-
-                add_socket(&stream).map(|io| TcpStreamConnect {
-                    io_data: OptionCell::new(io),
-                    stream: OptionCell::new(stream),
-                    timeout,
-                    addr,
-                    is_connected: false,
-                })
-            })
+            .and_then(|(stream, addr)| Self::from_socket(stream, addr, timeout))
+    }
+
+    // connect with the already configured socket
+    pub fn from_socket(
+        stream: Socket,
+        addr: SocketAddr,
+        timeout: Option<Duration>,
+    ) -> io::Result<Self> {
+        // before yield we must set the socket to nonblocking mode and registe to selector
+        stream.set_nonblocking(true)?;
+
+        add_socket(&stream).map(|io| TcpStreamConnect {
+            _reason: park_reason::record(ParkReason::IoWrite(park_reason::detail(|| io.fd as u64))),
+            io_data: OptionCell::new(io),
+            stream: OptionCell::new(stream),
+            deadline: timeout.and_then(|dur| Instant::now().checked_add(dur)),
+            addr,
+            is_connected: false,
+            fast_open: None,
+            fast_open_sent: 0,
+        })
+    }
+
+    // send the data in the SYN when connect
+    pub fn set_fast_open(&mut self, data: Vec<u8>) {
+        self.fast_open = Some(data);
+    }
+
+    // how many bytes of the fast open data are sent with the connect
+    // the rest must be written after the connection is established
+    pub fn fast_open_sent(&self) -> usize {
+        self.fast_open_sent
+    }
+
+    // start the connect with the fast open data
+    // return None if fast open is not supported
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn fast_open_connect(&mut self, data: &[u8]) -> Option<io::Result<bool>> {
+        use std::os::unix::io::AsRawFd;
+
+        let addr = socket2::SockAddr::from(self.addr);
+        let ret = unsafe {
+            libc::sendto(
+                self.stream.as_raw_fd(),
+                data.as_ptr() as *const libc::c_void,
+                data.len(),
+                libc::MSG_FASTOPEN | libc::MSG_NOSIGNAL,
+                addr.as_ptr(),
+                addr.len(),
+            )
+        };
+        if ret >= 0 {
+            // the data is queued in the SYN, the connect is still in progress
+            self.fast_open_sent = ret as usize;
+            return Some(Ok(false));
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            // no cookie yet, the SYN is sent without data
+            Some(libc::EINPROGRESS) => Some(Ok(false)),
+            // fast open is disabled by the sysctl
+            Some(libc::EOPNOTSUPP) | Some(libc::ENOTCONN) | Some(libc::EPIPE) => None,
+            _ => Some(Err(err)),
+        }
+    }
+
+    // start the connect with the fast open data
+    // return None if fast open is not supported
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    fn fast_open_connect(&mut self, data: &[u8]) -> Option<io::Result<bool>> {
+        use std::os::unix::io::AsRawFd;
+
+        let addr = socket2::SockAddr::from(self.addr);
+        let endpoints = libc::sa_endpoints_t {
+            sae_srcif: 0,
+            sae_srcaddr: std::ptr::null(),
+            sae_srcaddrlen: 0,
+            sae_dstaddr: addr.as_ptr(),
+            sae_dstaddrlen: addr.len(),
+        };
+        let iov = libc::iovec {
+            iov_base: data.as_ptr() as *mut libc::c_void,
+            iov_len: data.len(),
+        };
+        let mut sent: libc::size_t = 0;
+        let ret = unsafe {
+            libc::connectx(
+                self.stream.as_raw_fd(),
+                &endpoints,
+                libc::SAE_ASSOCID_ANY,
+                libc::CONNECT_DATA_IDEMPOTENT,
+                &iov,
+                1,
+                &mut sent,
+                std::ptr::null_mut(),
+            )
+        };
+        self.fast_open_sent = sent;
+        if ret == 0 {
+            self.is_connected = true;
+            return Some(Ok(true));
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EINPROGRESS) => Some(Ok(false)),
+            Some(libc::EOPNOTSUPP) | Some(libc::ENOTSUP) => None,
+            _ => Some(Err(err)),
+        }
+    }
+
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios"
+    )))]
+    fn fast_open_connect(&mut self, _data: &[u8]) -> Option<io::Result<bool>> {
+        None
     }
 
     #[inline]
@@ -53,6 +168,12 @@ impl TcpStreamConnect {
     pub fn check_connected(&mut self) -> io::Result<bool> {
         // unix connect is some like completion mode
         // we must give the connect request first to the system
+        if let Some(data) = self.fast_open.take() {
+            if let Some(ret) = self.fast_open_connect(&data) {
+                return ret;
+            }
+            // fall back to the normal handshake
+        }
         match self.stream.connect(&self.addr.into()) {
             Ok(_) => {
                 self.is_connected = true;
@@ -80,6 +201,11 @@ impl TcpStreamConnect {
             // clear the io_flag
             self.io_data.io_flag.store(false, Ordering::Relaxed);
 
+            // the async connect failure is reported by SO_ERROR
+            if let Some(e) = self.stream.take_error()? {
+                return Err(e);
+            }
+
             match self.stream.connect(&self.addr.into()) {
                 Ok(_) => return Ok(convert_to_stream(self)),
                 Err(ref e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {}
@@ -95,6 +221,9 @@ impl TcpStreamConnect {
             }
 
             // the result is still EINPROGRESS, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
@@ -106,10 +235,11 @@ impl EventSource for TcpStreamConnect {
         let cancel = handle.get_cancel();
         let io_data = self.io_data.clone();
 
-        if let Some(dur) = self.timeout {
+        if let Some(deadline) = self.deadline {
+            let left = deadline.saturating_duration_since(Instant::now());
             get_scheduler()
                 .get_selector()
-                .add_io_timer(&self.io_data, dur);
+                .add_io_timer(&self.io_data, left);
         }
         io_data.co.swap(co, Ordering::Release);
 
@@ -7,11 +7,14 @@ use super::super::{co_io_result, IoData};
 use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::net::UdpSocket;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 
 pub struct UdpRecvFrom<'a> {
     io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     buf: &'a mut [u8],
     socket: &'a std::net::UdpSocket,
     timeout: Option<Duration>,
@@ -19,8 +22,10 @@ pub struct UdpRecvFrom<'a> {
 
 impl<'a> UdpRecvFrom<'a> {
     pub fn new(socket: &'a UdpSocket, buf: &'a mut [u8]) -> Self {
+        let fd = socket.as_io_data().fd;
         UdpRecvFrom {
             io_data: socket.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64))),
             buf,
             socket: socket.inner(),
             timeout: socket.read_timeout().unwrap(),
@@ -52,6 +57,9 @@ impl<'a> UdpRecvFrom<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
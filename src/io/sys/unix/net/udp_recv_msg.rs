@@ -0,0 +1,142 @@
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::{io, mem, ptr};
+
+use super::super::{co_io_result, IoData};
+use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
+use crate::io::AsIoData;
+use crate::net::{RecvMsg, UdpSocket};
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
+use crate::scheduler::get_scheduler;
+use crate::yield_now::yield_with;
+use socket2::SockAddr;
+
+// nonblocking recvmsg that also collect the TOS / traffic class cmsg
+pub fn recv_msg(fd: RawFd, buf: &mut [u8]) -> io::Result<RecvMsg> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut _,
+        iov_len: buf.len(),
+    };
+    // u64 array to make sure the cmsg buffer is properly aligned
+    let mut cmsg_buf = [0u64; 8];
+    let mut tos = None;
+
+    let (n, addr) = unsafe {
+        SockAddr::init(|storage, len| {
+            let mut hdr: libc::msghdr = mem::zeroed();
+            hdr.msg_name = storage as *mut _;
+            hdr.msg_namelen = *len;
+            hdr.msg_iov = &mut iov;
+            hdr.msg_iovlen = 1;
+            hdr.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+            hdr.msg_controllen = mem::size_of_val(&cmsg_buf) as _;
+
+            let n = libc::recvmsg(fd, &mut hdr, 0);
+            if n < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            *len = hdr.msg_namelen;
+
+            let mut cmsg = libc::CMSG_FIRSTHDR(&hdr);
+            while !cmsg.is_null() {
+                let data = libc::CMSG_DATA(cmsg);
+                match ((*cmsg).cmsg_level, (*cmsg).cmsg_type) {
+                    (libc::IPPROTO_IP, libc::IP_TOS) => tos = Some(*data),
+                    (libc::IPPROTO_IPV6, libc::IPV6_TCLASS) => {
+                        tos = Some(ptr::read_unaligned(data as *const libc::c_int) as u8)
+                    }
+                    _ => {}
+                }
+                cmsg = libc::CMSG_NXTHDR(&hdr, cmsg);
+            }
+            Ok(n as usize)
+        })?
+    };
+
+    let addr = addr
+        .as_socket()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid address family"))?;
+    Ok(RecvMsg { len: n, addr, tos })
+}
+
+pub struct UdpRecvMsg<'a> {
+    io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
+    buf: &'a mut [u8],
+    fd: RawFd,
+    timeout: Option<Duration>,
+}
+
+impl<'a> UdpRecvMsg<'a> {
+    pub fn new(socket: &'a UdpSocket, buf: &'a mut [u8]) -> Self {
+        let fd = socket.as_io_data().fd;
+        UdpRecvMsg {
+            io_data: socket.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64))),
+            buf,
+            fd: socket.as_raw_fd(),
+            timeout: socket.read_timeout().unwrap(),
+        }
+    }
+
+    pub fn done(&mut self) -> io::Result<RecvMsg> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match recv_msg(self.fd, self.buf) {
+                Ok(msg) => return Ok(msg),
+                Err(e) => {
+                    // raw_os_error is faster than kind
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64)));
+            yield_with(self);
+        }
+    }
+}
+
+impl<'a> EventSource for UdpRecvMsg<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
+        let io_data = (*self.io_data).clone();
+
+        if let Some(dur) = self.timeout {
+            get_scheduler()
+                .get_selector()
+                .add_io_timer(self.io_data, dur);
+        }
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if io_data.io_flag.load(Ordering::Acquire) {
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
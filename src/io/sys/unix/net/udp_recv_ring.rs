@@ -0,0 +1,157 @@
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use super::super::{co_io_result, IoData};
+use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
+use crate::io::AsIoData;
+use crate::net::{DatagramRing, UdpSocket};
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
+use crate::scheduler::get_scheduler;
+use crate::yield_now::yield_with;
+
+// receive as many datagrams as available into the ring
+// only wait for the first one if the socket is in blocking mode
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn recv_ring(fd: RawFd, ring: &mut DatagramRing) -> io::Result<usize> {
+    ring.len = 0;
+    let slots = ring.capacity();
+    for i in 0..slots {
+        let slot = ring.slot_mut(i);
+        ring.iovs[i] = libc::iovec {
+            iov_base: slot.as_mut_ptr() as *mut _,
+            iov_len: slot.len(),
+        };
+        let hdr = &mut ring.hdrs[i];
+        *hdr = unsafe { std::mem::zeroed() };
+        hdr.msg_hdr.msg_iov = &mut ring.iovs[i];
+        hdr.msg_hdr.msg_iovlen = 1;
+    }
+
+    let n = unsafe {
+        libc::recvmmsg(
+            fd,
+            ring.hdrs.as_mut_ptr(),
+            slots as _,
+            libc::MSG_WAITFORONE as _,
+            std::ptr::null_mut(),
+        )
+    };
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let n = n as usize;
+    for i in 0..n {
+        ring.lens[i] = ring.hdrs[i].msg_len as usize;
+    }
+    ring.len = n;
+    Ok(n)
+}
+
+// receive as many datagrams as available into the ring
+// only wait for the first one if the socket is in blocking mode
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn recv_ring(fd: RawFd, ring: &mut DatagramRing) -> io::Result<usize> {
+    ring.len = 0;
+    let slots = ring.capacity();
+    let mut n = 0;
+    while n < slots {
+        let flags = if n == 0 { 0 } else { libc::MSG_DONTWAIT };
+        let slot = ring.slot_mut(n);
+        let ret = unsafe { libc::recv(fd, slot.as_mut_ptr() as *mut _, slot.len(), flags) };
+        if ret < 0 {
+            if n == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // no more datagrams available
+            break;
+        }
+        ring.lens[n] = ret as usize;
+        n += 1;
+    }
+    ring.len = n;
+    Ok(n)
+}
+
+pub struct UdpRecvRing<'a> {
+    io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
+    ring: &'a mut DatagramRing,
+    fd: RawFd,
+    timeout: Option<Duration>,
+}
+
+impl<'a> UdpRecvRing<'a> {
+    pub fn new(socket: &'a UdpSocket, ring: &'a mut DatagramRing) -> Self {
+        let fd = socket.as_io_data().fd;
+        UdpRecvRing {
+            io_data: socket.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64))),
+            ring,
+            fd: socket.as_raw_fd(),
+            timeout: socket.read_timeout().unwrap(),
+        }
+    }
+
+    pub fn done(&mut self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match recv_ring(self.fd, self.ring) {
+                Ok(n) => return Ok(n),
+                Err(e) => {
+                    // raw_os_error is faster than kind
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64)));
+            yield_with(self);
+        }
+    }
+}
+
+impl<'a> EventSource for UdpRecvRing<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let handle = co_get_handle(&co);
+        let cancel = handle.get_cancel();
+        let io_data = (*self.io_data).clone();
+
+        if let Some(dur) = self.timeout {
+            get_scheduler()
+                .get_selector()
+                .add_io_timer(self.io_data, dur);
+        }
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if io_data.io_flag.load(Ordering::Acquire) {
+            return io_data.schedule();
+        }
+
+        // register the cancel io data
+        cancel.set_io(io_data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::{io, mem, ptr};
+
+use super::super::{co_io_result, IoData};
+use crate::coroutine_impl::{CoroutineImpl, EventSource};
+use crate::io::AsIoData;
+use crate::net::{SendMsg, UdpSocket};
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
+use crate::scheduler::get_scheduler;
+use crate::yield_now::yield_with;
+use socket2::SockAddr;
+
+// nonblocking sendmsg that attach the TOS / traffic class cmsg if any
+pub fn send_msg(fd: RawFd, buf: &[u8], msg: &SendMsg) -> io::Result<usize> {
+    let addr = SockAddr::from(msg.addr);
+    let mut iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut _,
+        iov_len: buf.len(),
+    };
+    // u64 array to make sure the cmsg buffer is properly aligned
+    let mut cmsg_buf = [0u64; 4];
+
+    let n = unsafe {
+        let mut hdr: libc::msghdr = mem::zeroed();
+        hdr.msg_name = addr.as_ptr() as *mut _;
+        hdr.msg_namelen = addr.len();
+        hdr.msg_iov = &mut iov;
+        hdr.msg_iovlen = 1;
+
+        if let Some(tos) = msg.tos {
+            let (level, ty) = if msg.addr.is_ipv4() {
+                (libc::IPPROTO_IP, libc::IP_TOS)
+            } else {
+                (libc::IPPROTO_IPV6, libc::IPV6_TCLASS)
+            };
+            let size = mem::size_of::<libc::c_int>() as u32;
+            hdr.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+            hdr.msg_controllen = libc::CMSG_SPACE(size) as _;
+            let cmsg = libc::CMSG_FIRSTHDR(&hdr);
+            (*cmsg).cmsg_level = level;
+            (*cmsg).cmsg_type = ty;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(size) as _;
+            ptr::write_unaligned(
+                libc::CMSG_DATA(cmsg) as *mut libc::c_int,
+                libc::c_int::from(tos),
+            );
+        }
+
+        libc::sendmsg(fd, &hdr, 0)
+    };
+
+    if n < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(n as usize)
+}
+
+pub struct UdpSendMsg<'a> {
+    io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
+    buf: &'a [u8],
+    msg: &'a SendMsg,
+    fd: RawFd,
+    timeout: Option<Duration>,
+}
+
+impl<'a> UdpSendMsg<'a> {
+    pub fn new(socket: &'a UdpSocket, buf: &'a [u8], msg: &'a SendMsg) -> Self {
+        let fd = socket.as_io_data().fd;
+        UdpSendMsg {
+            io_data: socket.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64))),
+            buf,
+            msg,
+            fd: socket.as_raw_fd(),
+            timeout: socket.write_timeout().unwrap(),
+        }
+    }
+
+    pub fn done(&mut self) -> io::Result<usize> {
+        loop {
+            co_io_result()?;
+
+            // clear the io_flag
+            self.io_data.io_flag.store(false, Ordering::Relaxed);
+
+            match send_msg(self.fd, self.buf, self.msg) {
+                Ok(n) => return Ok(n),
+                Err(e) => {
+                    // raw_os_error is faster than kind
+                    let raw_err = e.raw_os_error();
+                    if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                        // do nothing here
+                    } else {
+                        return Err(e);
+                    }
+                }
+            }
+
+            if self.io_data.io_flag.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+
+            // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64)));
+            yield_with(self);
+        }
+    }
+}
+
+impl<'a> EventSource for UdpSendMsg<'a> {
+    fn subscribe(&mut self, co: CoroutineImpl) {
+        let io_data = (*self.io_data).clone();
+
+        if let Some(dur) = self.timeout {
+            get_scheduler()
+                .get_selector()
+                .add_io_timer(self.io_data, dur);
+        }
+        self.io_data.co.swap(co, Ordering::Release);
+
+        // there is event, re-run the coroutine
+        if io_data.io_flag.load(Ordering::Acquire) {
+            io_data.schedule();
+        }
+    }
+}
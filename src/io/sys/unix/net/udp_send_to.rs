@@ -7,11 +7,14 @@ use super::super::{co_io_result, IoData};
 use crate::coroutine_impl::{CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::net::UdpSocket;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 
 pub struct UdpSendTo<'a, A: ToSocketAddrs> {
     io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     buf: &'a [u8],
     socket: &'a std::net::UdpSocket,
     addr: A,
@@ -20,8 +23,10 @@ pub struct UdpSendTo<'a, A: ToSocketAddrs> {
 
 impl<'a, A: ToSocketAddrs> UdpSendTo<'a, A> {
     pub fn new(socket: &'a UdpSocket, buf: &'a [u8], addr: A) -> io::Result<Self> {
+        let fd = socket.as_io_data().fd;
         Ok(UdpSendTo {
             io_data: socket.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64))),
             buf,
             socket: socket.inner(),
             addr,
@@ -54,6 +59,9 @@ impl<'a, A: ToSocketAddrs> UdpSendTo<'a, A> {
             }
 
             // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
@@ -6,17 +6,22 @@ use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
 use crate::io::sys::{co_io_result, IoData};
 use crate::io::{AsIoData, CoIo};
 use crate::os::unix::net::{UnixListener, UnixStream};
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::yield_now::yield_with;
 
 pub struct UnixListenerAccept<'a> {
     io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     socket: &'a net::UnixListener,
 }
 
 impl<'a> UnixListenerAccept<'a> {
     pub fn new(socket: &'a UnixListener) -> io::Result<Self> {
+        let fd = socket.0.as_io_data().fd;
         Ok(UnixListenerAccept {
             io_data: socket.0.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64))),
             socket: socket.0.inner(),
         })
     }
@@ -49,6 +54,9 @@ impl<'a> UnixListenerAccept<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
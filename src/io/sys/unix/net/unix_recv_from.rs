@@ -7,11 +7,14 @@ use super::super::{co_io_result, IoData};
 use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::os::unix::net::UnixDatagram;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 
 pub struct UnixRecvFrom<'a> {
     io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     buf: &'a mut [u8],
     socket: &'a std::os::unix::net::UnixDatagram,
     timeout: Option<Duration>,
@@ -19,8 +22,10 @@ pub struct UnixRecvFrom<'a> {
 
 impl<'a> UnixRecvFrom<'a> {
     pub fn new(socket: &'a UnixDatagram, buf: &'a mut [u8]) -> Self {
+        let fd = socket.0.as_io_data().fd;
         UnixRecvFrom {
             io_data: socket.0.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64))),
             buf,
             socket: socket.0.inner(),
             timeout: socket.0.read_timeout().unwrap(),
@@ -52,6 +57,9 @@ impl<'a> UnixRecvFrom<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoRead(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
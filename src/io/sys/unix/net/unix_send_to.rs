@@ -7,11 +7,14 @@ use super::super::{co_io_result, IoData};
 use crate::coroutine_impl::{CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::os::unix::net::UnixDatagram;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 
 pub struct UnixSendTo<'a> {
     io_data: &'a IoData,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     buf: &'a [u8],
     socket: &'a std::os::unix::net::UnixDatagram,
     path: &'a Path,
@@ -20,8 +23,10 @@ pub struct UnixSendTo<'a> {
 
 impl<'a> UnixSendTo<'a> {
     pub fn new(socket: &'a UnixDatagram, buf: &'a [u8], path: &'a Path) -> io::Result<Self> {
+        let fd = socket.0.as_io_data().fd;
         Ok(UnixSendTo {
             io_data: socket.0.as_io_data(),
+            _reason: park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64))),
             buf,
             socket: socket.0.inner(),
             path,
@@ -54,6 +59,9 @@ impl<'a> UnixSendTo<'a> {
             }
 
             // the result is still WouldBlock, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
@@ -7,12 +7,15 @@ use super::super::{add_socket, co_io_result, IoData};
 use crate::coroutine_impl::{co_get_handle, CoroutineImpl, EventSource};
 use crate::io::{CoIo, OptionCell};
 use crate::os::unix::net::UnixStream;
+use crate::park_reason::{self, ParkReason, ParkReasonGuard};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::yield_with;
 use socket2::{Domain, SockAddr, Socket, Type};
 
 pub struct UnixStreamConnect {
     io_data: OptionCell<IoData>,
+    // the first park is in the caller's `yield_with`
+    _reason: ParkReasonGuard,
     stream: OptionCell<Socket>,
     path: SockAddr,
     is_connected: bool,
@@ -25,6 +28,7 @@ impl UnixStreamConnect {
         // before yield we must set the socket to nonblocking mode and registe to selector
         socket.set_nonblocking(true)?;
         add_socket(&socket).map(|io| UnixStreamConnect {
+            _reason: park_reason::record(ParkReason::IoWrite(park_reason::detail(|| io.fd as u64))),
             io_data: OptionCell::new(io),
             stream: OptionCell::new(socket),
             path,
@@ -79,6 +83,9 @@ impl UnixStreamConnect {
             }
 
             // the result is still EINPROGRESS, need to try again
+            let fd = self.io_data.fd;
+            let _reason =
+                park_reason::record(ParkReason::IoWrite(park_reason::detail(|| fd as u64)));
             yield_with(self);
         }
     }
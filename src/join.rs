@@ -4,7 +4,10 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::Result;
 
+use crate::cancel::trigger_cancel_panic;
 use crate::coroutine_impl::Coroutine;
+use crate::park::ParkError;
+use crate::park_reason::{self, ParkReason};
 use crate::sync::{AtomicOption, Blocker};
 use crossbeam::atomic::AtomicCell;
 use generator::Error;
@@ -47,6 +50,7 @@ impl Join {
 
     fn wait(&self) {
         if self.state.load(Ordering::Acquire) {
+            let _reason = park_reason::record(ParkReason::Join);
             let cur = Blocker::current();
             // register the blocker first
             self.to_wake.swap(cur.clone(), Ordering::Release);
@@ -100,6 +104,44 @@ impl<T> JoinHandle<T> {
         !self.join.state.load(Ordering::Acquire)
     }
 
+    /// cancel the coroutine
+    ///
+    /// a coroutine blocked in io or parking is woken up and unwinds with a
+    /// cancel panic, which releases the guards it holds. the `join` would
+    /// return the `Cancel` error
+    ///
+    /// # Safety
+    ///
+    /// same as [`Coroutine::cancel`]
+    ///
+    /// [`Coroutine::cancel`]: struct.Coroutine.html#method.cancel
+    pub unsafe fn cancel(&self) {
+        self.co.cancel();
+    }
+
+    /// make the park token of the coroutine available, see `coroutine::park`
+    #[inline]
+    pub fn unpark(&self) {
+        self.co.unpark();
+    }
+
+    /// return true if the coroutine is finished, same as `is_done`
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.is_done()
+    }
+
+    /// take the result if the coroutine is finished without blocking
+    ///
+    /// return the handle back if the coroutine is still running
+    pub fn try_join(self) -> ::std::result::Result<Result<T>, JoinHandle<T>> {
+        if self.is_done() {
+            Ok(self.join())
+        } else {
+            Err(self)
+        }
+    }
+
     /// block until the coroutine is done
     pub fn wait(&self) {
         self.join.wait();
@@ -121,3 +163,76 @@ impl<T> fmt::Debug for JoinHandle<T> {
         f.pad("JoinHandle { .. }")
     }
 }
+
+/// join all the coroutines, return their results in the same order
+///
+/// each join would park the current context until the coroutine is done
+pub fn join_all<T>(handles: Vec<JoinHandle<T>>) -> Vec<Result<T>> {
+    handles.into_iter().map(JoinHandle::join).collect()
+}
+
+/// join all the coroutines, return the first panic as soon as it happens
+///
+/// when any coroutine panics, the remaining unfinished coroutines are
+/// canceled and detached, and the panic is returned without waiting for
+/// them. otherwise return all the results in the same order
+///
+/// # Safety
+///
+/// the remaining coroutines are canceled on panic, which has the same side
+/// effect as [`Coroutine::cancel`]
+///
+/// [`Coroutine::cancel`]: struct.Coroutine.html#method.cancel
+pub unsafe fn try_join_all<T>(handles: Vec<JoinHandle<T>>) -> Result<Vec<T>> {
+    let mut pending: Vec<_> = handles.into_iter().map(Some).collect();
+    let mut results: Vec<Option<T>> = pending.iter().map(|_| None).collect();
+    let mut left = pending.len();
+
+    loop {
+        // collect the finished coroutines
+        for (i, slot) in pending.iter_mut().enumerate() {
+            if !matches!(slot, Some(h) if h.is_done()) {
+                continue;
+            }
+            left -= 1;
+            match slot.take().unwrap().join() {
+                Ok(t) => results[i] = Some(t),
+                Err(panic) => {
+                    for h in pending.iter().flatten() {
+                        h.coroutine().cancel();
+                    }
+                    return Err(panic);
+                }
+            }
+        }
+
+        if left == 0 {
+            return Ok(results.into_iter().map(Option::unwrap).collect());
+        }
+
+        // wait for any of the coroutines done
+        let cur = Blocker::current();
+        let mut done = false;
+        for h in pending.iter().flatten() {
+            h.join.to_wake.swap(cur.clone(), Ordering::Release);
+            // re-check the state after register the blocker
+            done |= h.is_done();
+        }
+
+        let ret = if done {
+            Ok(())
+        } else {
+            let _reason = park_reason::record(ParkReason::Join);
+            cur.park(None)
+        };
+
+        // deregister the blocker from the unfinished ones
+        for h in pending.iter().flatten() {
+            h.join.to_wake.take(Ordering::Acquire);
+        }
+
+        if ret == Err(ParkError::Canceled) {
+            trigger_cancel_panic();
+        }
+    }
+}
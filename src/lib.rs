@@ -30,26 +30,36 @@
 #[macro_use]
 extern crate log;
 
+mod blocking;
 mod cancel;
 mod config;
 mod join;
 mod local;
 mod park;
+mod park_reason;
 mod pool;
 mod sleep;
 #[macro_use]
 mod macros;
 mod coroutine_impl;
+mod random;
 mod scheduler;
 mod scoped;
 mod timeout_list;
+mod watchdog;
 mod yield_now;
 
 pub mod coroutine;
 pub mod cqueue;
+pub mod fs;
+pub mod future;
 pub mod io;
 pub mod net;
 pub mod os;
 pub mod sync;
-pub use crate::config::{config, Config};
+#[cfg(feature = "test-util")]
+pub mod test;
+pub mod time;
+pub use crate::config::{config, Config, PollerPanicPolicy};
 pub use crate::local::LocalKey;
+pub use crate::watchdog::WatchdogAction;
@@ -1,11 +1,12 @@
 use std::any::TypeId;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::hash::{BuildHasherDefault, Hasher};
 use std::ptr::NonNull;
 use std::sync::Arc;
 
 use crate::coroutine_impl::Coroutine;
+use crate::cqueue::SelectClaim;
 use crate::join::Join;
 use generator::get_local_data;
 
@@ -20,6 +21,10 @@ pub struct CoroutineLocal {
     join: Arc<Join>,
     // real local data hash map
     local_data: LocalMap,
+    // if the coroutine is a select arm spawned by a cqueue
+    select_arm: Cell<bool>,
+    // the claim of the select that the arm belongs to
+    select_claim: Cell<Option<SelectClaim>>,
 }
 
 impl CoroutineLocal {
@@ -29,6 +34,8 @@ impl CoroutineLocal {
             co,
             join,
             local_data: RefCell::new(HashMap::default()),
+            select_arm: Cell::new(false),
+            select_claim: Cell::new(None),
         })
     }
 
@@ -41,6 +48,19 @@ impl CoroutineLocal {
     pub fn get_join(&self) -> Arc<Join> {
         self.join.clone()
     }
+
+    // if the coroutine is a select arm spawned by a cqueue
+    #[inline]
+    pub fn is_select_arm(&self) -> bool {
+        self.select_arm.get()
+    }
+
+    // drop all the local data before the join is triggered
+    // the map is taken out first, so the values could access the storage in drop
+    pub fn clear_local_data(&self) {
+        let data = std::mem::take(&mut *self.local_data.borrow_mut());
+        drop(data);
+    }
 }
 
 #[inline]
@@ -50,6 +70,37 @@ pub fn get_co_local_data() -> Option<NonNull<CoroutineLocal>> {
     NonNull::new(ptr as *mut CoroutineLocal)
 }
 
+// mark the current coroutine as a select arm
+pub(crate) fn set_select_arm(claim: Option<SelectClaim>) {
+    if let Some(local) = get_co_local_data() {
+        let local = unsafe { local.as_ref() };
+        local.select_arm.set(true);
+        local.select_claim.set(claim);
+    }
+}
+
+// get the select claim of the current coroutine
+#[inline]
+pub(crate) fn select_claim() -> Option<SelectClaim> {
+    get_co_local_data().and_then(|local| unsafe { local.as_ref() }.select_claim.get())
+}
+
+// keep the select claim when the coroutine blocks, restored on drop
+// used when the coroutine has already consumed something
+pub(crate) struct HoldClaim(Option<SelectClaim>);
+
+pub(crate) fn hold_select_claim() -> HoldClaim {
+    HoldClaim(get_co_local_data().and_then(|local| unsafe { local.as_ref() }.select_claim.take()))
+}
+
+impl Drop for HoldClaim {
+    fn drop(&mut self) {
+        if let (Some(local), Some(claim)) = (get_co_local_data(), self.0) {
+            unsafe { local.as_ref() }.select_claim.set(Some(claim));
+        }
+    }
+}
+
 fn with<F: FnOnce(&LocalMap) -> R, R>(f: F) -> R {
     match get_co_local_data() {
         Some(v) => f(&(unsafe { v.as_ref() }.local_data)),
@@ -67,7 +118,8 @@ impl<T> Opaque for T {}
 /// This type is generated by the `coroutine_local!` macro and performs very
 /// similarly to the `thread_local!` macro and `std::thread::LocalKey` types.
 /// Data associated with a `LocalKey<T>` is stored inside of a coroutine,
-/// and the data is destroyed when the coroutine is completed.
+/// and the data is destroyed when the coroutine is completed, even on
+/// panic or cancel, before its `join` returns.
 ///
 /// coroutine-local data requires the `'static` bound to ensure it lives long
 /// enough. When a key is accessed for the first time the coroutine's data is
@@ -114,24 +114,132 @@ macro_rules! cqueue_add_oneshot {
 
 /// macro used to select for only one event
 /// it will return the index of which event happens first
+///
+/// only the bottom half of the first event is executed, the arms that lose
+/// the race are canceled. only one arm runs its top half at a time, and
+/// once an arm finished its top half, the others are canceled before they
+/// go on from where they are blocked, so a losing arm never consumes any
+/// data from the channel or io object it's waiting on. so a top half
+/// should not block again once it has consumed something
+///
+/// besides the `pattern = expression => bottom` arms, a send arm is written
+/// as `send tx, slot => bottom`, where `slot` is a `&mut Option<T>` holding
+/// the value for the `tx` sender. the value is only taken out of the slot
+/// when the arm sends it, so it stays in the slot if the arm loses, even
+/// when it's blocked on a full bounded channel, or if the receiver is gone.
+/// an arm with an empty slot is disabled and never fires, like a nil
+/// channel in go.
+///
+/// the last arm could be a timeout arm written as `default(dur) => bottom`.
+/// if no other arm fires within `dur`, the others are canceled and the
+/// bottom runs in the caller context. it's a timed wait of the caller, no
+/// extra coroutine is spawned for it.
+///
+/// the last arm could also be a nonblocking arm written as
+/// `default => bottom`. the other arms are run in the caller context until
+/// they block, and if none of them is ready the bottom runs at once.
+///
+/// ```
+/// #[macro_use]
+/// extern crate may;
+/// use may::sync::mpsc::channel;
+///
+/// # fn main() {
+/// let (tx, rx) = channel();
+/// let (_tx2, rx2) = channel::<i32>();
+/// let mut slot = Some(1);
+/// let id = select!(
+///     v = rx2.recv() => println!("recv {:?}", v),
+///     send tx, &mut slot => {}
+/// );
+/// assert_eq!(id, 1);
+/// assert_eq!(slot, None);
+/// assert_eq!(rx.recv(), Ok(1));
+/// # }
+/// ```
 #[macro_export]
 macro_rules! select {
+    (@has_default) => { false };
+    (@has_default default => $($rest:tt)*) => { true };
+    (@has_default $first:tt $($rest:tt)*) => { select!(@has_default $($rest)*) };
+    (@arms $cqueue:ident, $token:ident,) => {
+        match $cqueue.poll(None) {
+            Ok(ev) => ev.token,
+            _ => unreachable!("select error"),
+        }
+    };
+    (@arms $cqueue:ident, $token:ident, default => $bottom:expr $(,)?) => {
+        // all the arms are already run until they block when added
+        if $cqueue.claim_default() {
+            $bottom;
+            $token
+        } else {
+            select!(@arms $cqueue, $token,)
+        }
+    };
+    (@arms $cqueue:ident, $token:ident, default($dur:expr) => $bottom:expr $(,)?) => {
+        match $cqueue.poll(Some($dur)) {
+            Ok(ev) => ev.token,
+            Err(e) => {
+                // there is no other arm to wait for
+                if e == $crate::cqueue::PollError::Finished {
+                    $crate::coroutine::sleep($dur);
+                }
+                // an arm may win right at the timeout
+                if $cqueue.claim_default() {
+                    $bottom;
+                    $token
+                } else {
+                    select!(@arms $cqueue, $token,)
+                }
+            }
+        }
+    };
     (
-        $($name:pat = $top:expr => $bottom:expr),+
-    ) => ({
+        @arms $cqueue:ident, $token:ident,
+        $name:pat = $top:expr => $bottom:expr $(, $($rest:tt)*)?
+    ) => {{
+        cqueue_add_oneshot!($cqueue, $token, $name = $top => $bottom);
+        $token += 1;
+        select!(@arms $cqueue, $token, $($($rest)*)?)
+    }};
+    (
+        @arms $cqueue:ident, $token:ident,
+        send $tx:expr, $slot:expr => $bottom:expr $(, $($rest:tt)*)?
+    ) => {{
+        go!($cqueue, $token, |es| {
+            let slot: &mut Option<_> = $slot;
+            match slot.take() {
+                Some(v) => {
+                    // put the value back if the receiver is gone
+                    // or the arm is canceled when blocked
+                    if let Err(e) = $tx.send(v) {
+                        *slot = Some(e.0);
+                    }
+                    es.send(es.get_token());
+                    $bottom
+                }
+                // the disabled arm waits until canceled
+                None => loop {
+                    $crate::coroutine::park();
+                },
+            }
+        });
+        $token += 1;
+        select!(@arms $cqueue, $token, $($($rest)*)?)
+    }};
+    ($($arms:tt)+) => ({
         use $crate::cqueue;
         cqueue::scope(|cqueue| {
+            // probe the arms once for the nonblocking select
+            cqueue.set_run_inline(select!(@has_default $($arms)+));
+            // only the winning arm could consume anything
+            cqueue.set_exclusive(true);
+            #[allow(unused_mut)]
             let mut _token = 0;
-            $(
-                cqueue_add_oneshot!(cqueue, _token, $name = $top => $bottom);
-                _token += 1;
-            )+
-            match cqueue.poll(None) {
-                Ok(ev) => return ev.token,
-                _ => unreachable!("select error"),
-            }
+            select!(@arms cqueue, _token, $($arms)+)
         })
-    })
+    });
 }
 
 /// macro used to join all scoped sub coroutines
@@ -0,0 +1,49 @@
+//! network configuration interface
+//!
+
+use std::time::Duration;
+
+use crate::sync::atomic_dur::AtomicDuration;
+
+static DEFAULT_READ_TIMEOUT: AtomicDuration = AtomicDuration::none();
+static DEFAULT_WRITE_TIMEOUT: AtomicDuration = AtomicDuration::none();
+
+/// network configuration type
+pub struct NetConfig;
+
+/// get the network configuration instance
+pub fn config() -> NetConfig {
+    NetConfig
+}
+
+/// the defaults only apply to the streams created after the call,
+/// each stream can still override them by `set_read_timeout`/`set_write_timeout`
+impl NetConfig {
+    /// set the default read timeout for new `TcpStream`s
+    ///
+    /// pass `None` to disable the default timeout
+    pub fn default_read_timeout(&self, dur: Option<Duration>) -> &Self {
+        info!("set default read timeout={:?}", dur);
+        DEFAULT_READ_TIMEOUT.swap(dur);
+        self
+    }
+
+    /// get the default read timeout for new `TcpStream`s
+    pub fn get_default_read_timeout(&self) -> Option<Duration> {
+        DEFAULT_READ_TIMEOUT.get()
+    }
+
+    /// set the default write timeout for new `TcpStream`s
+    ///
+    /// pass `None` to disable the default timeout
+    pub fn default_write_timeout(&self, dur: Option<Duration>) -> &Self {
+        info!("set default write timeout={:?}", dur);
+        DEFAULT_WRITE_TIMEOUT.swap(dur);
+        self
+    }
+
+    /// get the default write timeout for new `TcpStream`s
+    pub fn get_default_write_timeout(&self) -> Option<Duration> {
+        DEFAULT_WRITE_TIMEOUT.get()
+    }
+}
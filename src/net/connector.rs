@@ -0,0 +1,190 @@
+use std::io::{self, Write};
+use std::net::{self, SocketAddr};
+use std::time::Duration;
+
+use crate::coroutine_impl::is_coroutine;
+use crate::io as io_impl;
+use crate::io::net as net_impl;
+use crate::net::TcpStream;
+use crate::yield_now::yield_with;
+use socket2::{Domain, Socket, Type};
+
+/// A builder to set the socket options before connect
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use may::net::TcpConnector;
+///
+/// let s = TcpConnector::new()
+///     .nodelay(true)
+///     .ttl(64)
+///     .timeout(Duration::from_secs(3))
+///     .connect("127.0.0.1:8080".parse().unwrap())
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TcpConnector {
+    local: Option<SocketAddr>,
+    nodelay: Option<bool>,
+    ttl: Option<u32>,
+    timeout: Option<Duration>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    device: Option<Vec<u8>>,
+    fast_open: Option<Vec<u8>>,
+}
+
+impl TcpConnector {
+    /// create a connector with the system default options
+    pub fn new() -> Self {
+        TcpConnector::default()
+    }
+
+    /// bind the socket to the local address before connect
+    pub fn bind(mut self, local: SocketAddr) -> TcpConnector {
+        self.local = Some(local);
+        self
+    }
+
+    /// set `TCP_NODELAY`
+    pub fn nodelay(mut self, nodelay: bool) -> TcpConnector {
+        self.nodelay = Some(nodelay);
+        self
+    }
+
+    /// set the `IP_TTL`
+    pub fn ttl(mut self, ttl: u32) -> TcpConnector {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// set the connect timeout
+    pub fn timeout(mut self, dur: Duration) -> TcpConnector {
+        self.timeout = Some(dur);
+        self
+    }
+
+    /// set `SO_RCVBUF`
+    pub fn recv_buffer_size(mut self, size: usize) -> TcpConnector {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// set `SO_SNDBUF`
+    pub fn send_buffer_size(mut self, size: usize) -> TcpConnector {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// bind the socket to the network interface by `SO_BINDTODEVICE`
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn bind_device(mut self, interface: &[u8]) -> TcpConnector {
+        self.device = Some(interface.to_vec());
+        self
+    }
+
+    /// send the initial data in the SYN by TCP Fast Open
+    ///
+    /// this saves a round trip when reconnecting to a server that has
+    /// handed out a fast open cookie before. on linux the data is sent
+    /// by `sendto` with `MSG_FASTOPEN`, which requires the client bit
+    /// (`1`) of the `net.ipv4.tcp_fastopen` sysctl. on macos it's sent
+    /// by `connectx`. the server must enable it too, see
+    /// `TcpListener::set_fast_open`.
+    ///
+    /// the data is always delivered, when fast open is not supported
+    /// or there is no cookie yet, it silently falls back to a normal
+    /// handshake and the data is written once connected. note that the
+    /// SYN data may be replayed by the network, so it must be idempotent
+    pub fn fast_open(mut self, data: &[u8]) -> TcpConnector {
+        self.fast_open = Some(data.to_vec());
+        self
+    }
+
+    // create the socket and apply all the options
+    fn socket(&self, addr: &SocketAddr) -> io::Result<Socket> {
+        let socket = match addr {
+            SocketAddr::V4(..) => Socket::new(Domain::IPV4, Type::STREAM, None)?,
+            SocketAddr::V6(..) => Socket::new(Domain::IPV6, Type::STREAM, None)?,
+        };
+        if let Some(nodelay) = self.nodelay {
+            socket.set_nodelay(nodelay)?;
+        }
+        if let Some(ttl) = self.ttl {
+            socket.set_ttl(ttl)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            if let Some(ref device) = self.device {
+                socket.bind_device(Some(device))?;
+            }
+        }
+        if let Some(local) = self.local {
+            socket.bind(&local.into())?;
+        }
+        Ok(socket)
+    }
+
+    /// create the socket with the options and connect to the remote address
+    pub fn connect(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        let socket = self.socket(&addr)?;
+
+        if !is_coroutine() {
+            // the data would be carried by the SYN of the first write
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            {
+                use std::os::unix::io::AsRawFd;
+                if self.fast_open.is_some() {
+                    let fd = socket.as_raw_fd();
+                    let on: libc::c_int = 1;
+                    // not supported by old kernels, just ignore it
+                    io_impl::set_sockopt(fd, libc::IPPROTO_TCP, libc::TCP_FASTOPEN_CONNECT, on)
+                        .ok();
+                }
+            }
+            match self.timeout {
+                Some(dur) => socket.connect_timeout(&addr.into(), dur)?,
+                None => socket.connect(&addr.into())?,
+            }
+            let s: net::TcpStream = socket.into();
+            s.set_nonblocking(true)?;
+            let io = io_impl::add_socket(&s)?;
+            let mut s = TcpStream::from_stream(s, io);
+            if let Some(ref data) = self.fast_open {
+                s.write_all(data)?;
+            }
+            return Ok(s);
+        }
+
+        let mut c = net_impl::TcpStreamConnect::from_socket(socket, addr, self.timeout)?;
+
+        #[cfg(unix)]
+        {
+            if let Some(ref data) = self.fast_open {
+                c.set_fast_open(data.clone());
+            }
+            if !c.check_connected()? {
+                yield_with(&c);
+            }
+        }
+        #[cfg(not(unix))]
+        yield_with(&c);
+
+        let mut s = c.done()?;
+        if let Some(ref data) = self.fast_open {
+            #[cfg(unix)]
+            let data = &data[c.fast_open_sent()..];
+            s.write_all(data)?;
+        }
+        Ok(s)
+    }
+}
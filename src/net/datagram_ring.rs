@@ -0,0 +1,90 @@
+use std::slice;
+
+/// A preallocated buffer region for receiving a batch of datagrams
+///
+/// the buffer is split into fixed size slots, each received datagram is
+/// stored in its own slot, so receiving into the ring never allocates.
+/// datagrams that are larger than the slot size are truncated.
+///
+/// it's filled by [`UdpSocket::recv_into_ring`]
+///
+/// [`UdpSocket::recv_into_ring`]: struct.UdpSocket.html#method.recv_into_ring
+pub struct DatagramRing {
+    pub(crate) buf: Box<[u8]>,
+    pub(crate) slot_size: usize,
+    // length of each received datagram
+    pub(crate) lens: Box<[usize]>,
+    // number of received datagrams
+    pub(crate) len: usize,
+    // the recvmmsg headers, kept here to avoid allocation for each recv
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) hdrs: Box<[libc::mmsghdr]>,
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(crate) iovs: Box<[libc::iovec]>,
+}
+
+// the raw pointers in the headers only point to the buffer of the ring itself
+unsafe impl Send for DatagramRing {}
+unsafe impl Sync for DatagramRing {}
+
+impl DatagramRing {
+    /// create a ring that could hold `slots` datagrams of `slot_size` bytes
+    pub fn new(slots: usize, slot_size: usize) -> Self {
+        assert!(slots > 0 && slot_size > 0, "empty datagram ring");
+        DatagramRing {
+            buf: vec![0; slots * slot_size].into_boxed_slice(),
+            slot_size,
+            lens: vec![0; slots].into_boxed_slice(),
+            len: 0,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            hdrs: (0..slots).map(|_| unsafe { std::mem::zeroed() }).collect(),
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            iovs: (0..slots)
+                .map(|_| libc::iovec {
+                    iov_base: std::ptr::null_mut(),
+                    iov_len: 0,
+                })
+                .collect(),
+        }
+    }
+
+    /// the max number of datagrams the ring could hold
+    pub fn capacity(&self) -> usize {
+        self.lens.len()
+    }
+
+    /// the size of each slot
+    pub fn slot_size(&self) -> usize {
+        self.slot_size
+    }
+
+    /// the number of datagrams received by the last recv
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// return true if there is no datagram received
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// get the nth received datagram
+    pub fn get(&self, i: usize) -> Option<&[u8]> {
+        if i >= self.len {
+            return None;
+        }
+        let offset = i * self.slot_size;
+        Some(&self.buf[offset..offset + self.lens[i]])
+    }
+
+    /// iterate the received datagrams
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        (0..self.len).map(move |i| self.get(i).unwrap())
+    }
+
+    // get the nth slot buffer for receiving
+    pub(crate) fn slot_mut(&mut self, i: usize) -> &mut [u8] {
+        let offset = i * self.slot_size;
+        unsafe { slice::from_raw_parts_mut(self.buf.as_mut_ptr().add(offset), self.slot_size) }
+    }
+}
@@ -0,0 +1,451 @@
+//! A minimal DNS stub resolver built on the coroutine sockets
+//!
+//! the queries are sent over `UdpSocket` and fall back to `TcpStream` when
+//! the response is truncated, so the resolution only parks the coroutine
+//! instead of blocking the worker thread.
+
+use std::collections::hash_map::RandomState;
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use crate::net::{TcpStream, UdpSocket};
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+// max udp response size without EDNS
+const UDP_SIZE: usize = 512;
+
+/// default timeout for each query attempt
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+/// default number of attempts for each query
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+
+/// resolve the name to ip addresses by querying the DNS server
+///
+/// both A and AAAA records are queried, with the default timeout and
+/// attempts. see [`resolve_with`] for details
+///
+/// [`resolve_with`]: fn.resolve_with.html
+pub fn resolve(name: &str, server: SocketAddr) -> io::Result<Vec<IpAddr>> {
+    resolve_with(name, server, DEFAULT_TIMEOUT, DEFAULT_ATTEMPTS)
+}
+
+/// resolve the name to ip addresses with the timeout and attempts
+///
+/// each query is sent over udp and resent on timeout up to `attempts`
+/// times, a truncated response is retried over tcp. the A and AAAA queries
+/// are independent, the addresses are returned if either one succeeds, and
+/// the error of the A query is returned if both fail. return `NotFound` if
+/// the name doesn't exist or has no address records
+pub fn resolve_with(
+    name: &str,
+    server: SocketAddr,
+    timeout: Duration,
+    attempts: u32,
+) -> io::Result<Vec<IpAddr>> {
+    // many resolvers drop the AAAA queries, each type is tried on its own
+    let v4 = query(name, TYPE_A, server, timeout, attempts);
+    let v6 = query(name, TYPE_AAAA, server, timeout, attempts);
+    let addrs: Vec<_> = match (v4, v6) {
+        (Err(e), Err(_)) => return Err(e),
+        (v4, v6) => v4.into_iter().chain(v6).flatten().collect(),
+    };
+    if addrs.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no address records for {}", name),
+        ));
+    }
+    Ok(addrs)
+}
+
+// query the records of the type, return the addresses
+fn query(
+    name: &str,
+    qtype: u16,
+    server: SocketAddr,
+    timeout: Duration,
+    attempts: u32,
+) -> io::Result<Vec<IpAddr>> {
+    let id = RandomState::new().build_hasher().finish() as u16;
+    let req = encode_query(id, name, qtype)?;
+    match query_udp(&req, id, server, timeout, attempts)? {
+        Response::Answer(addrs) => Ok(addrs),
+        Response::Truncated => match query_tcp(&req, id, server, timeout)? {
+            Response::Answer(addrs) => Ok(addrs),
+            Response::Truncated => Err(invalid_data("truncated tcp response")),
+        },
+    }
+}
+
+fn query_udp(
+    req: &[u8],
+    id: u16,
+    server: SocketAddr,
+    timeout: Duration,
+    attempts: u32,
+) -> io::Result<Response> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let sock = UdpSocket::bind(local)?;
+    sock.connect(server)?;
+    sock.set_read_timeout(Some(timeout))?;
+
+    let mut buf = [0; UDP_SIZE];
+    let mut last_err = io::Error::new(io::ErrorKind::TimedOut, "dns query timed out");
+    for _ in 0..attempts.max(1) {
+        sock.send(req)?;
+        // ignore the stale or spoofed responses until timeout
+        loop {
+            let n = match sock.recv(&mut buf) {
+                Ok(n) => n,
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::WouldBlock
+                    {
+                        last_err = e;
+                        break;
+                    }
+                    return Err(e);
+                }
+            };
+            match parse_response(&buf[..n], id) {
+                Ok(Some(rsp)) => return Ok(rsp),
+                Ok(None) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Err(last_err)
+}
+
+fn query_tcp(req: &[u8], id: u16, server: SocketAddr, timeout: Duration) -> io::Result<Response> {
+    let mut stream = TcpStream::connect_timeout(server, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    // tcp messages are prefixed with the two bytes length
+    let mut msg = Vec::with_capacity(req.len() + 2);
+    msg.extend_from_slice(&(req.len() as u16).to_be_bytes());
+    msg.extend_from_slice(req);
+    stream.write_all(&msg)?;
+
+    let mut len = [0; 2];
+    stream.read_exact(&mut len)?;
+    let mut buf = vec![0; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut buf)?;
+    parse_response(&buf, id)?.ok_or_else(|| invalid_data("mismatched dns response id"))
+}
+
+#[derive(Debug, PartialEq)]
+enum Response {
+    Answer(Vec<IpAddr>),
+    Truncated,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn encode_query(id: u16, name: &str, qtype: u16) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid dns name");
+    let name = name.strip_suffix('.').unwrap_or(name);
+    if name.is_empty() || name.len() > 253 {
+        return Err(invalid());
+    }
+
+    let mut buf = Vec::with_capacity(18 + name.len());
+    buf.extend_from_slice(&id.to_be_bytes());
+    // standard query with recursion desired
+    buf.extend_from_slice(&0x0100u16.to_be_bytes());
+    // one question, no other records
+    buf.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(invalid());
+        }
+        buf.push(label.len() as u8);
+        buf.extend_from_slice(label.as_bytes());
+    }
+    buf.push(0);
+    buf.extend_from_slice(&qtype.to_be_bytes());
+    buf.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(buf)
+}
+
+// return None if the response is not for the query id
+fn parse_response(buf: &[u8], id: u16) -> io::Result<Option<Response>> {
+    let mut r = Reader { buf, pos: 0 };
+    if r.u16()? != id {
+        return Ok(None);
+    }
+    let flags = r.u16()?;
+    if flags & 0x8000 == 0 {
+        // not a response
+        return Ok(None);
+    }
+    if flags & 0x0200 != 0 {
+        return Ok(Some(Response::Truncated));
+    }
+    match flags & 0x000f {
+        0 => {}
+        3 => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "dns name not exist",
+            ))
+        }
+        rcode => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("dns server error, rcode = {}", rcode),
+            ))
+        }
+    }
+
+    let qdcount = r.u16()?;
+    let ancount = r.u16()?;
+    // skip the authority and additional counts
+    r.skip(4)?;
+    for _ in 0..qdcount {
+        r.skip_name()?;
+        // type and class
+        r.skip(4)?;
+    }
+
+    let mut addrs = Vec::new();
+    for _ in 0..ancount {
+        r.skip_name()?;
+        let rtype = r.u16()?;
+        let class = r.u16()?;
+        // ttl
+        r.skip(4)?;
+        let len = r.u16()? as usize;
+        let data = r.take(len)?;
+        match (rtype, class, len) {
+            (TYPE_A, CLASS_IN, 4) => {
+                addrs.push(IpAddr::from(<[u8; 4]>::try_from(data).unwrap()));
+            }
+            (TYPE_AAAA, CLASS_IN, 16) => {
+                addrs.push(IpAddr::from(<[u8; 16]>::try_from(data).unwrap()));
+            }
+            // CNAME and the others
+            _ => {}
+        }
+    }
+    Ok(Some(Response::Answer(addrs)))
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        let end = self.pos + n;
+        if end > self.buf.len() {
+            return Err(invalid_data("short dns response"));
+        }
+        let data = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(data)
+    }
+
+    fn skip(&mut self, n: usize) -> io::Result<()> {
+        self.take(n).map(|_| ())
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    // skip a possibly compressed name
+    fn skip_name(&mut self) -> io::Result<()> {
+        loop {
+            let len = self.take(1)?[0];
+            match len {
+                0 => return Ok(()),
+                // the pointer ends the name
+                l if l & 0xc0 == 0xc0 => return self.skip(1),
+                l if l & 0xc0 == 0 => self.skip(l as usize)?,
+                _ => return Err(invalid_data("invalid dns name label")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::TcpListener;
+
+    // build the response for the query, flags is or-ed to the response flags
+    fn response(req: &[u8], flags: u16, a: &[Ipv4Addr], aaaa: &[Ipv6Addr]) -> Vec<u8> {
+        let qtype = u16::from_be_bytes([req[req.len() - 4], req[req.len() - 3]]);
+        let answers: Vec<Vec<u8>> = match qtype {
+            TYPE_A => a.iter().map(|ip| ip.octets().to_vec()).collect(),
+            _ => aaaa.iter().map(|ip| ip.octets().to_vec()).collect(),
+        };
+
+        let mut rsp = req[..2].to_vec();
+        rsp.extend_from_slice(&(0x8180 | flags).to_be_bytes());
+        rsp.extend_from_slice(&[0, 1]);
+        rsp.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        rsp.extend_from_slice(&[0, 0, 0, 0]);
+        rsp.extend_from_slice(&req[12..]);
+        for data in answers {
+            // pointer to the question name
+            rsp.extend_from_slice(&[0xc0, 12]);
+            rsp.extend_from_slice(&qtype.to_be_bytes());
+            rsp.extend_from_slice(&CLASS_IN.to_be_bytes());
+            rsp.extend_from_slice(&60u32.to_be_bytes());
+            rsp.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            rsp.extend_from_slice(&data);
+        }
+        rsp
+    }
+
+    #[test]
+    fn encode_name() {
+        let q = encode_query(1, "www.example.com.", TYPE_A).unwrap();
+        assert_eq!(&q[12..], b"\x03www\x07example\x03com\x00\x00\x01\x00\x01");
+        assert!(encode_query(1, "a..b", TYPE_A).is_err());
+        assert!(encode_query(1, "", TYPE_A).is_err());
+        assert!(encode_query(1, &"a".repeat(64), TYPE_A).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let req = encode_query(7, "may.test", TYPE_A).unwrap();
+        let ip = Ipv4Addr::new(1, 2, 3, 4);
+        let rsp = response(&req, 0, &[ip], &[]);
+        let addrs = vec![IpAddr::V4(ip)];
+        assert_eq!(
+            parse_response(&rsp, 7).unwrap(),
+            Some(Response::Answer(addrs))
+        );
+        // mismatched id
+        assert_eq!(parse_response(&rsp, 8).unwrap(), None);
+        // truncated
+        let rsp = response(&req, 0x0200, &[], &[]);
+        assert_eq!(parse_response(&rsp, 7).unwrap(), Some(Response::Truncated));
+        // nxdomain
+        let rsp = response(&req, 3, &[], &[]);
+        let err = parse_response(&rsp, 7).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        // short
+        assert!(parse_response(&rsp[..rsp.len() - 1], 7).is_err());
+    }
+
+    #[test]
+    fn resolve_udp_retry() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let v4 = Ipv4Addr::new(10, 0, 0, 1);
+        let v6 = Ipv6Addr::LOCALHOST;
+
+        let h = go!(move || {
+            let mut buf = [0; 512];
+            // drop the first query to trigger the retry
+            server.recv_from(&mut buf).unwrap();
+            for _ in 0..2 {
+                let (n, peer) = server.recv_from(&mut buf).unwrap();
+                let rsp = response(&buf[..n], 0, &[v4], &[v6]);
+                server.send_to(&rsp, peer).unwrap();
+            }
+        });
+
+        let addrs = go!(move || resolve_with("may.test", addr, Duration::from_millis(100), 2))
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(addrs, vec![IpAddr::V4(v4), IpAddr::V6(v6)]);
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn resolve_tcp_fallback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let udp = UdpSocket::bind(addr).unwrap();
+        let v4 = Ipv4Addr::new(10, 0, 0, 2);
+
+        let h1 = go!(move || {
+            let mut buf = [0; 512];
+            for _ in 0..2 {
+                let (n, peer) = udp.recv_from(&mut buf).unwrap();
+                udp.send_to(&response(&buf[..n], 0x0200, &[], &[]), peer)
+                    .unwrap();
+            }
+        });
+        let h2 = go!(move || {
+            for _ in 0..2 {
+                let (mut s, _) = listener.accept().unwrap();
+                let mut len = [0; 2];
+                s.read_exact(&mut len).unwrap();
+                let mut req = vec![0; u16::from_be_bytes(len) as usize];
+                s.read_exact(&mut req).unwrap();
+                let rsp = response(&req, 0, &[v4], &[]);
+                s.write_all(&(rsp.len() as u16).to_be_bytes()).unwrap();
+                s.write_all(&rsp).unwrap();
+            }
+        });
+
+        let addrs = go!(move || resolve("may.test", addr)).join().unwrap();
+        assert_eq!(addrs.unwrap(), vec![IpAddr::V4(v4)]);
+        h1.join().unwrap();
+        h2.join().unwrap();
+    }
+
+    #[test]
+    fn resolve_not_found() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let h = go!(move || {
+            let mut buf = [0; 512];
+            for _ in 0..2 {
+                let (n, peer) = server.recv_from(&mut buf).unwrap();
+                server
+                    .send_to(&response(&buf[..n], 3, &[], &[]), peer)
+                    .unwrap();
+            }
+        });
+        let err = resolve("none.test", addr).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn resolve_aaaa_dropped() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let v4 = Ipv4Addr::new(10, 0, 0, 3);
+
+        let h = go!(move || {
+            let mut buf = [0; 512];
+            // answer the A query and drop all the AAAA ones
+            for _ in 0..3 {
+                let (n, peer) = server.recv_from(&mut buf).unwrap();
+                let qtype = u16::from_be_bytes([buf[n - 4], buf[n - 3]]);
+                if qtype == TYPE_A {
+                    server
+                        .send_to(&response(&buf[..n], 0, &[v4], &[]), peer)
+                        .unwrap();
+                }
+            }
+        });
+
+        let addrs = go!(move || resolve_with("may.test", addr, Duration::from_millis(50), 2))
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(addrs, vec![IpAddr::V4(v4)]);
+        h.join().unwrap();
+    }
+}
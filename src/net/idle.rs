@@ -0,0 +1,163 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::Shutdown;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::coroutine::JoinHandle;
+use crate::net::TcpStream;
+use crate::time::Timer;
+
+// the activity shared with the watcher
+struct Activity {
+    base: Instant,
+    // nanos since base of the last read or write
+    last: AtomicU64,
+    // wake up the watcher, canceled when the guard is dropped
+    timer: Timer,
+    // set when the guard is dropped, a reset would re-arm the canceled timer
+    stop: AtomicBool,
+}
+
+impl Activity {
+    fn touch(&self) {
+        let now = self.base.elapsed().as_nanos() as u64;
+        self.last.store(now, Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.base + Duration::from_nanos(self.last.load(Ordering::Relaxed))
+    }
+}
+
+/// A connection wrapper that manages the idle timeout
+///
+/// every successful read or write through the guard counts as activity.
+/// a watcher coroutine checks the idle time: when it exceeds the `soft`
+/// threshold the `on_idle` hook is invoked once with a clone of the stream,
+/// e.g. to send a keepalive ping. when it exceeds the `hard` threshold the
+/// connection is shut down, which wakes up any parked read with EOF.
+///
+/// the activity tracking is just an atomic store, the watcher only wakes
+/// up when a threshold may be reached.
+pub struct IdleGuard {
+    stream: TcpStream,
+    activity: Arc<Activity>,
+    watcher: Option<JoinHandle<()>>,
+}
+
+impl IdleGuard {
+    /// wrap the stream and start the idle watcher
+    ///
+    /// `hard` should be greater than `soft`, the ping is skipped otherwise
+    pub fn new<F>(stream: TcpStream, soft: Duration, hard: Duration, on_idle: F) -> io::Result<Self>
+    where
+        F: Fn(&mut TcpStream) -> io::Result<()> + Send + 'static,
+    {
+        let mut peer = stream.try_clone()?;
+        let activity = Arc::new(Activity {
+            base: Instant::now(),
+            last: AtomicU64::new(0),
+            timer: Timer::new(soft.min(hard)),
+            stop: AtomicBool::new(false),
+        });
+
+        let a = activity.clone();
+        let watcher = go!(move || {
+            // the last activity that the hook is called for
+            let mut pinged = None;
+            while a.timer.wait() {
+                let last = a.last();
+                let idle = last.elapsed();
+                if idle >= hard {
+                    peer.shutdown(Shutdown::Both).ok();
+                    return;
+                }
+                let next = if idle < soft {
+                    soft
+                } else {
+                    if pinged != Some(last) {
+                        pinged = Some(last);
+                        if let Err(e) = on_idle(&mut peer) {
+                            warn!("idle hook failed, err = {:?}", e);
+                        }
+                    }
+                    hard
+                };
+                // sleep until the next threshold from the last activity
+                a.timer
+                    .reset((last + next).saturating_duration_since(Instant::now()));
+                // the cancel may be overwritten by the reset above
+                if a.stop.load(Ordering::SeqCst) {
+                    return;
+                }
+            }
+        });
+
+        Ok(IdleGuard {
+            stream,
+            activity,
+            watcher: Some(watcher),
+        })
+    }
+
+    /// return the instant of the last read or write
+    pub fn last_activity(&self) -> Instant {
+        self.activity.last()
+    }
+
+    /// get a reference to the inner stream
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    /// get a mutable reference to the inner stream
+    ///
+    /// the io through it directly is not counted as activity
+    pub fn get_mut(&mut self) -> &mut TcpStream {
+        &mut self.stream
+    }
+}
+
+impl Read for IdleGuard {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stream.read(buf)?;
+        if n > 0 {
+            self.activity.touch();
+        }
+        Ok(n)
+    }
+}
+
+impl Write for IdleGuard {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.stream.write(buf)?;
+        self.activity.touch();
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Drop for IdleGuard {
+    fn drop(&mut self) {
+        // stop the watcher
+        self.activity.stop.store(true, Ordering::SeqCst);
+        self.activity.timer.cancel();
+        if let Some(w) = self.watcher.take() {
+            w.join().ok();
+        }
+    }
+}
+
+impl fmt::Debug for IdleGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IdleGuard")
+            .field("stream", &self.stream)
+            .field("last_activity", &self.last_activity())
+            .finish()
+    }
+}
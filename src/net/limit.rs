@@ -0,0 +1,45 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// 0 means no limit
+static MAX_OPEN_SOCKETS: AtomicUsize = AtomicUsize::new(0);
+static OPEN_SOCKETS: AtomicUsize = AtomicUsize::new(0);
+
+/// set the max number of sockets that can be registered to the runtime
+///
+/// once the cap is hit, creating new sockets, including `TcpStream`,
+/// `TcpListener`, `UdpSocket` and the accepted or connected streams, fails
+/// with an `EMFILE` error just like the process runs out of fds. for
+/// `TcpListener::accept` the connection is already accepted by the kernel,
+/// it's closed immediately and the accept backs off by its backoff policy.
+/// pass 0 to remove the cap, which is the default
+///
+/// the cap is only enforced on unix
+pub fn set_max_open_sockets(n: usize) {
+    MAX_OPEN_SOCKETS.store(n, Ordering::Relaxed);
+}
+
+/// return the number of sockets that are currently registered to the runtime
+///
+/// the sockets are only tracked on unix, always return 0 on the others
+pub fn open_socket_count() -> usize {
+    OPEN_SOCKETS.load(Ordering::Relaxed)
+}
+
+// count a new socket, fail if the cap is hit
+#[cfg(unix)]
+pub(crate) fn acquire_socket() -> io::Result<()> {
+    let max = MAX_OPEN_SOCKETS.load(Ordering::Relaxed);
+    let cnt = OPEN_SOCKETS.fetch_add(1, Ordering::AcqRel);
+    if max != 0 && cnt >= max {
+        OPEN_SOCKETS.fetch_sub(1, Ordering::AcqRel);
+        return Err(io::Error::from_raw_os_error(libc::EMFILE));
+    }
+    Ok(())
+}
+
+// uncount a socket
+#[cfg(unix)]
+pub(crate) fn release_socket() {
+    OPEN_SOCKETS.fetch_sub(1, Ordering::AcqRel);
+}
@@ -0,0 +1,138 @@
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use crate::net::TcpListener;
+use socket2::{Domain, Socket, Type};
+
+/// A builder to set the socket options before bind and listen
+///
+/// the defaults are the same as `TcpListener::bind`
+///
+/// # Examples
+///
+/// ```rust
+/// use may::net::TcpListenerBuilder;
+///
+/// let listener = TcpListenerBuilder::new()
+///     .reuse_port(false)
+///     .backlog(1024)
+///     .bind("127.0.0.1:0")
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TcpListenerBuilder {
+    reuse_address: bool,
+    reuse_port: bool,
+    backlog: i32,
+    defer_accept: Option<u32>,
+    fast_open: Option<u32>,
+    ttl: Option<u32>,
+    only_v6: Option<bool>,
+}
+
+impl Default for TcpListenerBuilder {
+    fn default() -> Self {
+        TcpListenerBuilder {
+            reuse_address: true,
+            reuse_port: cfg!(unix),
+            backlog: 256,
+            defer_accept: None,
+            fast_open: None,
+            ttl: None,
+            only_v6: None,
+        }
+    }
+}
+
+impl TcpListenerBuilder {
+    /// create a builder with the default options
+    pub fn new() -> Self {
+        TcpListenerBuilder::default()
+    }
+
+    /// set `SO_REUSEADDR`, default true
+    pub fn reuse_address(mut self, on: bool) -> TcpListenerBuilder {
+        self.reuse_address = on;
+        self
+    }
+
+    /// set `SO_REUSEPORT`, default true on unix, ignored on windows
+    ///
+    /// on linux the listeners that bind the same port with it share the
+    /// incoming connections, so each worker could run its own listener
+    pub fn reuse_port(mut self, on: bool) -> TcpListenerBuilder {
+        self.reuse_port = on;
+        self
+    }
+
+    /// set the listen backlog, default 256
+    pub fn backlog(mut self, backlog: i32) -> TcpListenerBuilder {
+        self.backlog = backlog;
+        self
+    }
+
+    /// defer the accept until data arrives, see `TcpListener::set_defer_accept`
+    pub fn defer_accept(mut self, secs: u32) -> TcpListenerBuilder {
+        self.defer_accept = Some(secs);
+        self
+    }
+
+    /// enable TCP Fast Open, see `TcpListener::set_fast_open`
+    pub fn fast_open(mut self, qlen: u32) -> TcpListenerBuilder {
+        self.fast_open = Some(qlen);
+        self
+    }
+
+    /// set the `IP_TTL`, which is inherited by the accepted streams
+    pub fn ttl(mut self, ttl: u32) -> TcpListenerBuilder {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// set `IPV6_V6ONLY` to control the dual-stack behavior
+    ///
+    /// only applied when binding to an IPv6 address
+    pub fn only_v6(mut self, on: bool) -> TcpListenerBuilder {
+        self.only_v6 = Some(on);
+        self
+    }
+
+    /// create the listener with the options and bind to the address
+    pub fn bind<A: ToSocketAddrs>(&self, addr: A) -> io::Result<TcpListener> {
+        let mut addrs = addr.to_socket_addrs()?;
+        let addr = addrs.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no socket addresses resolved")
+        })?;
+        let socket = match &addr {
+            SocketAddr::V4(_) => Socket::new(Domain::IPV4, Type::STREAM, None)?,
+            SocketAddr::V6(_) => Socket::new(Domain::IPV6, Type::STREAM, None)?,
+        };
+
+        // all the options that must be set before bind
+        // windows not have reuse port but reuse address is not safe
+        socket.set_reuse_address(self.reuse_address)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(self.reuse_port)?;
+        if let (Some(on), SocketAddr::V6(_)) = (self.only_v6, &addr) {
+            socket.set_only_v6(on)?;
+        }
+        if let Some(ttl) = self.ttl {
+            socket.set_ttl(ttl)?;
+        }
+
+        socket.bind(&addr.into())?;
+        for addr in addrs {
+            socket.bind(&addr.into())?;
+        }
+        socket.listen(self.backlog)?;
+
+        let listener = TcpListener::new(socket.into())?;
+        if let Some(secs) = self.defer_accept {
+            listener.set_defer_accept(secs)?;
+        }
+        if let Some(qlen) = self.fast_open {
+            listener.set_fast_open(qlen)?;
+        }
+        Ok(listener)
+    }
+}
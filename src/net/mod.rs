@@ -1,8 +1,35 @@
 //! Networking primitives
 //!
 
+mod config;
+mod connector;
+#[cfg(unix)]
+mod datagram_ring;
+pub mod dns;
+mod idle;
+pub(crate) mod limit;
+mod listener_builder;
+mod resolver;
+mod ring_buf;
+mod serve;
 mod tcp;
 mod udp;
 
-pub use self::tcp::{TcpListener, TcpStream};
+pub use self::config::{config, NetConfig};
+pub use self::connector::TcpConnector;
+#[cfg(unix)]
+pub use self::datagram_ring::DatagramRing;
+pub use self::idle::IdleGuard;
+pub use self::limit::{open_socket_count, set_max_open_sockets};
+pub use self::listener_builder::TcpListenerBuilder;
+pub use self::resolver::{resolve, set_resolver, Resolver};
+pub use self::ring_buf::RingBuf;
+pub use self::serve::{Handler, ServeHandle};
+#[cfg(unix)]
+pub use self::tcp::RecvFlags;
+pub use self::tcp::{
+    AcceptAction, AcceptPermit, Incoming, IncomingWithAddr, TcpInfo, TcpListener, TcpStream,
+};
 pub use self::udp::UdpSocket;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub use self::udp::{RecvMsg, SendMsg};
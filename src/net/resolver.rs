@@ -0,0 +1,44 @@
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::blocking::run_blocking;
+
+/// custom DNS resolver type, resolve the host and port to socket addresses
+pub type Resolver = Box<dyn Fn(&str, u16) -> io::Result<Vec<SocketAddr>> + Send + Sync>;
+
+static RESOLVER: AtomicPtr<Resolver> = AtomicPtr::new(ptr::null_mut());
+
+/// set the custom DNS resolver used by [`resolve`] and [`TcpStream::connect_host`]
+///
+/// the resolver is used instead of the system one for host names, IP
+/// addresses are never passed to it. when called in a coroutine, the resolver
+/// runs on a small pool of blocking threads, so it can do blocking IO without
+/// stalling the worker. a later call would replace the previous resolver
+///
+/// [`resolve`]: fn.resolve.html
+/// [`TcpStream::connect_host`]: struct.TcpStream.html#method.connect_host
+pub fn set_resolver(resolver: Resolver) {
+    // the old one may still be used by others, just leak it
+    RESOLVER.swap(Box::into_raw(Box::new(resolver)), Ordering::AcqRel);
+}
+
+/// resolve the host and port to socket addresses
+///
+/// use the resolver set by [`set_resolver`] if any, or the system one
+///
+/// [`set_resolver`]: fn.set_resolver.html
+pub fn resolve(host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+
+    let resolver = match unsafe { RESOLVER.load(Ordering::Acquire).as_ref() } {
+        Some(resolver) => resolver,
+        None => return (host, port).to_socket_addrs().map(Iterator::collect),
+    };
+
+    let host = host.to_owned();
+    run_blocking(move || resolver(&host, port))
+}
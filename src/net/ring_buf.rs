@@ -0,0 +1,142 @@
+/// A fixed size byte ring buffer for streaming reads
+///
+/// the data is appended at the write cursor and consumed from the read
+/// cursor, both wrap around the end of the buffer. the free region is
+/// at most two contiguous segments, so reading into the ring never
+/// needs a temporary buffer.
+///
+/// it's filled by [`TcpStream::read_into_ring`]
+///
+/// [`TcpStream::read_into_ring`]: struct.TcpStream.html#method.read_into_ring
+pub struct RingBuf {
+    buf: Box<[u8]>,
+    // the read cursor
+    head: usize,
+    // the number of readable bytes
+    len: usize,
+}
+
+impl RingBuf {
+    /// create a ring that could hold `capacity` bytes
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "empty ring buffer");
+        RingBuf {
+            buf: vec![0; capacity].into_boxed_slice(),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// the max number of bytes the ring could hold
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// the number of readable bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// return true if there is no readable bytes
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// return true if there is no free space
+    pub fn is_full(&self) -> bool {
+        self.len == self.buf.len()
+    }
+
+    /// the number of free bytes
+    pub fn free_len(&self) -> usize {
+        self.buf.len() - self.len
+    }
+
+    /// the readable bytes, the second slice is not empty when the data
+    /// wraps around the end of the buffer
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        let cap = self.buf.len();
+        let end = self.head + self.len;
+        if end <= cap {
+            (&self.buf[self.head..end], &[])
+        } else {
+            (&self.buf[self.head..], &self.buf[..end - cap])
+        }
+    }
+
+    /// the free space, the second slice is not empty when the free space
+    /// wraps around the end of the buffer
+    pub fn free_slices_mut(&mut self) -> (&mut [u8], &mut [u8]) {
+        let cap = self.buf.len();
+        let tail = (self.head + self.len) % cap;
+        if self.is_full() {
+            (&mut [], &mut [])
+        } else if tail >= self.head {
+            // the free space is [tail, cap) and [0, head)
+            let (front, back) = self.buf.split_at_mut(tail);
+            (back, &mut front[..self.head])
+        } else {
+            (&mut self.buf[tail..self.head], &mut [])
+        }
+    }
+
+    /// move the write cursor after `n` bytes are written to the free space
+    pub fn advance(&mut self, n: usize) {
+        assert!(n <= self.free_len(), "advance beyond the free space");
+        self.len += n;
+    }
+
+    /// drop the first `n` readable bytes
+    pub fn consume(&mut self, n: usize) {
+        assert!(n <= self.len, "consume beyond the readable bytes");
+        self.head = (self.head + n) % self.buf.len();
+        self.len -= n;
+    }
+
+    /// drop all the readable bytes
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+}
+
+impl std::fmt::Debug for RingBuf {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RingBuf")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_around() {
+        let mut ring = RingBuf::new(8);
+        let (a, b) = ring.free_slices_mut();
+        assert_eq!((a.len(), b.len()), (8, 0));
+        a[..6].copy_from_slice(b"abcdef");
+        ring.advance(6);
+        ring.consume(4);
+        assert_eq!(ring.as_slices(), (&b"ef"[..], &b""[..]));
+
+        // the free space is split by the end of the buffer
+        let (a, b) = ring.free_slices_mut();
+        assert_eq!((a.len(), b.len()), (2, 4));
+        a.copy_from_slice(b"gh");
+        b[..3].copy_from_slice(b"ijk");
+        ring.advance(5);
+        assert_eq!(ring.as_slices(), (&b"efgh"[..], &b"ijk"[..]));
+
+        // only one contiguous segment is free
+        let (a, b) = ring.free_slices_mut();
+        assert_eq!((a.len(), b.len()), (1, 0));
+        ring.advance(1);
+        assert!(ring.is_full());
+        let (a, b) = ring.free_slices_mut();
+        assert!(a.is_empty() && b.is_empty());
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::thread;
+
+use crate::coroutine::JoinHandle;
+use crate::net::{TcpListener, TcpStream};
+use crate::sync::RwLock;
+
+/// the per connection handler type used by [`TcpListener::serve`]
+///
+/// [`TcpListener::serve`]: struct.TcpListener.html#method.serve
+pub type Handler = Arc<dyn Fn(TcpStream) + Send + Sync>;
+
+/// A handle to a running accept loop created by [`TcpListener::serve`]
+///
+/// [`TcpListener::serve`]: struct.TcpListener.html#method.serve
+pub struct ServeHandle {
+    handler: Arc<RwLock<Handler>>,
+    co: JoinHandle<io::Result<()>>,
+}
+
+impl ServeHandle {
+    /// atomically replace the connection handler
+    ///
+    /// new connections would use the new handler, while the in-flight ones
+    /// finish with the old one
+    pub fn set_handler<F>(&self, handler: F)
+    where
+        F: Fn(TcpStream) + Send + Sync + 'static,
+    {
+        *self.handler.write().unwrap() = Arc::new(handler);
+    }
+
+    /// get the accept loop coroutine join handle
+    pub fn join_handle(&self) -> &JoinHandle<io::Result<()>> {
+        &self.co
+    }
+
+    /// wait for the accept loop to exit, return the accept error that stops it
+    pub fn join(self) -> thread::Result<io::Result<()>> {
+        self.co.join()
+    }
+}
+
+impl fmt::Debug for ServeHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("ServeHandle { .. }")
+    }
+}
+
+impl TcpListener {
+    /// run the accept loop in a coroutine, each connection is handled by
+    /// the handler in a new coroutine
+    ///
+    /// the loop stops at the first accept error. the handler can be replaced
+    /// at runtime by [`ServeHandle::set_handler`]
+    ///
+    /// [`ServeHandle::set_handler`]: struct.ServeHandle.html#method.set_handler
+    pub fn serve<F>(self, handler: F) -> ServeHandle
+    where
+        F: Fn(TcpStream) + Send + Sync + 'static,
+    {
+        let handler: Arc<RwLock<Handler>> = Arc::new(RwLock::new(Arc::new(handler)));
+        let h = handler.clone();
+        let co = go!(move || -> io::Result<()> {
+            loop {
+                let (stream, _) = self.accept()?;
+                // hold the current handler for the whole connection
+                let f = h.read().unwrap().clone();
+                go!(move || f(stream));
+            }
+        });
+        ServeHandle { handler, co }
+    }
+}
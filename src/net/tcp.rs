@@ -1,17 +1,187 @@
+use std::any::Any;
+use std::fmt;
 use std::io::{self, Read, Write};
 use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use crate::coroutine_impl::is_coroutine;
 use crate::io as io_impl;
 use crate::io::net as net_impl;
+use crate::net::config as net_config;
+use crate::net::RingBuf;
 use crate::sync::atomic_dur::AtomicDuration;
+use crate::sync::Semphore;
 use crate::yield_now::yield_with;
 
+// the max size of the data returned by `TcpStream::shutdown_and_drain`
+const MAX_DRAIN: usize = 16 * 1024 * 1024;
+
+// ===== RecvFlags =====
+//
+//
+
+/// The flags for [`TcpStream::recv_with_flags`]
+///
+/// [`TcpStream::recv_with_flags`]: struct.TcpStream.html#method.recv_with_flags
+#[cfg(unix)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RecvFlags(libc::c_int);
+
+#[cfg(unix)]
+impl RecvFlags {
+    /// wait until the whole buffer is filled, `MSG_WAITALL`
+    pub const WAITALL: RecvFlags = RecvFlags(libc::MSG_WAITALL);
+    /// never block, return `WouldBlock` if no data, `MSG_DONTWAIT`
+    pub const DONTWAIT: RecvFlags = RecvFlags(libc::MSG_DONTWAIT);
+    /// return the data without removing it from the queue, `MSG_PEEK`
+    pub const PEEK: RecvFlags = RecvFlags(libc::MSG_PEEK);
+
+    /// no flags
+    pub const fn empty() -> RecvFlags {
+        RecvFlags(0)
+    }
+
+    /// return the raw flags value
+    pub const fn bits(&self) -> libc::c_int {
+        self.0
+    }
+
+    /// return true if all the flags in `other` are set
+    pub const fn contains(&self, other: RecvFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+#[cfg(unix)]
+impl std::ops::BitOr for RecvFlags {
+    type Output = RecvFlags;
+    fn bitor(self, other: RecvFlags) -> RecvFlags {
+        RecvFlags(self.0 | other.0)
+    }
+}
+
+// ===== TcpInfo =====
+//
+//
+
+/// Kernel TCP statistics of a connection, returned by [`TcpStream::tcp_info`]
+///
+/// [`TcpStream::tcp_info`]: struct.TcpStream.html#method.tcp_info
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpInfo {
+    /// the raw TCP state, e.g. 1 for `TCP_ESTABLISHED`
+    pub state: u8,
+    /// smoothed round trip time
+    pub rtt: Duration,
+    /// round trip time variance
+    pub rttvar: Duration,
+    /// congestion window in segments
+    pub snd_cwnd: u32,
+    /// number of segments being retransmitted
+    pub retrans: u32,
+    /// total number of retransmitted segments of the connection
+    pub total_retrans: u32,
+}
+
+// ===== AcceptAction =====
+//
+//
+
+/// The action for an accept error, decided by the accept backoff policy
+///
+/// see [`TcpListener::set_accept_backoff`]
+///
+/// [`TcpListener::set_accept_backoff`]: struct.TcpListener.html#method.set_accept_backoff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptAction {
+    /// retry the accept immediately
+    Retry,
+    /// sleep for the duration and then retry the accept
+    RetryAfter(Duration),
+    /// return the error to the caller
+    Fail,
+}
+
+impl AcceptAction {
+    /// the default accept backoff policy
+    ///
+    /// retry `ECONNABORTED` and `EINTR` immediately, back off 100ms on
+    /// `EMFILE` and `ENFILE`, and fail for all the others
+    pub fn for_error(e: &io::Error) -> AcceptAction {
+        match e.kind() {
+            io::ErrorKind::ConnectionAborted | io::ErrorKind::Interrupted => {
+                return AcceptAction::Retry
+            }
+            _ => {}
+        }
+        #[cfg(unix)]
+        {
+            if let Some(libc::EMFILE) | Some(libc::ENFILE) = e.raw_os_error() {
+                return AcceptAction::RetryAfter(Duration::from_millis(100));
+            }
+        }
+        AcceptAction::Fail
+    }
+}
+
+type AcceptBackoff = Arc<dyn Fn(&io::Error) -> AcceptAction + Send + Sync>;
+
+// run the accept until success or the policy decides to fail, the backoff
+// sleep never goes beyond the deadline
+fn retry_accept<T, F>(
+    policy: &dyn Fn(&io::Error) -> AcceptAction,
+    deadline: Option<Instant>,
+    mut f: F,
+) -> io::Result<T>
+where
+    F: FnMut() -> io::Result<T>,
+{
+    loop {
+        let e = match f() {
+            Ok(t) => return Ok(t),
+            Err(e) => e,
+        };
+        match policy(&e) {
+            AcceptAction::Retry => {}
+            AcceptAction::RetryAfter(dur) => {
+                let dur = match deadline {
+                    None => dur,
+                    Some(deadline) => {
+                        let left = deadline.saturating_duration_since(Instant::now());
+                        if left == Duration::from_secs(0) {
+                            return Err(io::Error::new(
+                                io::ErrorKind::TimedOut,
+                                "accept timed out",
+                            ));
+                        }
+                        dur.min(left)
+                    }
+                };
+                crate::coroutine::sleep(dur)
+            }
+            AcceptAction::Fail => return Err(e),
+        }
+    }
+}
+
 // ===== TcpStream =====
 //
 //
 
+// restore the stream state when dropped, so a closure that unwinds, like
+// a canceled coroutine, doesn't leave the stream changed
+struct Restore<'a, F: FnMut(&mut TcpStream)> {
+    stream: &'a mut TcpStream,
+    restore: F,
+}
+
+impl<'a, F: FnMut(&mut TcpStream)> Drop for Restore<'a, F> {
+    fn drop(&mut self) {
+        (self.restore)(self.stream);
+    }
+}
+
 #[derive(Debug)]
 pub struct TcpStream {
     io: io_impl::IoData,
@@ -19,6 +189,13 @@ pub struct TcpStream {
     ctx: io_impl::IoContext,
     read_timeout: AtomicDuration,
     write_timeout: AtomicDuration,
+    // the shared deadline for all reads in `with_read_deadline`
+    read_deadline: Option<Instant>,
+    // there is data held back by `write_more`
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    more_pending: bool,
+    // the user data attached by `set_context`
+    context: Option<Box<dyn Any + Send + Sync>>,
 }
 
 impl TcpStream {
@@ -28,19 +205,49 @@ impl TcpStream {
         // to avoid unnecessary context switch
         s.set_nonblocking(true)?;
 
-        io_impl::add_socket(&s).map(|io| TcpStream {
-            io,
-            sys: s,
-            ctx: io_impl::IoContext::new(),
-            read_timeout: AtomicDuration::new(None),
-            write_timeout: AtomicDuration::new(None),
-        })
+        io_impl::add_socket(&s).map(|io| TcpStream::from_stream(s, io))
     }
 
     pub fn inner(&self) -> &net::TcpStream {
         &self.sys
     }
 
+    /// attach a value to the stream, replacing the previous one
+    ///
+    /// it's useful to carry the connection scoped data like the peer
+    /// identity through the layers of a server. the value is dropped
+    /// with the stream, and is not shared by the `try_clone` streams
+    pub fn set_context<T: Any + Send + Sync>(&mut self, value: T) {
+        self.context = Some(Box::new(value));
+    }
+
+    /// get the value attached by `set_context`
+    ///
+    /// return `None` if there is no value or it's not of type `T`
+    pub fn context<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.context.as_ref()?.downcast_ref()
+    }
+
+    /// get the mutable value attached by `set_context`
+    ///
+    /// return `None` if there is no value or it's not of type `T`
+    pub fn context_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        self.context.as_mut()?.downcast_mut()
+    }
+
+    /// detach the value attached by `set_context`
+    ///
+    /// return `None` and keep the value if it's not of type `T`
+    pub fn take_context<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        match self.context.take()?.downcast() {
+            Ok(v) => Some(*v),
+            Err(v) => {
+                self.context = Some(v);
+                None
+            }
+        }
+    }
+
     pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
         if !is_coroutine() {
             let s = net::TcpStream::connect(addr)?;
@@ -62,9 +269,39 @@ impl TcpStream {
         c.done()
     }
 
-    pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    /// connect to the host, the host name is resolved by [`resolve`]
+    ///
+    /// unlike `connect`, this would use the custom resolver set by
+    /// [`set_resolver`] for host names
+    ///
+    /// [`resolve`]: fn.resolve.html
+    /// [`set_resolver`]: fn.set_resolver.html
+    pub fn connect_host(host: &str, port: u16) -> io::Result<TcpStream> {
+        let addrs = super::resolve(host, port)?;
+        TcpStream::connect(&addrs[..])
+    }
+
+    /// connect to the address with a timeout
+    ///
+    /// each resolved address is tried in turn with the full timeout, the
+    /// error of the last one is returned, which is of kind `TimedOut` if
+    /// the connection is not established in time
+    pub fn connect_timeout<A: ToSocketAddrs>(addr: A, timeout: Duration) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs()? {
+            match Self::connect_addr_timeout(addr, timeout) {
+                Ok(s) => return Ok(s),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no socket addresses resolved")
+        }))
+    }
+
+    fn connect_addr_timeout(addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
         if !is_coroutine() {
-            let s = net::TcpStream::connect_timeout(addr, timeout)?;
+            let s = net::TcpStream::connect_timeout(&addr, timeout)?;
             s.set_nonblocking(true)?;
             let io = io_impl::add_socket(&s)?;
             return Ok(TcpStream::from_stream(s, io));
@@ -113,6 +350,10 @@ impl TcpStream {
             ctx: io_impl::IoContext::new(),
             read_timeout: AtomicDuration::new(self.read_timeout.get()),
             write_timeout: AtomicDuration::new(self.write_timeout.get()),
+            read_deadline: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            more_pending: false,
+            context: None,
         })
     }
 
@@ -120,6 +361,115 @@ impl TcpStream {
         self.sys.shutdown(how)
     }
 
+    /// half close the stream after all the pending data is written
+    ///
+    /// this would flush the stream and then shut down the write half, the
+    /// peer would see an EOF while the read half is still open to receive
+    /// the response
+    pub fn finish_writing(&self) -> io::Result<()> {
+        // TcpStream has no user space buffer, flush is just a sys call
+        (&self.sys).flush()?;
+        self.shutdown(Shutdown::Write)
+    }
+
+    /// half close the stream and read the rest of the peer's data until EOF
+    ///
+    /// this is the graceful close of the request/response protocols. the
+    /// read parks the coroutine on `WouldBlock` instead of spinning, even if
+    /// the stream is set to nonblocking, and a half open peer that never
+    /// closes is bounded by the read timeout. at most 16 MiB of data is
+    /// returned, `InvalidData` is reported if the peer sends more. a peer
+    /// that already reset the connection is not an error for the shutdown,
+    /// the drain would report the reset if any
+    pub fn shutdown_and_drain(&mut self) -> io::Result<Vec<u8>> {
+        match self.finish_writing() {
+            Err(e) if e.kind() != io::ErrorKind::NotConnected => return Err(e),
+            _ => {}
+        }
+        let mut data = Vec::new();
+        self.drain(MAX_DRAIN, None, |buf| data.extend_from_slice(buf))?;
+        Ok(data)
+    }
+
+    /// read the data directly into the free space of the ring
+    ///
+    /// when the free space wraps around the end of the ring, a single
+    /// vectored read fills both segments, so there is no copy from a
+    /// temporary buffer. the write cursor of the ring is advanced by the
+    /// returned size. return `Ok(0)` without reading if the ring is full,
+    /// which is not EOF, check `RingBuf::is_full` to tell them apart
+    pub fn read_into_ring(&mut self, ring: &mut RingBuf) -> io::Result<usize> {
+        if ring.is_full() {
+            return Ok(0);
+        }
+        let (a, b) = ring.free_slices_mut();
+        let n = if b.is_empty() {
+            self.read(a)?
+        } else {
+            self.read_vectored(&mut [io::IoSliceMut::new(a), io::IoSliceMut::new(b)])?
+        };
+        ring.advance(n);
+        Ok(n)
+    }
+
+    /// close the stream after draining the pending data from the peer
+    ///
+    /// closing a socket with unread data makes the kernel send a RST, and
+    /// the peer may lose the response that is already sent. this would shut
+    /// down the write half and then read and discard the incoming data until
+    /// EOF, at most `max` bytes within `timeout`, so a misbehaving peer can't
+    /// hold the connection forever. the stream is closed in any case, an
+    /// error is returned if EOF is not seen within the bounds. the reads wait
+    /// for the data until the timeout even if the stream is set to nonblocking
+    pub fn drain_and_close(mut self, max: usize, timeout: Duration) -> io::Result<()> {
+        self.finish_writing()?;
+        self.drain(max, Some(Instant::now() + timeout), |_| {})
+    }
+
+    // read the data until EOF with a stack buffer, at most `max` bytes
+    //
+    // each piece of data is passed to `f`. the reads wait for the data no
+    // matter if the stream is set to nonblocking, bounded by the deadline if
+    // any, and the read timeout
+    fn drain<F>(&mut self, max: usize, deadline: Option<Instant>, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&[u8]),
+    {
+        let mut buf = [0u8; 1024];
+        let mut left = max;
+        let mut drain = |s: &mut Self| loop {
+            let len = buf.len().min(left.saturating_add(1));
+            match s.read(&mut buf[..len]) {
+                Ok(0) => return Ok(()),
+                Ok(n) if n > left => {
+                    let msg = "too much data to drain";
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                }
+                Ok(n) => {
+                    left -= n;
+                    f(&buf[..n]);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                // the blocking read reports the timeout as WouldBlock
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "drain timeout"));
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let nonblocking = self.ctx.swap_nonblocking(false);
+        let guard = Restore {
+            stream: self,
+            restore: move |s: &mut Self| s.ctx.set_nonblocking(nonblocking),
+        };
+        let s = &mut *guard.stream;
+        match deadline {
+            Some(deadline) => s.with_read_deadline(deadline, drain),
+            None => drain(s),
+        }
+    }
+
     pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
         self.sys.set_nodelay(nodelay)
     }
@@ -148,6 +498,328 @@ impl TcpStream {
         Ok(self.write_timeout.get())
     }
 
+    /// run the closure with a deadline that is shared by all the reads inside
+    ///
+    /// unlike the read timeout which is applied to each read, the pending read
+    /// would return `TimedOut` once the deadline is reached, no matter how many
+    /// reads already succeeded before. the read timeout is still respected if
+    /// it expires earlier than the deadline.
+    ///
+    /// in thread context the deadline is applied by the socket timeout, which
+    /// reports `WouldBlock` instead of `TimedOut` on some platforms
+    pub fn with_read_deadline<F, R>(&mut self, deadline: Instant, f: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        let old = self.read_deadline.replace(deadline);
+        let guard = Restore {
+            stream: self,
+            restore: move |s: &mut Self| s.read_deadline = old,
+        };
+        f(&mut *guard.stream)
+    }
+
+    // get the timeout for the next read, the read deadline is considered
+    fn next_read_timeout(&self) -> io::Result<Option<Duration>> {
+        let timeout = self.read_timeout.get();
+        let deadline = match self.read_deadline {
+            None => return Ok(timeout),
+            Some(deadline) => deadline,
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "read deadline"));
+        }
+        let left = deadline - now;
+        Ok(Some(timeout.map_or(left, |t| t.min(left))))
+    }
+
+    /// receive data with the flags
+    ///
+    /// with `WAITALL` the coroutine is parked until the buffer is full or
+    /// the connection is closed. the read timeout applies to each wait for
+    /// more data, if it fires after some data is received the partial count
+    /// is returned, otherwise `TimedOut`. the other errors are returned even
+    /// after a partial receive. with `DONTWAIT` it never blocks and
+    /// returns `WouldBlock` if no data is available
+    #[cfg(unix)]
+    pub fn recv_with_flags(&self, buf: &mut [u8], flags: RecvFlags) -> io::Result<usize> {
+        let fd = self.sys.as_raw_fd();
+        if flags.contains(RecvFlags::DONTWAIT)
+            || self
+                .ctx
+                .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+        {
+            return net_impl::recv(fd, buf, flags.bits());
+        }
+
+        if !self.ctx.check_context(|b| self.sys.set_nonblocking(b))? {
+            if self.read_deadline.is_none() {
+                return net_impl::recv(fd, buf, flags.bits());
+            }
+            // apply the deadline to the blocking recv
+            self.sys.set_read_timeout(self.next_read_timeout()?)?;
+            let ret = net_impl::recv(fd, buf, flags.bits());
+            self.sys.set_read_timeout(self.read_timeout.get())?;
+            return ret;
+        }
+
+        // the nonblocking socket would not wait for all, loop here instead
+        let wait_all = flags.contains(RecvFlags::WAITALL);
+        let flags = flags.bits() & !libc::MSG_WAITALL;
+        let mut n = 0;
+        loop {
+            match self.recv_once(&mut buf[n..], flags) {
+                Ok(0) => return Ok(n),
+                Ok(m) => n += m,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // only an expired wait turns into a partial count
+                Err(ref e)
+                    if n > 0
+                        && (e.kind() == io::ErrorKind::TimedOut
+                            || e.kind() == io::ErrorKind::WouldBlock) =>
+                {
+                    return Ok(n)
+                }
+                Err(e) => return Err(e),
+            }
+            if !wait_all || n == buf.len() {
+                return Ok(n);
+            }
+        }
+    }
+
+    // single recv in coroutine context
+    #[cfg(unix)]
+    fn recv_once(&self, buf: &mut [u8], flags: libc::c_int) -> io::Result<usize> {
+        let timeout = self.next_read_timeout()?;
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking recv
+        match net_impl::recv(self.sys.as_raw_fd(), buf, flags) {
+            Ok(n) => return Ok(n),
+            Err(e) => {
+                // raw_os_error is faster than kind
+                let raw_err = e.raw_os_error();
+                if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                    // do nothing here
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut reader = net_impl::SocketRecv::new(self, buf, flags, timeout);
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// write with a hint that more data would follow soon
+    ///
+    /// on linux the data is sent with `MSG_MORE`, the kernel would hold it
+    /// back to coalesce with the following writes instead of sending a small
+    /// segment. a plain `write` or `flush` pushes out all the data held back,
+    /// so always finish a batch with one of them, otherwise the data may be
+    /// delayed for up to 200ms. on other platforms it's a normal `write`
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn write_more(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.send_with_flags(buf, libc::MSG_MORE)?;
+        self.more_pending = true;
+        Ok(n)
+    }
+
+    /// write with a hint that more data would follow soon
+    ///
+    /// on linux the data is sent with `MSG_MORE`, the kernel would hold it
+    /// back to coalesce with the following writes instead of sending a small
+    /// segment. a plain `write` or `flush` pushes out all the data held back,
+    /// so always finish a batch with one of them, otherwise the data may be
+    /// delayed for up to 200ms. on other platforms it's a normal `write`
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    pub fn write_more(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write(buf)
+    }
+
+    // send with the flags, yield if not ready
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn send_with_flags(&self, buf: &[u8], flags: libc::c_int) -> io::Result<usize> {
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return net_impl::send(self.sys.as_raw_fd(), buf, flags);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking send
+        match net_impl::send(self.sys.as_raw_fd(), buf, flags) {
+            Ok(n) => return Ok(n),
+            Err(e) => {
+                // raw_os_error is faster than kind
+                let raw_err = e.raw_os_error();
+                if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                    // do nothing here
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut writer = net_impl::SocketSend::new(self, buf, flags, self.write_timeout.get());
+        yield_with(&writer);
+        writer.done()
+    }
+
+    /// receive data without removing it from the queue
+    ///
+    /// the coroutine is parked until some data arrives like `read`, and the
+    /// read timeout is respected. a following `read` returns the same data
+    #[cfg(unix)]
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.recv_with_flags(buf, RecvFlags::PEEK)
+    }
+
+    /// receive data without removing it from the queue
+    ///
+    /// a following `read` returns the same data
+    #[cfg(windows)]
+    pub fn peek(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.sys.peek(buf)
+    }
+
+    /// return the number of bytes that can be read without blocking
+    ///
+    /// it's a `FIONREAD` ioctl that never yields and doesn't consume any
+    /// data. the count only covers the bytes already in the receive buffer
+    #[cfg(unix)]
+    pub fn peek_len(&self) -> io::Result<usize> {
+        io_impl::bytes_readable(self.as_raw_fd())
+    }
+
+    /// check if the idle connection is still usable without a round trip
+    ///
+    /// this is a nonblocking one byte `MSG_PEEK` that never yields and
+    /// doesn't consume any data. pending data or `WouldBlock` means alive.
+    /// EOF means the peer closed or half closed its write side, that is
+    /// not reusable for the request/response pattern, so it's treated as
+    /// dead together with any socket error
+    #[cfg(unix)]
+    pub fn is_healthy(&self) -> bool {
+        let mut buf = [0u8; 1];
+        let flags = libc::MSG_PEEK | libc::MSG_DONTWAIT;
+        loop {
+            return match net_impl::recv(self.as_raw_fd(), &mut buf, flags) {
+                Ok(n) => n > 0,
+                Err(e) => match e.kind() {
+                    io::ErrorKind::WouldBlock => true,
+                    io::ErrorKind::Interrupted => continue,
+                    _ => false,
+                },
+            };
+        }
+    }
+
+    /// set the `SO_OOBINLINE` option
+    ///
+    /// if set, the urgent data is placed in the normal data stream and
+    /// `recv_urgent` would never see it
+    pub fn set_oob_inline(&self, oob_inline: bool) -> io::Result<()> {
+        socket2::SockRef::from(&self.sys).set_out_of_band_inline(oob_inline)
+    }
+
+    /// get the `SO_OOBINLINE` option
+    pub fn oob_inline(&self) -> io::Result<bool> {
+        socket2::SockRef::from(&self.sys).out_of_band_inline()
+    }
+
+    /// receive the pending urgent (out-of-band) byte by `MSG_OOB`
+    ///
+    /// it never blocks, return `None` if there is no urgent byte pending,
+    /// or the urgent data is inlined by `set_oob_inline`
+    #[cfg(unix)]
+    pub fn recv_urgent(&self) -> io::Result<Option<u8>> {
+        let mut buf = [0u8; 1];
+        let flags = libc::MSG_OOB | libc::MSG_DONTWAIT;
+        loop {
+            return match net_impl::recv(self.as_raw_fd(), &mut buf, flags) {
+                Ok(1) => Ok(Some(buf[0])),
+                Ok(_) => Ok(None),
+                Err(e) => match e.raw_os_error() {
+                    // no urgent data or it's not arrived yet
+                    Some(libc::EINVAL) | Some(libc::EAGAIN) => Ok(None),
+                    Some(libc::EINTR) => continue,
+                    _ => Err(e),
+                },
+            };
+        }
+    }
+
+    /// wait until an urgent (out-of-band) byte is pending and receive it
+    ///
+    /// the coroutine is waked up by the exceptional condition reported by
+    /// the selector, the normal data is left untouched. the read timeout
+    /// doesn't apply to it. the call registers the socket for the
+    /// exceptional condition, which is only supported by epoll
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn wait_urgent(&self) -> io::Result<u8> {
+        use crate::io::WaitIo;
+
+        let in_co = is_coroutine();
+        if in_co {
+            io_impl::watch_urgent(&self.io)?;
+        }
+        loop {
+            if in_co {
+                self.reset_io();
+            }
+            if let Some(b) = self.recv_urgent()? {
+                return Ok(b);
+            }
+            if in_co {
+                self.wait_io();
+                continue;
+            }
+            let mut fds = libc::pollfd {
+                fd: self.as_raw_fd(),
+                events: libc::POLLPRI,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut fds, 1, -1) } < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// read the kernel TCP statistics by `TCP_INFO`
+    #[cfg(target_os = "linux")]
+    pub fn tcp_info(&self) -> io::Result<TcpInfo> {
+        let info: libc::tcp_info =
+            io_impl::get_sockopt(self.as_raw_fd(), libc::IPPROTO_TCP, libc::TCP_INFO)?;
+        Ok(TcpInfo {
+            state: info.tcpi_state,
+            rtt: Duration::from_micros(info.tcpi_rtt as u64),
+            rttvar: Duration::from_micros(info.tcpi_rttvar as u64),
+            snd_cwnd: info.tcpi_snd_cwnd,
+            retrans: info.tcpi_retrans,
+            total_retrans: info.tcpi_total_retrans,
+        })
+    }
+
+    /// read the kernel TCP statistics
+    ///
+    /// not supported on this platform, always return an `Unsupported` error
+    #[cfg(not(target_os = "linux"))]
+    pub fn tcp_info(&self) -> io::Result<TcpInfo> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "tcp info is not supported on this platform",
+        ))
+    }
+
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.ctx.set_nonblocking(nonblocking);
         Ok(())
@@ -161,14 +833,42 @@ impl TcpStream {
         self.sys.ttl()
     }
 
+    /// set the `SO_LINGER` option
+    ///
+    /// with `Some(dur)` closing the socket waits up to `dur` for the pending
+    /// data to be sent, `Some(0)` resets the connection on close. `None`
+    /// restores the default graceful close in the background
+    pub fn set_linger(&self, linger: Option<Duration>) -> io::Result<()> {
+        socket2::SockRef::from(&self.sys).set_linger(linger)
+    }
+
+    /// get the `SO_LINGER` option
+    pub fn linger(&self) -> io::Result<Option<Duration>> {
+        socket2::SockRef::from(&self.sys).linger()
+    }
+
     // convert std::net::TcpStream to Self without add_socket
+    // the default timeouts of the net config are applied
     pub(crate) fn from_stream(s: net::TcpStream, io: io_impl::IoData) -> Self {
+        let read_timeout = net_config().get_default_read_timeout();
+        let write_timeout = net_config().get_default_write_timeout();
+        // the sys timeout is used in thread context
+        if read_timeout.is_some() {
+            s.set_read_timeout(read_timeout).ok();
+        }
+        if write_timeout.is_some() {
+            s.set_write_timeout(write_timeout).ok();
+        }
         TcpStream {
             io,
             sys: s,
             ctx: io_impl::IoContext::new(),
-            read_timeout: AtomicDuration::new(None),
-            write_timeout: AtomicDuration::new(None),
+            read_timeout: AtomicDuration::new(read_timeout),
+            write_timeout: AtomicDuration::new(write_timeout),
+            read_deadline: None,
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            more_pending: false,
+            context: None,
         }
     }
 }
@@ -178,11 +878,23 @@ impl Read for TcpStream {
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
-            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
         {
             return self.sys.read(buf);
         }
 
+        let timeout = self.next_read_timeout()?;
+
+        if !self.ctx.check_context(|b| self.sys.set_nonblocking(b))? {
+            if self.read_deadline.is_none() {
+                return self.sys.read(buf);
+            }
+            // apply the deadline to the blocking read
+            self.sys.set_read_timeout(timeout)?;
+            let ret = self.sys.read(buf);
+            self.sys.set_read_timeout(self.read_timeout.get())?;
+            return ret;
+        }
+
         #[cfg(unix)]
         {
             self.io.reset();
@@ -202,7 +914,50 @@ impl Read for TcpStream {
             }
         }
 
-        let mut reader = net_impl::SocketRead::new(self, buf, self.read_timeout.get());
+        let mut reader = net_impl::SocketRead::new(self, buf, timeout);
+        yield_with(&reader);
+        reader.done()
+    }
+
+    #[cfg(unix)]
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+        {
+            return self.sys.read_vectored(bufs);
+        }
+
+        let timeout = self.next_read_timeout()?;
+
+        if !self.ctx.check_context(|b| self.sys.set_nonblocking(b))? {
+            if self.read_deadline.is_none() {
+                return self.sys.read_vectored(bufs);
+            }
+            // apply the deadline to the blocking read
+            self.sys.set_read_timeout(timeout)?;
+            let ret = self.sys.read_vectored(bufs);
+            self.sys.set_read_timeout(self.read_timeout.get())?;
+            return ret;
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match self.sys.read_vectored(bufs) {
+            Ok(n) => return Ok(n),
+            Err(e) => {
+                // raw_os_error is faster than kind
+                let raw_err = e.raw_os_error();
+                if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                    // do nothing here
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+
+        let sys = &self.sys;
+        let mut reader = net_impl::SocketReadVectored::new(&*self, sys, bufs, timeout);
         yield_with(&reader);
         reader.done()
     }
@@ -276,6 +1031,12 @@ impl Write for TcpStream {
     }
 
     fn flush(&mut self) -> io::Result<()> {
+        // push out the data held back by `write_more`, clearing the cork
+        // would push the pending frames even the cork is not set
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        if std::mem::take(&mut self.more_pending) {
+            io_impl::set_sockopt(self.as_raw_fd(), libc::IPPROTO_TCP, libc::TCP_CORK, 0i32)?;
+        }
         // TcpStream just return Ok(()), no need to yield
         self.sys.flush()
     }
@@ -311,15 +1072,26 @@ impl io_impl::AsIoData for TcpStream {
 //
 //
 
-#[derive(Debug)]
 pub struct TcpListener {
     io: io_impl::IoData,
     ctx: io_impl::IoContext,
     sys: net::TcpListener,
+    // None for the default policy
+    backoff: Mutex<Option<AcceptBackoff>>,
+}
+
+impl fmt::Debug for TcpListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TcpListener")
+            .field("io", &self.io)
+            .field("ctx", &self.ctx)
+            .field("sys", &self.sys)
+            .finish()
+    }
 }
 
 impl TcpListener {
-    fn new(s: net::TcpListener) -> io::Result<TcpListener> {
+    pub(crate) fn new(s: net::TcpListener) -> io::Result<TcpListener> {
         // only set non blocking in coroutine context
         // we would first call nonblocking io in the coroutine
         // to avoid unnecessary context switch
@@ -329,6 +1101,7 @@ impl TcpListener {
             io,
             ctx: io_impl::IoContext::new(),
             sys: s,
+            backoff: Mutex::new(None),
         })
     }
 
@@ -337,35 +1110,78 @@ impl TcpListener {
     }
 
     pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
-        use socket2::{Domain, Socket, Type};
-        let mut addrs = addr.to_socket_addrs()?;
-        let addr = addrs.next().unwrap();
-        let listener = match &addr {
-            SocketAddr::V4(_) => Socket::new(Domain::IPV4, Type::STREAM, None)?,
-            SocketAddr::V6(_) => Socket::new(Domain::IPV6, Type::STREAM, None)?,
+        super::TcpListenerBuilder::new().bind(addr)
+    }
+
+    /// accept a new connection
+    ///
+    /// the accept errors are handled by the accept backoff policy, see
+    /// [`set_accept_backoff`](#method.set_accept_backoff)
+    pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        retry_accept(&|e| self.accept_action(e), None, || self.accept_once(None))
+    }
+
+    /// accept a new connection with a timeout
+    ///
+    /// it's the same as `accept` except that an error of kind `TimedOut`
+    /// is returned if no connection arrives in time, which is useful to
+    /// periodically check a shutdown flag. the timeout covers the retries
+    /// of the accept backoff policy
+    pub fn accept_timeout(&self, dur: Duration) -> io::Result<(TcpStream, SocketAddr)> {
+        let deadline = Instant::now().checked_add(dur);
+        // never retry when the deadline is reached
+        let policy = |e: &io::Error| match e.kind() {
+            io::ErrorKind::TimedOut => AcceptAction::Fail,
+            _ => self.accept_action(e),
         };
+        retry_accept(&policy, deadline, || self.accept_once(deadline))
+    }
 
-        // windows not have reuset port but reuse address is not safe
-        listener.set_reuse_address(true)?;
+    /// accept a new connection only when there is capacity to handle it
+    ///
+    /// a permit of the `gate` is acquired before accepting, so when all
+    /// the permits are taken by the running handlers the new connections
+    /// are held in the kernel backlog instead of being accepted into an
+    /// overloaded system. the permit is returned with the connection and
+    /// posted back to the gate when dropped, it's also released if the
+    /// accept fails or unwinds
+    pub fn accept_when(
+        &self,
+        gate: &Arc<Semphore>,
+    ) -> io::Result<(TcpStream, SocketAddr, AcceptPermit)> {
+        gate.wait();
+        let permit = AcceptPermit { gate: gate.clone() };
+        let (s, addr) = self.accept()?;
+        Ok((s, addr, permit))
+    }
 
-        #[cfg(unix)]
-        listener.set_reuse_port(true)?;
+    /// set the policy that decides the action for each accept error
+    ///
+    /// both `accept` and `incoming` consult the policy. the default one is
+    /// [`AcceptAction::for_error`]
+    ///
+    /// [`AcceptAction::for_error`]: enum.AcceptAction.html#method.for_error
+    pub fn set_accept_backoff<F>(&self, policy: F)
+    where
+        F: Fn(&io::Error) -> AcceptAction + Send + Sync + 'static,
+    {
+        *self.backoff.lock().unwrap() = Some(Arc::new(policy));
+    }
 
-        listener.bind(&addr.into())?;
-        for addr in addrs {
-            listener.bind(&addr.into())?;
+    // the policy is only looked up on error, so a successful accept
+    // never touches the lock
+    fn accept_action(&self, e: &io::Error) -> AcceptAction {
+        let backoff = self.backoff.lock().unwrap().clone();
+        match backoff {
+            Some(policy) => policy(e),
+            None => AcceptAction::for_error(e),
         }
-        listener.listen(256)?;
-
-        let s = listener.into();
-        TcpListener::new(s)
     }
 
-    pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
+    fn accept_once(&self, deadline: Option<Instant>) -> io::Result<(TcpStream, SocketAddr)> {
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
-            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
         {
             return self
                 .sys
@@ -373,6 +1189,16 @@ impl TcpListener {
                 .and_then(|(s, a)| TcpStream::new(s).map(|s| (s, a)));
         }
 
+        if !self.ctx.check_context(|b| self.sys.set_nonblocking(b))? {
+            if let Some(deadline) = deadline {
+                self.wait_acceptable(deadline)?;
+            }
+            return self
+                .sys
+                .accept()
+                .and_then(|(s, a)| TcpStream::new(s).map(|s| (s, a)));
+        }
+
         #[cfg(unix)]
         {
             self.io.reset();
@@ -390,15 +1216,63 @@ impl TcpListener {
             }
         }
 
-        let mut a = net_impl::TcpListenerAccept::new(self)?;
+        let timeout = match deadline {
+            None => None,
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(left) if left > Duration::from_secs(0) => Some(left),
+                _ => return Err(io::Error::new(io::ErrorKind::TimedOut, "accept timed out")),
+            },
+        };
+        let mut a = net_impl::TcpListenerAccept::new(self, timeout)?;
         yield_with(&a);
         a.done()
     }
 
+    // block the thread until there is a connection to accept
+    #[cfg(unix)]
+    fn wait_acceptable(&self, deadline: Instant) -> io::Result<()> {
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let ms = left.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+            let mut fds = libc::pollfd {
+                fd: self.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            match unsafe { libc::poll(&mut fds, 1, ms) } {
+                0 if Instant::now() >= deadline => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "accept timed out"))
+                }
+                0 => {}
+                n if n > 0 => return Ok(()),
+                _ => {
+                    let e = io::Error::last_os_error();
+                    if e.kind() != io::ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    // block the thread until there is a connection to accept
+    #[cfg(windows)]
+    fn wait_acceptable(&self, _deadline: Instant) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "accept timeout in thread context is not supported on this platform",
+        ))
+    }
+
     pub fn incoming(&self) -> Incoming {
         Incoming { listener: self }
     }
 
+    /// same as `incoming` except that the peer address is also yielded
+    pub fn incoming_with_addr(&self) -> IncomingWithAddr<'_> {
+        IncomingWithAddr { listener: self }
+    }
+
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.sys.local_addr()
     }
@@ -418,6 +1292,7 @@ impl TcpListener {
             io: io_impl::IoData::new(0),
             sys: s,
             ctx: io_impl::IoContext::new(),
+            backoff: Mutex::new(None),
         })
     }
 
@@ -430,6 +1305,128 @@ impl TcpListener {
         self.sys.take_error()
     }
 
+    /// defer the accept notification until the client has sent data
+    ///
+    /// so that `accept` would not be woken up by connections that never
+    /// send anything, which is ideal for request/response servers. note
+    /// that legitimate connections that don't send first would be delayed
+    /// or dropped. pass 0 to disable it.
+    ///
+    /// - on linux, it sets `TCP_DEFER_ACCEPT` with `secs` as the max time
+    ///   to wait for the first data
+    /// - on freebsd/netbsd, it installs the `dataready` accept filter by
+    ///   `SO_ACCEPTFILTER`, `secs` is only used as an on/off switch and
+    ///   the `accf_data` kernel module must be loaded
+    /// - other platforms return an error
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_defer_accept(&self, secs: u32) -> io::Result<()> {
+        let secs = secs.min(libc::c_int::MAX as u32) as libc::c_int;
+        io_impl::set_sockopt(
+            self.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_DEFER_ACCEPT,
+            secs,
+        )
+    }
+
+    /// defer the accept notification until the client has sent data
+    ///
+    /// see the linux version for details
+    #[cfg(any(target_os = "freebsd", target_os = "netbsd"))]
+    pub fn set_defer_accept(&self, secs: u32) -> io::Result<()> {
+        if secs == 0 {
+            // remove the accept filter
+            let ret = unsafe {
+                libc::setsockopt(
+                    self.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_ACCEPTFILTER,
+                    std::ptr::null(),
+                    0,
+                )
+            };
+            if ret < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            return Ok(());
+        }
+
+        let mut arg: libc::accept_filter_arg = unsafe { std::mem::zeroed() };
+        for (dst, src) in arg.af_name.iter_mut().zip(b"dataready") {
+            *dst = *src as libc::c_char;
+        }
+        io_impl::set_sockopt(
+            self.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_ACCEPTFILTER,
+            arg,
+        )
+    }
+
+    /// defer the accept notification until the client has sent data
+    ///
+    /// not supported on this platform, always return an `Unsupported` error
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "netbsd"
+    )))]
+    pub fn set_defer_accept(&self, _secs: u32) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "defer accept is not supported on this platform",
+        ))
+    }
+
+    /// enable TCP Fast Open on the listener by `TCP_FASTOPEN`
+    ///
+    /// the accepted connections could carry data in the SYN, which is
+    /// readable on the first `read` without an extra round trip. the
+    /// clients could send it by `TcpConnector::fast_open`. pass 0 to
+    /// disable it.
+    ///
+    /// - on linux, `qlen` is the max number of pending fast open requests,
+    ///   the server bit (`2`) of the `net.ipv4.tcp_fastopen` sysctl must
+    ///   be set, or the connections silently use a normal handshake
+    /// - on macos, `qlen` is only used as an on/off switch
+    /// - other platforms return an error
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_fast_open(&self, qlen: u32) -> io::Result<()> {
+        let qlen = qlen.min(libc::c_int::MAX as u32) as libc::c_int;
+        io_impl::set_sockopt(
+            self.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_FASTOPEN,
+            qlen,
+        )
+    }
+
+    /// enable TCP Fast Open on the listener by `TCP_FASTOPEN`
+    ///
+    /// see the linux version for details
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    pub fn set_fast_open(&self, qlen: u32) -> io::Result<()> {
+        let on = (qlen != 0) as libc::c_int;
+        io_impl::set_sockopt(self.as_raw_fd(), libc::IPPROTO_TCP, libc::TCP_FASTOPEN, on)
+    }
+
+    /// enable TCP Fast Open on the listener by `TCP_FASTOPEN`
+    ///
+    /// not supported on this platform, always return an `Unsupported` error
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "macos",
+        target_os = "ios"
+    )))]
+    pub fn set_fast_open(&self, _qlen: u32) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "fast open is not supported on this platform",
+        ))
+    }
+
     // TODO: add all std functions
 }
 
@@ -440,6 +1437,31 @@ impl io_impl::AsIoData for TcpListener {
     }
 }
 
+// ===== AcceptPermit =====
+//
+//
+
+/// The permit of a connection accepted by [`TcpListener::accept_when`]
+///
+/// the permit is posted back to the gate when dropped
+///
+/// [`TcpListener::accept_when`]: struct.TcpListener.html#method.accept_when
+pub struct AcceptPermit {
+    gate: Arc<Semphore>,
+}
+
+impl Drop for AcceptPermit {
+    fn drop(&mut self) {
+        self.gate.post();
+    }
+}
+
+impl fmt::Debug for AcceptPermit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AcceptPermit").finish()
+    }
+}
+
 // ===== Incoming =====
 //
 //
@@ -455,6 +1477,17 @@ impl<'a> Iterator for Incoming<'a> {
     }
 }
 
+pub struct IncomingWithAddr<'a> {
+    listener: &'a TcpListener,
+}
+
+impl<'a> Iterator for IncomingWithAddr<'a> {
+    type Item = io::Result<(TcpStream, SocketAddr)>;
+    fn next(&mut self) -> Option<io::Result<(TcpStream, SocketAddr)>> {
+        Some(self.listener.accept())
+    }
+}
+
 // ===== UNIX ext =====
 //
 //
@@ -562,3 +1595,64 @@ impl FromRawSocket for TcpListener {
             .unwrap_or_else(|e| panic!("from_raw_socket for TcpListener, err = {:?}", e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_backoff() {
+        let err = |kind| Err::<(), _>(io::Error::from(kind));
+
+        // transient errors are retried by the default policy
+        let mut errs = vec![
+            err(io::ErrorKind::Interrupted),
+            err(io::ErrorKind::ConnectionAborted),
+        ];
+        assert!(retry_accept(&AcceptAction::for_error, None, || errs
+            .pop()
+            .unwrap_or(Ok(())))
+        .is_ok());
+        assert!(errs.is_empty());
+
+        // the others are propagated
+        let e = retry_accept(&AcceptAction::for_error, None, || {
+            err(io::ErrorKind::WouldBlock)
+        });
+        assert_eq!(e.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+        #[cfg(unix)]
+        {
+            let e = io::Error::from_raw_os_error(libc::EMFILE);
+            let dur = Duration::from_millis(100);
+            assert_eq!(AcceptAction::for_error(&e), AcceptAction::RetryAfter(dur));
+        }
+
+        // custom policy
+        let policy = |e: &io::Error| match e.kind() {
+            io::ErrorKind::Other => AcceptAction::RetryAfter(Duration::from_millis(10)),
+            _ => AcceptAction::Fail,
+        };
+        let mut n = 0;
+        let now = Instant::now();
+        let ret = retry_accept(&policy, None, || {
+            n += 1;
+            if n < 3 {
+                err(io::ErrorKind::Other)
+            } else {
+                err(io::ErrorKind::Interrupted)
+            }
+        });
+        assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        assert_eq!(n, 3);
+        assert!(now.elapsed() >= Duration::from_millis(20));
+
+        // the backoff is clamped to the deadline
+        let policy = |_: &io::Error| AcceptAction::RetryAfter(Duration::from_secs(10));
+        let now = Instant::now();
+        let deadline = now + Duration::from_millis(20);
+        let ret = retry_accept(&policy, Some(deadline), || err(io::ErrorKind::Other));
+        assert_eq!(ret.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(now.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,12 +1,65 @@
 use std::io;
-use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::net::{self, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 use crate::io as io_impl;
 use crate::io::net as net_impl;
+#[cfg(unix)]
+use crate::net::DatagramRing;
 use crate::sync::atomic_dur::AtomicDuration;
 use crate::yield_now::yield_with;
 
+/// Datagram info returned by [`UdpSocket::recv_msg`]
+///
+/// [`UdpSocket::recv_msg`]: struct.UdpSocket.html#method.recv_msg
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvMsg {
+    /// number of bytes received
+    pub len: usize,
+    /// the peer address of the datagram
+    pub addr: SocketAddr,
+    /// the received TOS / traffic class byte
+    /// only available after `set_recv_tos(true)`
+    pub tos: Option<u8>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl RecvMsg {
+    /// the ECN codepoint of the datagram, the low 2 bits of the TOS byte
+    pub fn ecn(&self) -> Option<u8> {
+        self.tos.map(|tos| tos & 0x03)
+    }
+}
+
+/// Datagram info used by [`UdpSocket::send_msg`]
+///
+/// [`UdpSocket::send_msg`]: struct.UdpSocket.html#method.send_msg
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendMsg {
+    /// the target address of the datagram
+    pub addr: SocketAddr,
+    /// the outgoing TOS / traffic class byte, `None` to use the socket default
+    pub tos: Option<u8>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl SendMsg {
+    /// create a send message to the address with the socket default TOS
+    pub fn new(addr: SocketAddr) -> Self {
+        SendMsg { addr, tos: None }
+    }
+
+    /// set the ECN codepoint of the outgoing datagram, DSCP bits are cleared
+    pub fn ecn(mut self, ecn: u8) -> Self {
+        self.tos = Some(ecn & 0x03);
+        self
+    }
+}
+
 #[derive(Debug)]
 pub struct UdpSocket {
     io: io_impl::IoData,
@@ -14,6 +67,9 @@ pub struct UdpSocket {
     ctx: io_impl::IoContext,
     read_timeout: AtomicDuration,
     write_timeout: AtomicDuration,
+    // if `connect` is called, `send` and `recv` require a peer
+    // shared with the clones since they are the same socket
+    connected: Arc<AtomicBool>,
 }
 
 impl UdpSocket {
@@ -23,12 +79,15 @@ impl UdpSocket {
         // to avoid unnecessary context switch
         s.set_nonblocking(true)?;
 
+        // the socket may be already connected when it's from a raw fd
+        let connected = Arc::new(AtomicBool::new(s.peer_addr().is_ok()));
         io_impl::add_socket(&s).map(|io| UdpSocket {
             io,
             sys: s,
             ctx: io_impl::IoContext::new(),
             read_timeout: AtomicDuration::new(None),
             write_timeout: AtomicDuration::new(None),
+            connected,
         })
     }
 
@@ -40,10 +99,40 @@ impl UdpSocket {
         net::UdpSocket::bind(addr).and_then(UdpSocket::new)
     }
 
+    /// bind to the IPv6 address with `IPV6_V6ONLY` set before bind
+    ///
+    /// pass false to get a dual-stack socket that also receives the IPv4
+    /// traffic as IPv4-mapped addresses. without this the default depends
+    /// on the platform: linux follows the `net.ipv6.bindv6only` sysctl
+    /// (usually false), while windows and the BSDs default to true
+    pub fn bind_v6only(addr: SocketAddrV6, only_v6: bool) -> io::Result<UdpSocket> {
+        use socket2::{Domain, Socket, Type};
+
+        let socket = Socket::new(Domain::IPV6, Type::DGRAM, None)?;
+        socket.set_only_v6(only_v6)?;
+        socket.bind(&SocketAddr::V6(addr).into())?;
+        UdpSocket::new(socket.into())
+    }
+
     pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
         // for udp connect it's a nonblocking operation
         // so we just use the system call
-        self.sys.connect(addr)
+        self.sys.connect(addr)?;
+        self.connected.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // `send` and `recv` only work for the connected socket
+    #[inline]
+    fn check_connected(&self) -> io::Result<()> {
+        if self.connected.load(Ordering::Relaxed) {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "the udp socket is not connected",
+            ))
+        }
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
@@ -52,7 +141,8 @@ impl UdpSocket {
 
     #[cfg(not(windows))]
     pub fn try_clone(&self) -> io::Result<UdpSocket> {
-        let s = self.sys.try_clone().and_then(UdpSocket::new)?;
+        let mut s = self.sys.try_clone().and_then(UdpSocket::new)?;
+        s.connected = self.connected.clone();
         s.set_read_timeout(self.read_timeout.get()).unwrap();
         s.set_write_timeout(self.write_timeout.get()).unwrap();
         Ok(s)
@@ -70,6 +160,7 @@ impl UdpSocket {
             ctx: io_impl::IoContext::new(),
             read_timeout: AtomicDuration::new(self.read_timeout.get()),
             write_timeout: AtomicDuration::new(self.write_timeout.get()),
+            connected: self.connected.clone(),
         })
     }
 
@@ -139,7 +230,12 @@ impl UdpSocket {
         reader.done()
     }
 
+    /// send the data to the connected peer
+    ///
+    /// it's cheaper than `send_to` since there is no address for each
+    /// datagram, return `NotConnected` if `connect` is never called
     pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        self.check_connected()?;
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
@@ -172,7 +268,12 @@ impl UdpSocket {
         writer.done()
     }
 
+    /// receive a datagram from the connected peer
+    ///
+    /// it's cheaper than `recv_from` since the source address is not
+    /// reported, return `NotConnected` if `connect` is never called
     pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.check_connected()?;
         if self
             .ctx
             .check_nonblocking(|b| self.sys.set_nonblocking(b))?
@@ -205,6 +306,182 @@ impl UdpSocket {
         reader.done()
     }
 
+    /// receive as many datagrams as available into the ring
+    ///
+    /// it blocks until at least one datagram is received, then drains the
+    /// available ones without blocking until the ring is full. the previous
+    /// content of the ring is overwritten. return the number of datagrams.
+    ///
+    /// it uses `recvmmsg` on linux and a `recv` loop on other platforms,
+    /// there is no allocation for each call
+    #[cfg(unix)]
+    pub fn recv_into_ring(&self, ring: &mut DatagramRing) -> io::Result<usize> {
+        use std::os::unix::io::AsRawFd;
+
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return net_impl::recv_ring(self.as_raw_fd(), ring);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match net_impl::recv_ring(self.as_raw_fd(), ring) {
+            Ok(n) => return Ok(n),
+            Err(e) => {
+                // raw_os_error is faster than kind
+                let raw_err = e.raw_os_error();
+                if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                    // do nothing here
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut reader = net_impl::UdpRecvRing::new(self, ring);
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// receive a datagram together with its TOS / traffic class byte
+    ///
+    /// the TOS is only reported after enabling it by `set_recv_tos(true)`
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn recv_msg(&self, buf: &mut [u8]) -> io::Result<RecvMsg> {
+        use std::os::unix::io::AsRawFd;
+
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return net_impl::recv_msg(self.as_raw_fd(), buf);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking read
+        match net_impl::recv_msg(self.as_raw_fd(), buf) {
+            Ok(msg) => return Ok(msg),
+            Err(e) => {
+                // raw_os_error is faster than kind
+                let raw_err = e.raw_os_error();
+                if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                    // do nothing here
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut reader = net_impl::UdpRecvMsg::new(self, buf);
+        yield_with(&reader);
+        reader.done()
+    }
+
+    /// send a datagram with the TOS / traffic class byte set in `msg`
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn send_msg(&self, buf: &[u8], msg: &SendMsg) -> io::Result<usize> {
+        use std::os::unix::io::AsRawFd;
+
+        if self
+            .ctx
+            .check_nonblocking(|b| self.sys.set_nonblocking(b))?
+            || !self.ctx.check_context(|b| self.sys.set_nonblocking(b))?
+        {
+            return net_impl::send_msg(self.as_raw_fd(), buf, msg);
+        }
+
+        self.io.reset();
+        // this is an earlier return try for nonblocking write
+        match net_impl::send_msg(self.as_raw_fd(), buf, msg) {
+            Ok(n) => return Ok(n),
+            Err(e) => {
+                // raw_os_error is faster than kind
+                let raw_err = e.raw_os_error();
+                if raw_err == Some(libc::EAGAIN) || raw_err == Some(libc::EWOULDBLOCK) {
+                    // do nothing here
+                } else {
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut writer = net_impl::UdpSendMsg::new(self, buf, msg);
+        yield_with(&writer);
+        writer.done()
+    }
+
+    /// enable reporting the received TOS / traffic class byte in `recv_msg`
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_recv_tos(&self, on: bool) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let (level, name) = if self.local_addr()?.is_ipv4() {
+            (libc::IPPROTO_IP, libc::IP_RECVTOS)
+        } else {
+            (libc::IPPROTO_IPV6, libc::IPV6_RECVTCLASS)
+        };
+        io_impl::set_sockopt(self.as_raw_fd(), level, name, on as libc::c_int)
+    }
+
+    /// cap the sending rate of the socket in bytes per second
+    ///
+    /// this sets `SO_MAX_PACING_RATE`, the kernel would space out the packets
+    /// instead of sending them in bursts. it only takes effect when the `fq`
+    /// queueing discipline is active on the outgoing interface, and it's a
+    /// best-effort rate cap rather than a guarantee.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn set_pacing_rate(&self, bytes_per_sec: u64) -> io::Result<()> {
+        use std::convert::TryFrom;
+        use std::os::unix::io::AsRawFd;
+
+        let fd = self.as_raw_fd();
+        let name = libc::SO_MAX_PACING_RATE;
+        // old kernels only accept a u32 value
+        match u32::try_from(bytes_per_sec) {
+            Ok(rate) => io_impl::set_sockopt(fd, libc::SOL_SOCKET, name, rate),
+            Err(_) => io_impl::set_sockopt(fd, libc::SOL_SOCKET, name, bytes_per_sec),
+        }
+    }
+
+    /// return the size of the next pending datagram, 0 if there is none
+    ///
+    /// it never yields and doesn't consume the datagram. on linux and
+    /// android it's a `FIONREAD` ioctl. on the other platforms `FIONREAD`
+    /// reports the whole receive buffer, so the datagram is peeked instead,
+    /// which can't tell an empty datagram from none pending
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub fn next_datagram_len(&self) -> io::Result<usize> {
+        use std::os::unix::io::AsRawFd;
+        io_impl::bytes_readable(self.as_raw_fd())
+    }
+
+    /// return the size of the next pending datagram, 0 if there is none
+    ///
+    /// see the linux version for details
+    #[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+    pub fn next_datagram_len(&self) -> io::Result<usize> {
+        use std::cell::RefCell;
+        use std::os::unix::io::AsRawFd;
+
+        thread_local! {
+            // the coroutine stack is too small for the max datagram, the peek
+            // never yields so a buffer per thread is enough
+            static PEEK_BUF: RefCell<Vec<u8>> = RefCell::new(vec![0u8; 65536]);
+        }
+
+        let flags = libc::MSG_PEEK | libc::MSG_DONTWAIT;
+        let ret =
+            PEEK_BUF.with(|buf| net_impl::recv(self.as_raw_fd(), &mut buf.borrow_mut(), flags));
+        match ret {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            ret => ret,
+        }
+    }
+
     pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
         self.ctx.set_nonblocking(nonblocking);
         Ok(())
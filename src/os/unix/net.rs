@@ -1141,6 +1141,42 @@ mod test {
         thread.join().unwrap();
     }
 
+    #[test]
+    fn echo_coroutines() {
+        let dir = tmpdir();
+        let socket_path = dir.path().join("sock");
+
+        let listener = or_panic!(UnixListener::bind(&socket_path));
+        let server = go!(move || {
+            let mut stream = or_panic!(listener.incoming().next().unwrap());
+            // echo until the client half closes
+            let mut buf = [0; 1024];
+            loop {
+                match or_panic!(stream.read(&mut buf)) {
+                    0 => break,
+                    n => or_panic!(stream.write_all(&buf[..n])),
+                }
+            }
+        });
+
+        let client = go!(move || {
+            let mut stream = or_panic!(UnixStream::connect(&socket_path));
+            let msg = vec![0x5a; 64 * 1024];
+            let mut writer = or_panic!(stream.try_clone());
+            let w = go!(move || {
+                or_panic!(writer.write_all(&msg));
+                or_panic!(writer.shutdown(Shutdown::Write));
+                msg
+            });
+            let mut buf = vec![];
+            or_panic!(stream.read_to_end(&mut buf));
+            assert_eq!(w.join().unwrap(), buf);
+        });
+
+        client.join().unwrap();
+        server.join().unwrap();
+    }
+
     #[test]
     fn pair() {
         let msg1 = b"hello";
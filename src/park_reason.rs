@@ -0,0 +1,136 @@
+//! the reason that a coroutine is parked, for deadlock diagnosis
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "park_detail")]
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// what a parked coroutine is waiting for
+///
+/// the fd and the deadline are only recorded with the `park_detail`
+/// feature, they are `None` otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParkReason {
+    /// receiving from a channel
+    ChannelRecv,
+    /// locking a mutex
+    MutexLock,
+    /// waiting for the fd to be readable
+    IoRead(Option<u64>),
+    /// waiting for the fd to be writable
+    IoWrite(Option<u64>),
+    /// sleeping until the deadline
+    Sleep(Option<Instant>),
+    /// joining other coroutines
+    Join,
+}
+
+const TAG_BITS: u32 = 8;
+const TAG_MASK: u64 = (1 << TAG_BITS) - 1;
+
+// the deadlines are encoded as micros since the epoch
+#[cfg(feature = "park_detail")]
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+// capture the detail only with the `park_detail` feature
+#[inline]
+pub(crate) fn detail<T, F: FnOnce() -> T>(_f: F) -> Option<T> {
+    #[cfg(feature = "park_detail")]
+    return Some(_f());
+    #[cfg(not(feature = "park_detail"))]
+    None
+}
+
+// the payload 0 is reserved for no detail
+fn encode_payload(v: Option<u64>) -> u64 {
+    v.map_or(0, |v| v.wrapping_add(1))
+}
+
+fn decode_payload(v: u64) -> Option<u64> {
+    v.checked_sub(1)
+}
+
+impl ParkReason {
+    // a tagged value that is written by a single atomic store
+    fn encode(self) -> u64 {
+        let (tag, payload) = match self {
+            ParkReason::ChannelRecv => (1, 0),
+            ParkReason::MutexLock => (2, 0),
+            ParkReason::IoRead(fd) => (3, encode_payload(fd)),
+            ParkReason::IoWrite(fd) => (4, encode_payload(fd)),
+            #[cfg(feature = "park_detail")]
+            ParkReason::Sleep(until) => (
+                5,
+                encode_payload(
+                    until.map(|t| t.saturating_duration_since(epoch()).as_micros() as u64),
+                ),
+            ),
+            #[cfg(not(feature = "park_detail"))]
+            ParkReason::Sleep(_) => (5, 0),
+            ParkReason::Join => (6, 0),
+        };
+        tag | (payload << TAG_BITS)
+    }
+
+    pub(crate) fn decode(v: u64) -> Option<Self> {
+        let payload = decode_payload(v >> TAG_BITS);
+        let reason = match v & TAG_MASK {
+            1 => ParkReason::ChannelRecv,
+            2 => ParkReason::MutexLock,
+            3 => ParkReason::IoRead(payload),
+            4 => ParkReason::IoWrite(payload),
+            #[cfg(feature = "park_detail")]
+            5 => {
+                ParkReason::Sleep(payload.map(|us| epoch() + std::time::Duration::from_micros(us)))
+            }
+            #[cfg(not(feature = "park_detail"))]
+            5 => ParkReason::Sleep(None),
+            6 => ParkReason::Join,
+            _ => return None,
+        };
+        Some(reason)
+    }
+}
+
+// clear the park reason when the blocking call returns
+pub(crate) struct ParkReasonGuard(Option<&'static AtomicU64>);
+
+impl Drop for ParkReasonGuard {
+    fn drop(&mut self) {
+        if let Some(slot) = self.0 {
+            slot.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+// record the park reason of the current coroutine until the guard drops
+#[inline]
+pub(crate) fn record(reason: ParkReason) -> ParkReasonGuard {
+    let slot = crate::coroutine_impl::current_park_reason();
+    if let Some(slot) = slot {
+        slot.store(reason.encode(), Ordering::Relaxed);
+    }
+    ParkReasonGuard(slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let reasons = [
+            ParkReason::ChannelRecv,
+            ParkReason::MutexLock,
+            ParkReason::IoRead(detail(|| 7)),
+            ParkReason::IoWrite(None),
+            ParkReason::Join,
+        ];
+        for r in reasons.iter() {
+            assert_eq!(ParkReason::decode(r.encode()), Some(*r));
+        }
+        assert_eq!(ParkReason::decode(0), None);
+    }
+}
@@ -1,18 +1,33 @@
+use std::collections::HashMap;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
 use crate::config::config;
 use crate::coroutine_impl::CoroutineImpl;
 use crossbeam::queue::ArrayQueue as Queue;
 use generator::Gn;
 
+// the pool of the prewarmed stacks of one size
+struct SizedPool {
+    cap: usize,
+    stacks: Vec<CoroutineImpl>,
+}
+
 /// the raw coroutine pool, with stack and register prepared
 /// you need to tack care of the local storage
 pub struct CoroutinePool {
     // the pool must support mpmc operation!
     pool: Queue<CoroutineImpl>,
+    // the prewarmed stacks by size, only exist after prewarmed. the default
+    // size ones are the overflow of the pool, used when it's empty or full
+    sized: Mutex<HashMap<usize, SizedPool>>,
+    has_sized: AtomicBool,
 }
 
 impl CoroutinePool {
-    fn create_dummy_coroutine() -> CoroutineImpl {
-        Gn::new_opt(config().get_stack_size(), move || {
+    fn create_dummy_coroutine(size: usize) -> CoroutineImpl {
+        Gn::new_opt(size, move || {
             unreachable!("dummy coroutine should never be called");
         })
     }
@@ -21,26 +36,128 @@ impl CoroutinePool {
         let capacity = config().get_pool_capacity();
         let pool = Queue::new(capacity);
         for _ in 0..capacity {
-            let co = Self::create_dummy_coroutine();
+            let co = Self::create_dummy_coroutine(config().get_stack_size());
             pool.push(co).unwrap();
         }
 
-        CoroutinePool { pool }
+        CoroutinePool {
+            pool,
+            sized: Mutex::new(HashMap::new()),
+            has_sized: AtomicBool::new(false),
+        }
     }
 
     /// get a raw coroutine from the pool
     #[inline]
     pub fn get(&self) -> CoroutineImpl {
+        let size = config().get_stack_size();
         match self.pool.pop() {
             Some(co) => co,
-            None => Self::create_dummy_coroutine(),
+            None => self
+                .get_sized(size)
+                .unwrap_or_else(|| Self::create_dummy_coroutine(size)),
         }
     }
 
     /// put a raw coroutine into the pool
     #[inline]
     pub fn put(&self, co: CoroutineImpl) {
-        // discard the co if push failed
-        self.pool.push(co).ok();
+        // discard the co if push failed and it's not prewarmed
+        if let Err(co) = self.pool.push(co) {
+            self.put_sized(co, config().get_stack_size());
+        }
+    }
+
+    /// get a prewarmed raw coroutine of the stack size
+    /// return None if there is no prewarmed one
+    #[inline]
+    pub fn get_sized(&self, size: usize) -> Option<CoroutineImpl> {
+        if !self.has_sized.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut sized = self.sized.lock().unwrap();
+        sized.get_mut(&size).and_then(|p| p.stacks.pop())
+    }
+
+    /// put a raw coroutine of the stack size into the prewarmed pool
+    /// it's discarded if the size is never prewarmed
+    #[inline]
+    pub fn put_sized(&self, co: CoroutineImpl, size: usize) {
+        if !self.has_sized.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut sized = self.sized.lock().unwrap();
+        if let Some(p) = sized.get_mut(&size) {
+            if p.stacks.len() < p.cap {
+                p.stacks.push(co);
+            }
+        }
+    }
+
+    /// allocate `count` more coroutines of the stack `size` in words
+    /// return the number that is actually allocated
+    pub fn prewarm(&self, count: usize, size: usize) -> usize {
+        let create = || panic::catch_unwind(|| Self::create_dummy_coroutine(size)).ok();
+
+        let mut stacks = Vec::with_capacity(count);
+        while stacks.len() < count {
+            match create() {
+                Some(co) => stacks.push(co),
+                None => break,
+            }
+        }
+        let n = stacks.len();
+        let mut sized = self.sized.lock().unwrap();
+        let p = sized.entry(size).or_insert_with(|| SizedPool {
+            cap: 0,
+            stacks: Vec::new(),
+        });
+        p.cap += n;
+        p.stacks.append(&mut stacks);
+        self.has_sized.store(true, Ordering::Relaxed);
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prewarm() {
+        let pool = CoroutinePool::new();
+        let cap = config().get_pool_capacity();
+        let default = config().get_stack_size();
+        let prewarmed = |size| {
+            let sized = pool.sized.lock().unwrap();
+            sized.get(&size).map_or(0, |p| p.stacks.len())
+        };
+
+        // the default size ones are used after the pool is drained
+        assert_eq!(pool.prewarm(10, default), 10);
+        let cos: Vec<_> = (0..cap + 1).map(|_| pool.get()).collect();
+        assert!(pool.pool.is_empty());
+        assert_eq!(prewarmed(default), 9);
+        // and kept after the pool is full
+        cos.into_iter().for_each(|co| pool.put(co));
+        pool.put(CoroutinePool::create_dummy_coroutine(default));
+        assert_eq!(pool.pool.len(), cap);
+        assert_eq!(prewarmed(default), 10);
+
+        let size = default * 2;
+        assert!(pool.get_sized(size).is_none());
+        assert_eq!(pool.prewarm(2, size), 2);
+        let a = pool.get_sized(size).unwrap();
+        let b = pool.get_sized(size).unwrap();
+        assert!(pool.get_sized(size).is_none());
+
+        // the recycled stacks are kept up to the prewarmed number
+        pool.put_sized(a, size);
+        pool.put_sized(b, size);
+        pool.put_sized(CoroutinePool::create_dummy_coroutine(size), size);
+        assert_eq!(prewarmed(size), 2);
+        // the sizes that are never prewarmed are discarded
+        pool.put_sized(CoroutinePool::create_dummy_coroutine(size * 2), size * 2);
+        assert!(pool.get_sized(size * 2).is_none());
     }
 }
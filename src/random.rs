@@ -0,0 +1,177 @@
+//! fast random numbers for coroutines
+//!
+//! the generator state belongs to the running thread, so for coroutines
+//! it's per worker. each call only touches the state of the current worker
+//! without any yield, so it's fine that the coroutine migrates between calls.
+//! the state is seeded on the first use in the thread.
+//!
+//! this is a xorshift64* generator, it's NOT cryptographically secure
+use std::cell::Cell;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+thread_local! { static STATE: Cell<u64> = const { Cell::new(0) }; }
+
+// make sure each thread gets a different seed
+static SEED_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+// splitmix64, used to spread the seed bits
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn seed() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    let cnt = SEED_COUNTER.fetch_add(1, Ordering::Relaxed);
+    // the state must never be zero
+    match mix(now ^ mix(cnt)) {
+        0 => 1,
+        s => s,
+    }
+}
+
+fn next_u64() -> u64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        if x == 0 {
+            x = seed();
+        }
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    })
+}
+
+/// types that can be generated by [`random`]
+pub trait Random: Sized {
+    #[doc(hidden)]
+    fn from_u64(v: u64) -> Self;
+}
+
+/// integer types that can be generated by [`random_range`]
+pub trait RandomRange: Sized {
+    #[doc(hidden)]
+    fn sample(range: Range<Self>) -> Self;
+}
+
+macro_rules! impl_int {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl Random for $t {
+                #[inline]
+                fn from_u64(v: u64) -> Self {
+                    v as $t
+                }
+            }
+
+            impl RandomRange for $t {
+                fn sample(range: Range<Self>) -> Self {
+                    assert!(range.start < range.end, "random_range on empty range");
+                    let span = range.end.wrapping_sub(range.start) as $u as u64;
+                    // multiply shift instead of modulo, the bias is negligible
+                    let off = ((next_u64() as u128 * span as u128) >> 64) as $u;
+                    range.start.wrapping_add(off as $t)
+                }
+            }
+        )*
+    };
+}
+
+impl_int!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, usize => usize,
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize
+);
+
+impl Random for bool {
+    #[inline]
+    fn from_u64(v: u64) -> Self {
+        v >> 63 == 1
+    }
+}
+
+impl Random for f64 {
+    /// uniform in `[0, 1)`
+    #[inline]
+    fn from_u64(v: u64) -> Self {
+        (v >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+impl Random for f32 {
+    /// uniform in `[0, 1)`
+    #[inline]
+    fn from_u64(v: u64) -> Self {
+        (v >> 40) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+}
+
+/// return a random value from the per worker generator
+///
+/// integers cover the whole range of the type, floats are in `[0, 1)`.
+/// not cryptographically secure
+#[inline]
+pub fn random<T: Random>() -> T {
+    T::from_u64(next_u64())
+}
+
+/// return a random integer in `range`
+///
+/// not cryptographically secure
+///
+/// # Panics
+///
+/// panic if the range is empty
+pub fn random_range<T: RandomRange>(range: Range<T>) -> T {
+    T::sample(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range() {
+        for _ in 0..1000 {
+            let v = random_range(10u32..20);
+            assert!((10..20).contains(&v));
+            let v = random_range(-5i8..5);
+            assert!((-5..5).contains(&v));
+            let f: f64 = random();
+            assert!((0.0..1.0).contains(&f));
+        }
+        assert_eq!(random_range(7u64..8), 7);
+        // the full range of i64 doesn't overflow
+        random_range(i64::MIN..i64::MAX);
+    }
+
+    #[test]
+    fn spread() {
+        let mut hits = [0; 8];
+        for _ in 0..8000 {
+            hits[random_range(0..8usize)] += 1;
+        }
+        assert!(hits.iter().all(|&n| n > 800), "{:?}", hits);
+    }
+
+    #[test]
+    #[should_panic]
+    fn empty_range() {
+        random_range(3u8..3);
+    }
+
+    #[test]
+    fn coroutine() {
+        let h = go!(|| (0..100).map(|_| random::<u64>()).collect::<Vec<_>>());
+        let v = h.join().unwrap();
+        assert!(v.windows(2).any(|w| w[0] != w[1]));
+    }
+}
@@ -1,18 +1,22 @@
+use std::cell::Cell;
 use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Once};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::config::config;
-use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
+use crate::config::{config, get_tick, PollerPanicPolicy};
+use crate::coroutine_impl::{co_pinned_worker, run_coroutine, CoroutineImpl, NOT_PINNED};
 use crate::io::{EventLoop, Selector};
 use crate::pool::CoroutinePool;
 use crate::sync::AtomicOption;
 use crate::timeout_list;
 use crate::yield_now::set_co_para;
 use crossbeam::deque;
-use crossbeam::utils::Backoff;
+use crossbeam::queue::SegQueue;
+use crossbeam::utils::{Backoff, CachePadded};
 
 #[cfg(nightly)]
 use std::intrinsics::likely;
@@ -31,6 +35,38 @@ pub static WORKER_ID: AtomicUsize = AtomicUsize::new(!1);
 #[cfg(not(nightly))]
 thread_local! { pub static WORKER_ID: AtomicUsize = AtomicUsize::new(!1); }
 
+// the worker id of current thread, return !1 if it's not a worker thread
+#[inline]
+pub fn get_worker_id() -> usize {
+    #[cfg(nightly)]
+    let id = WORKER_ID.load(Ordering::Relaxed);
+    #[cfg(not(nightly))]
+    let id = WORKER_ID.with(|id| id.load(Ordering::Relaxed));
+    id
+}
+
+thread_local! { static NEXT_TICK: Cell<Option<Instant>> = const { Cell::new(None) }; }
+
+// run the tick callback if it's time for the worker
+// return the duration until the next tick
+#[inline]
+pub(crate) fn run_tick(id: usize) -> Option<Duration> {
+    let tick = get_tick()?;
+    let now = Instant::now();
+    NEXT_TICK.with(|next| {
+        let next_tick = match next.get() {
+            Some(t) if t > now => t,
+            Some(_) => {
+                (tick.f)(id);
+                now + tick.interval
+            }
+            None => now + tick.interval,
+        };
+        next.set(Some(next_tick));
+        Some(next_tick - now)
+    })
+}
+
 // here we use Arc<AtomicOption<>> for that in the select implementation
 // other event may try to consume the coroutine while timer thread consume it
 type TimerData = Arc<AtomicOption<CoroutineImpl>>;
@@ -39,6 +75,7 @@ type TimerThread = timeout_list::TimerThread<TimerData>;
 // filter out the cancel panic, don't print anything for it
 fn filter_cancel_panic() {
     use generator::Error;
+    use std::backtrace::{Backtrace, BacktraceStatus};
     use std::panic;
     let old = panic::take_hook();
     ::std::panic::set_hook(Box::new(move |info| {
@@ -46,6 +83,18 @@ fn filter_cancel_panic() {
             // this is not an error at all, ignore it
             return;
         }
+        // the chained hook only knows the worker thread name, so a named
+        // coroutine is reported by its own name instead
+        if let Some(local) = crate::local::get_co_local_data() {
+            if let Some(name) = unsafe { local.as_ref() }.get_co().name() {
+                eprintln!("coroutine '{}' {}", name, info);
+                let bt = Backtrace::capture();
+                if bt.status() == BacktraceStatus::Captured {
+                    eprintln!("stack backtrace:\n{}", bt);
+                }
+                return;
+            }
+        }
         old(info);
     }));
 }
@@ -113,9 +162,24 @@ fn init_scheduler() {
     for id in 0..workers {
         thread::spawn(move || {
             let s = unsafe { &*SCHED };
-            s.event_loop.run(id).unwrap_or_else(|e| {
-                panic!("event_loop failed running, err={}", e);
-            });
+            // supervise the event loop, a dead poller would hang all the io
+            loop {
+                let ret = panic::catch_unwind(AssertUnwindSafe(|| s.event_loop.run(id)));
+                match ret {
+                    Ok(ret) => ret.unwrap_or_else(|e| {
+                        panic!("event_loop failed running, err={}", e);
+                    }),
+                    Err(_) => match config().get_poller_panic() {
+                        PollerPanicPolicy::Restart => {
+                            error!("io poller thread {} panicked, restart it", id);
+                        }
+                        PollerPanicPolicy::Abort => {
+                            eprintln!("io poller thread {} panicked, abort the process", id);
+                            process::abort();
+                        }
+                    },
+                }
+            }
         });
     }
 }
@@ -166,15 +230,51 @@ fn steal_local<T>(stealer: &deque::Stealer<T>, local: &deque::Worker<T>) -> Opti
     }
 }
 
+/// a snapshot of the scheduler counters
+///
+/// the counters are read with relaxed atomics, so the snapshot is cheap
+/// but not an exact point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchedulerStats {
+    /// the number of the worker threads
+    pub workers: usize,
+    /// the number of the coroutines that are spawned but not completed
+    pub live: u64,
+    /// total coroutines spawned
+    pub spawned: u64,
+    /// total coroutines completed, including the panicked and canceled ones
+    pub completed: u64,
+    /// the run queue length of each worker, the pinned ones included
+    pub run_queues: Vec<usize>,
+    /// the length of the global run queue
+    pub global_queue: usize,
+}
+
+/// return a snapshot of the scheduler counters
+pub fn scheduler_stats() -> SchedulerStats {
+    get_scheduler().stats()
+}
+
+// the coroutine counters of one worker, padded to avoid false sharing
+#[derive(Default)]
+struct CoCounters {
+    spawned: AtomicU64,
+    completed: AtomicU64,
+}
+
 #[repr(align(128))]
 pub struct Scheduler {
     pub pool: CoroutinePool,
     event_loop: EventLoop,
     global_queue: deque::Injector<CoroutineImpl>,
     local_queues: Vec<deque::Worker<CoroutineImpl>>,
+    // coroutines that pinned to the worker, would never be stolen
+    pinned_queues: Vec<SegQueue<CoroutineImpl>>,
     pub(crate) workers: ParkStatus,
     timer_thread: TimerThread,
     stealers: Vec<Vec<(usize, deque::Stealer<CoroutineImpl>)>>,
+    // one for each worker, the last one is shared by the non-worker threads
+    counters: Vec<CachePadded<CoCounters>>,
 }
 
 impl Scheduler {
@@ -197,18 +297,28 @@ impl Scheduler {
             event_loop: EventLoop::new(workers).expect("can't create event_loop"),
             global_queue: deque::Injector::new(),
             local_queues,
+            pinned_queues: (0..workers).map(|_| SegQueue::new()).collect(),
             timer_thread: TimerThread::new(),
             workers: ParkStatus::new(workers),
             stealers,
+            counters: (0..=workers).map(|_| Default::default()).collect(),
         })
     }
 
     pub fn run_queued_tasks(&self, id: usize) {
         let local = unsafe { self.local_queues.get_unchecked(id) };
+        let pinned = unsafe { self.pinned_queues.get_unchecked(id) };
         let stealers = unsafe { self.stealers.get_unchecked(id) };
+        let mut cnt = 0usize;
         loop {
-            // Pop a task from the local queue
-            let co = local.pop().or_else(|| {
+            // check the tick between running coroutines
+            cnt = cnt.wrapping_add(1);
+            if cnt & 63 == 0 {
+                run_tick(id);
+            }
+
+            // Pop a task from the pinned queue and then the local queue
+            let co = pinned.pop().or_else(|| local.pop()).or_else(|| {
                 // Try stealing a of task from other local queues.
                 let parked_threads = self.workers.parked.load(Ordering::Relaxed);
                 stealers
@@ -238,12 +348,11 @@ impl Scheduler {
     /// put the coroutine to correct queue so that next time it can be scheduled
     #[inline]
     pub fn schedule(&self, co: CoroutineImpl) {
-        #[cfg(nightly)]
-        let id = WORKER_ID.load(Ordering::Relaxed);
-        #[cfg(not(nightly))]
-        let id = WORKER_ID.with(|id| id.load(Ordering::Relaxed));
-
-        if id == !1 {
+        let id = get_worker_id();
+        let pinned = co_pinned_worker(&co);
+        if pinned != NOT_PINNED {
+            self.schedule_pinned(pinned, co);
+        } else if id == !1 {
             self.schedule_global(co);
         } else {
             unsafe { self.local_queues.get_unchecked(id) }.push(co);
@@ -253,11 +362,27 @@ impl Scheduler {
     /// put the coroutine to global queue so that next time it can be scheduled
     #[inline]
     pub fn schedule_global(&self, co: CoroutineImpl) {
+        let pinned = co_pinned_worker(&co);
+        if pinned != NOT_PINNED {
+            return self.schedule_pinned(pinned, co);
+        }
         self.global_queue.push(co);
         // signal one waiting thread if any
         self.workers.wake_one(self);
     }
 
+    /// put the coroutine to the pinned queue of the worker
+    /// it can only be run by that worker
+    #[inline]
+    pub fn schedule_pinned(&self, id: usize, co: CoroutineImpl) {
+        unsafe { self.pinned_queues.get_unchecked(id) }.push(co);
+        if get_worker_id() != id {
+            // make sure the worker would see the coroutine
+            self.workers.parked.fetch_and(!(1 << id), Ordering::Relaxed);
+            self.get_selector().wakeup(id);
+        }
+    }
+
     #[inline]
     pub fn add_timer(
         &self,
@@ -272,6 +397,59 @@ impl Scheduler {
         self.timer_thread.del_timer(handle);
     }
 
+    /// the number of the workers
+    #[cfg(feature = "test-util")]
+    pub fn worker_count(&self) -> usize {
+        self.workers.workers
+    }
+
+    // the counters of the current thread
+    #[inline]
+    fn local_counters(&self) -> &CoCounters {
+        let id = get_worker_id().min(self.workers.workers);
+        unsafe { self.counters.get_unchecked(id) }
+    }
+
+    #[inline]
+    pub(crate) fn co_spawned(&self) {
+        self.local_counters()
+            .spawned
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn co_completed(&self) {
+        self.local_counters()
+            .completed
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> SchedulerStats {
+        // sum the completed first so that it rarely exceeds the spawned
+        let sum = |f: fn(&CoCounters) -> &AtomicU64| -> u64 {
+            self.counters
+                .iter()
+                .map(|c| f(c).load(Ordering::Relaxed))
+                .sum()
+        };
+        let completed = sum(|c| &c.completed);
+        let spawned = sum(|c| &c.spawned);
+        let run_queues = self
+            .local_queues
+            .iter()
+            .zip(self.pinned_queues.iter())
+            .map(|(local, pinned)| local.len() + pinned.len())
+            .collect();
+        SchedulerStats {
+            workers: self.workers.workers,
+            live: spawned.saturating_sub(completed),
+            spawned,
+            completed,
+            run_queues,
+            global_queue: self.global_queue.len(),
+        }
+    }
+
     #[inline]
     pub fn get_selector(&self) -> &Selector {
         self.event_loop.get_selector()
@@ -8,7 +8,7 @@ use std::rc::Rc;
 use std::sync::Arc;
 use std::thread;
 
-use crate::coroutine_impl::{spawn, Coroutine};
+use crate::coroutine_impl::{spawn, Builder, Coroutine};
 use crate::join::JoinHandle;
 use crossbeam::atomic::AtomicCell;
 
@@ -22,6 +22,16 @@ where
     spawn(move || closure())
 }
 
+/// Like `spawn_unsafe`, but first run the coroutine in current context
+pub unsafe fn spawn_unsafe_local<'a, F>(f: F) -> JoinHandle<()>
+where
+    F: FnOnce() + Send + 'a,
+{
+    let closure: Box<dyn FnOnce() + 'a> = Box::new(f);
+    let closure: Box<dyn FnOnce() + Send> = mem::transmute(closure);
+    Builder::new().spawn_local(closure).unwrap()
+}
+
 pub struct Scope<'a> {
     dtors: RefCell<Option<DtorChain<'a>>>,
 }
@@ -61,6 +71,9 @@ pub struct ScopedJoinHandle<T> {
 /// Create a new `scope`, for deferred destructors.
 ///
 /// Scopes, in particular, support scoped coroutine spawning.
+/// all the scoped coroutines are joined before it returns, even if the
+/// body panics, so they could borrow the data of the enclosing frame.
+/// a panic of the children is propagated after that.
 ///
 pub fn scope<'a, F, R>(f: F) -> R
 where
@@ -1,9 +1,10 @@
 use crate::sync::AtomicOption;
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::coroutine_impl::{co_cancel_data, is_coroutine, CoroutineImpl, EventSource};
+use crate::park_reason::{self, ParkReason};
 use crate::scheduler::get_scheduler;
 use crate::yield_now::{get_co_para, yield_with};
 
@@ -34,8 +35,22 @@ pub fn sleep(dur: Duration) {
         return thread::sleep(dur);
     }
 
+    let _reason = park_reason::record(ParkReason::Sleep(park_reason::detail(|| {
+        Instant::now() + dur
+    })));
     let sleeper = Sleep { dur };
     yield_with(&sleeper);
     // consume the timeout error
     get_co_para();
 }
+
+/// block the current coroutine until the deadline
+///
+/// return immediately without yielding if the deadline is already passed
+pub fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline <= now {
+        return;
+    }
+    sleep(deadline - now);
+}
@@ -15,6 +15,10 @@ impl AtomicDuration {
         AtomicDuration(AtomicUsize::new(dur))
     }
 
+    pub const fn none() -> Self {
+        AtomicDuration(AtomicUsize::new(0))
+    }
+
     #[inline]
     pub fn get(&self) -> Option<Duration> {
         match self.0.load(Ordering::Relaxed) {
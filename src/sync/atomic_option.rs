@@ -92,16 +92,85 @@ impl<T: Wrapped> AtomicOption<T> {
         }
     }
 
+    /// store the value and return the old one
+    ///
+    /// the `order` applies to the underlying pointer swap, use `Release` to
+    /// publish the value and `Acquire` to see the old value's writes
     #[inline]
     pub fn swap(&self, t: T, order: Ordering) -> Option<T> {
         self.swap_inner(t.into_raw(), order)
     }
 
+    /// take the value out, only one of the racing callers gets it
+    ///
+    /// the `order` applies to the underlying pointer swap, use `Acquire` to
+    /// see the writes published with the value
     #[inline]
     pub fn take(&self, order: Ordering) -> Option<T> {
         self.swap_inner(ptr::null_mut(), order)
     }
 
+    /// take the value out with separate orderings for a hit and a miss
+    ///
+    /// an empty slot is only loaded with `failure`, so a miss never writes.
+    /// the value is taken by a `compare_exchange` with `success`, use
+    /// `Acquire` to see the writes published with the value. only one of
+    /// the racing callers gets it
+    #[inline]
+    pub fn take_ordering(&self, success: Ordering, failure: Ordering) -> Option<T> {
+        let mut cur = self.inner.load(failure);
+        while !cur.is_null() {
+            match self
+                .inner
+                .compare_exchange_weak(cur, ptr::null_mut(), success, failure)
+            {
+                Ok(_) => return Some(unsafe { T::from_raw(cur) }),
+                Err(x) => cur = x,
+            }
+        }
+        None
+    }
+
+    /// take the value out only if it's still the `expected` one
+    ///
+    /// the stored value is compared with `expected` by address, e.g. an
+    /// `Arc` that the caller keeps a clone of. `success` and `failure` are
+    /// the orderings of the underlying `compare_exchange`. return `None`
+    /// if the slot is empty or holds another value, which is left intact
+    #[inline]
+    pub fn compare_and_take(
+        &self,
+        expected: &T::Data,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Option<T> {
+        let expected = expected as *const T::Data as *mut T::Data;
+        match self
+            .inner
+            .compare_exchange(expected, ptr::null_mut(), success, failure)
+        {
+            Ok(p) => Some(unsafe { T::from_raw(p) }),
+            Err(_) => None,
+        }
+    }
+
+    /// store the value only if there is none, otherwise return it back
+    ///
+    /// only one of the racing callers succeeds. `success` and `failure` are
+    /// the orderings of the underlying `compare_exchange`, use `Release` on
+    /// success to publish the value for a later `take`
+    #[inline]
+    pub fn store_if_none(&self, t: T, success: Ordering, failure: Ordering) -> Result<(), T> {
+        let ptr = t.into_raw();
+        match self
+            .inner
+            .compare_exchange(ptr::null_mut(), ptr, success, failure)
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(unsafe { T::from_raw(ptr) }),
+        }
+    }
+
     #[inline]
     pub fn is_none(&self) -> bool {
         self.inner.load(Ordering::Acquire).is_null()
@@ -119,3 +188,97 @@ impl<T: Wrapped> Drop for AtomicOption<T> {
         self.take(Ordering::Acquire);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn take_race() {
+        for _ in 0..1000 {
+            let slot = Arc::new(AtomicOption::some(Box::new(42)));
+            let won = Arc::new(AtomicUsize::new(0));
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let slot = slot.clone();
+                    let won = won.clone();
+                    go!(move || {
+                        if let Some(v) = slot.take(Ordering::Acquire) {
+                            assert_eq!(*v, 42);
+                            won.fetch_add(1, Ordering::Relaxed);
+                        }
+                    })
+                })
+                .collect();
+            for h in handles {
+                h.join().unwrap();
+            }
+            assert_eq!(won.load(Ordering::Relaxed), 1);
+            assert!(slot.is_none());
+        }
+    }
+
+    #[test]
+    fn take_ordering_race() {
+        for _ in 0..1000 {
+            let slot = Arc::new(AtomicOption::some(Box::new(42)));
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let slot = slot.clone();
+                    go!(move || slot
+                        .take_ordering(Ordering::Acquire, Ordering::Relaxed)
+                        .is_some())
+                })
+                .collect();
+            let won = handles.into_iter().map(|h| h.join().unwrap());
+            assert_eq!(won.filter(|&ok| ok).count(), 1);
+            assert!(slot.is_none());
+        }
+    }
+
+    #[test]
+    fn compare_and_take_race() {
+        for _ in 0..1000 {
+            let v = Arc::new(42);
+            let slot = Arc::new(AtomicOption::some(v.clone()));
+            // another value is never taken
+            let other = Arc::new(42);
+            assert!(slot
+                .compare_and_take(&*other, Ordering::Acquire, Ordering::Relaxed)
+                .is_none());
+            let handles: Vec<_> = (0..2)
+                .map(|_| {
+                    let (slot, v) = (slot.clone(), v.clone());
+                    go!(move || slot
+                        .compare_and_take(&*v, Ordering::Acquire, Ordering::Relaxed)
+                        .is_some())
+                })
+                .collect();
+            let won = handles.into_iter().map(|h| h.join().unwrap());
+            assert_eq!(won.filter(|&ok| ok).count(), 1);
+            assert!(slot.is_none());
+            assert_eq!(Arc::strong_count(&v), 1);
+        }
+    }
+
+    #[test]
+    fn store_if_none_race() {
+        for _ in 0..1000 {
+            let slot = Arc::new(AtomicOption::<Box<usize>>::none());
+            let handles: Vec<_> = (0..2)
+                .map(|i| {
+                    let slot = slot.clone();
+                    go!(move || slot
+                        .store_if_none(Box::new(i), Ordering::Release, Ordering::Relaxed)
+                        .is_ok())
+                })
+                .collect();
+            let won: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+            assert_eq!(won.iter().filter(|&&ok| ok).count(), 1);
+            // the stored one is the winner's value
+            let v = slot.take(Ordering::Acquire).unwrap();
+            assert!(won[*v]);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt;
+
+use super::{Condvar, Mutex};
+
+// the state of the current generation
+struct BarrierState {
+    count: usize,
+    generation_id: usize,
+}
+
+/// Barrier primitive
+///
+/// compatible with `std::sync::Barrier` except for both thread and coroutine.
+/// it blocks the callers until `n` of them have reached the barrier, then
+/// releases them all at once. the barrier is reusable after the release.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use may::sync::Barrier;
+///
+/// let barrier = Arc::new(Barrier::new(10));
+/// let handles: Vec<_> = (0..10)
+///     .map(|_| {
+///         let b = barrier.clone();
+///         may::go!(move || b.wait().is_leader())
+///     })
+///     .collect();
+/// // exactly one of them is the leader
+/// let leaders = handles
+///     .into_iter()
+///     .map(|h| h.join().unwrap())
+///     .filter(|&leader| leader)
+///     .count();
+/// assert_eq!(leaders, 1);
+/// ```
+pub struct Barrier {
+    lock: Mutex<BarrierState>,
+    cvar: Condvar,
+    num_threads: usize,
+}
+
+/// the result returned by `Barrier::wait`
+pub struct BarrierWaitResult(bool);
+
+impl Barrier {
+    /// create a barrier that blocks until `n` callers are waiting on it
+    ///
+    /// a barrier of zero or one never blocks
+    pub fn new(n: usize) -> Barrier {
+        Barrier {
+            lock: Mutex::new(BarrierState {
+                count: 0,
+                generation_id: 0,
+            }),
+            cvar: Condvar::new(),
+            num_threads: n,
+        }
+    }
+
+    /// block until all the callers have reached the barrier
+    ///
+    /// exactly one caller of each generation gets a leader result
+    pub fn wait(&self) -> BarrierWaitResult {
+        let mut lock = self.lock.lock().unwrap();
+        let local_gen = lock.generation_id;
+        lock.count += 1;
+        if lock.count < self.num_threads {
+            // ignore the spurious wakeups of the same generation
+            let _guard = self
+                .cvar
+                .wait_while(lock, |state| local_gen == state.generation_id)
+                .unwrap();
+            BarrierWaitResult(false)
+        } else {
+            // start a new generation
+            lock.count = 0;
+            lock.generation_id = lock.generation_id.wrapping_add(1);
+            self.cvar.notify_all();
+            BarrierWaitResult(true)
+        }
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Barrier")
+            .field("num_threads", &self.num_threads)
+            .finish()
+    }
+}
+
+impl BarrierWaitResult {
+    /// return true if it's the leader of the generation
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl fmt::Debug for BarrierWaitResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BarrierWaitResult")
+            .field("is_leader", &self.is_leader())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn phases() {
+        const N: usize = 10;
+        const ROUNDS: usize = 5;
+
+        let barrier = Arc::new(Barrier::new(N));
+        let phase = Arc::new(AtomicUsize::new(0));
+        let worker = |barrier: Arc<Barrier>, phase: Arc<AtomicUsize>| {
+            move || {
+                let mut leaders = 0;
+                for round in 0..ROUNDS {
+                    // no one is in the next phase before all finished this one
+                    assert!(phase.fetch_add(1, Ordering::SeqCst) < (round + 1) * N);
+                    if barrier.wait().is_leader() {
+                        leaders += 1;
+                    }
+                    assert!(phase.load(Ordering::SeqCst) >= (round + 1) * N);
+                    // wait for all checked the phase
+                    barrier.wait();
+                }
+                leaders
+            }
+        };
+
+        // half coroutines and half threads
+        let cos: Vec<_> = (0..N / 2)
+            .map(|_| go!(worker(barrier.clone(), phase.clone())))
+            .collect();
+        let threads: Vec<_> = (0..N / 2)
+            .map(|_| thread::spawn(worker(barrier.clone(), phase.clone())))
+            .collect();
+
+        let leaders: usize = cos.into_iter().map(|h| h.join().unwrap()).sum::<usize>()
+            + threads
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .sum::<usize>();
+        // one leader for each generation
+        assert_eq!(leaders, ROUNDS);
+    }
+}
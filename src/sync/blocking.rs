@@ -1,9 +1,12 @@
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::TryRecvError;
 use std::sync::Arc;
 use std::sync::{Condvar, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use super::AtomicOption;
 use crate::coroutine_impl::is_coroutine;
+use crate::local::hold_select_claim;
 use crate::park::{Park, ParkError};
 
 #[derive(Debug)]
@@ -178,3 +181,104 @@ impl SyncBlocker {
         self.unparked.store(true, Ordering::Release);
     }
 }
+
+// the blockers that the timed primitives park on
+pub(crate) trait Waiter {
+    fn park(&self, timeout: Option<Duration>) -> Result<(), ParkError>;
+}
+
+impl Waiter for Blocker {
+    #[inline]
+    fn park(&self, timeout: Option<Duration>) -> Result<(), ParkError> {
+        Blocker::park(self, timeout)
+    }
+}
+
+impl Waiter for SyncBlocker {
+    #[inline]
+    fn park(&self, timeout: Option<Duration>) -> Result<(), ParkError> {
+        SyncBlocker::park(self, timeout)
+    }
+}
+
+// convert the timeout to a deadline, a timeout too large to represent
+// is treated as no deadline
+#[inline]
+pub(crate) fn deadline_after(dur: Duration) -> Option<Instant> {
+    Instant::now().checked_add(dur)
+}
+
+/// park on the blocker until it's unparked or the deadline is reached
+///
+/// this is the single timed parking path shared by all the primitives.
+/// `register` is called first to register the blocker as the waker and
+/// re-check the condition, it returns false if there is no need to park.
+/// a deadline that is already passed returns `Timeout` without calling
+/// `register`. the remaining time is always computed from the deadline,
+/// so a caller that loops on spurious wakeups with the same deadline
+/// never waits longer than it. the cancellation is reported as
+/// `Canceled`, the caller must clean up the registration before
+/// triggering the cancel panic
+pub(crate) fn park_until<B, F>(
+    blocker: &Arc<B>,
+    deadline: Option<Instant>,
+    register: F,
+) -> Result<(), ParkError>
+where
+    B: Waiter,
+    F: FnOnce(&Arc<B>) -> bool,
+{
+    let timeout = match deadline {
+        None => None,
+        Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+            Some(left) if left > Duration::from_secs(0) => Some(left),
+            _ => return Err(ParkError::Timeout),
+        },
+    };
+    if !register(blocker) {
+        return Ok(());
+    }
+    blocker.park(timeout)
+}
+
+/// park the single receiver until `try_recv` gets the data
+///
+/// the receiver is registered in `to_wake` before re-checking, so a send
+/// in between is never missed. `Empty` is returned for a timeout or a
+/// spurious wakeup, the caller should loop on it
+pub(crate) fn recv_until<T, F>(
+    to_wake: &AtomicOption<Arc<Blocker>>,
+    deadline: Option<Instant>,
+    try_recv: F,
+) -> Result<T, TryRecvError>
+where
+    F: Fn() -> Result<T, TryRecvError>,
+{
+    let cur = Blocker::current();
+    let mut data = Err(TryRecvError::Empty);
+    park_until(&cur, deadline, |cur| {
+        // register the waiter
+        to_wake.swap(cur.clone(), Ordering::Release);
+        // re-check the queue
+        data = try_recv();
+        if let Err(TryRecvError::Empty) = data {
+            return true;
+        }
+        // no need to park, contention with send
+        if let Some(w) = to_wake.take(Ordering::Acquire) {
+            w.unpark();
+        }
+        // consume the unpark token, the waker is taken by someone
+        // the data is taken, so a select arm must not lose the claim here
+        let _hold = hold_select_claim();
+        cur.park(None).ok();
+        false
+    })
+    .ok();
+
+    match data {
+        // after come back try recv again
+        Err(TryRecvError::Empty) => try_recv(),
+        data => data,
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::blocking::deadline_after;
+use super::{Condvar, Mutex};
+
+/// Bounded blocking queue
+///
+/// a shared fifo queue with a fixed capacity that can be used by both
+/// threads and coroutines. unlike the channels, there is no split of
+/// sender and receiver, the same handle can `put` and `take`.
+///
+/// `put` would block when the queue is full and `take` would block when
+/// the queue is empty. the blocked waiters are waked up in fifo order.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use may::coroutine;
+/// use may::sync::BlockingQueue;
+///
+/// let q = Arc::new(BlockingQueue::new(1));
+/// let q2 = q.clone();
+///
+/// let h = unsafe {
+///     coroutine::spawn(move || {
+///         for i in 0..10 {
+///             q2.put(i);
+///         }
+///     })
+/// };
+///
+/// for i in 0..10 {
+///     assert_eq!(q.take(), i);
+/// }
+/// h.join().unwrap();
+/// ```
+pub struct BlockingQueue<T> {
+    cap: usize,
+    queue: Mutex<VecDeque<T>>,
+    // waiters that wait for the queue not empty
+    not_empty: Condvar,
+    // waiters that wait for the queue not full
+    not_full: Condvar,
+}
+
+impl<T> BlockingQueue<T> {
+    /// create a blocking queue with the given capacity
+    pub fn new(cap: usize) -> Self {
+        assert!(cap > 0, "capacity must be greater than zero");
+        BlockingQueue {
+            cap,
+            queue: Mutex::new(VecDeque::with_capacity(cap)),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    /// return the capacity of the queue
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    /// return the number of elements in the queue
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// return true if the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.queue.lock().unwrap().is_empty()
+    }
+
+    // return the value back if timeout
+    fn put_impl(&self, t: T, deadline: Option<Instant>) -> Result<(), T> {
+        let mut queue = self.queue.lock().unwrap();
+        while queue.len() == self.cap {
+            queue = match deadline {
+                None => self.not_full.wait(queue).unwrap(),
+                Some(deadline) => {
+                    let (queue, ret) = self.not_full.wait_deadline(queue, deadline).unwrap();
+                    if ret.timed_out() && queue.len() == self.cap {
+                        return Err(t);
+                    }
+                    queue
+                }
+            };
+        }
+        queue.push_back(t);
+        drop(queue);
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    // return None if timeout
+    fn take_impl(&self, deadline: Option<Instant>) -> Option<T> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            if let Some(t) = queue.pop_front() {
+                drop(queue);
+                self.not_full.notify_one();
+                return Some(t);
+            }
+            queue = match deadline {
+                None => self.not_empty.wait(queue).unwrap(),
+                Some(deadline) => {
+                    let (queue, ret) = self.not_empty.wait_deadline(queue, deadline).unwrap();
+                    if ret.timed_out() && queue.is_empty() {
+                        return None;
+                    }
+                    queue
+                }
+            };
+        }
+    }
+
+    /// put a value into the queue, block until there is space available
+    pub fn put(&self, t: T) {
+        // without a timeout it would never fail
+        let _ = self.put_impl(t, None);
+    }
+
+    /// same as `put` except that with an extra timeout value
+    /// return the value back if timeout happened
+    pub fn put_timeout(&self, t: T, dur: Duration) -> Result<(), T> {
+        self.put_impl(t, deadline_after(dur))
+    }
+
+    /// same as `put` except that with a deadline
+    /// return the value back if the deadline is reached
+    pub fn put_deadline(&self, t: T, deadline: Instant) -> Result<(), T> {
+        self.put_impl(t, Some(deadline))
+    }
+
+    /// try to put a value into the queue without blocking
+    /// return the value back if the queue is full
+    pub fn try_put(&self, t: T) -> Result<(), T> {
+        self.put_impl(t, Some(Instant::now()))
+    }
+
+    /// take a value from the queue, block until there is one available
+    pub fn take(&self) -> T {
+        self.take_impl(None).expect("take without timeout failed")
+    }
+
+    /// same as `take` except that with an extra timeout value
+    /// return None if timeout happened
+    pub fn take_timeout(&self, dur: Duration) -> Option<T> {
+        self.take_impl(deadline_after(dur))
+    }
+
+    /// same as `take` except that with a deadline
+    /// return None if the deadline is reached
+    pub fn take_deadline(&self, deadline: Instant) -> Option<T> {
+        self.take_impl(Some(deadline))
+    }
+
+    /// try to take a value from the queue without blocking
+    /// return None if the queue is empty
+    pub fn try_take(&self) -> Option<T> {
+        self.take_impl(Some(Instant::now()))
+    }
+}
+
+impl<T> fmt::Debug for BlockingQueue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BlockingQueue")
+            .field("cap", &self.cap)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn sanity() {
+        let q = BlockingQueue::new(2);
+        assert!(q.is_empty());
+        assert_eq!(q.try_take(), None);
+        q.put(1);
+        assert_eq!(q.try_put(2), Ok(()));
+        assert_eq!(q.try_put(3), Err(3));
+        assert_eq!(q.len(), 2);
+        assert_eq!(q.take(), 1);
+        assert_eq!(q.try_take(), Some(2));
+        assert_eq!(q.try_take(), None);
+    }
+
+    #[test]
+    fn timeout() {
+        let q = BlockingQueue::new(1);
+        let now = Instant::now();
+        assert_eq!(q.take_timeout(Duration::from_millis(50)), None);
+        assert!(now.elapsed() >= Duration::from_millis(50));
+
+        q.put(1);
+        let now = Instant::now();
+        assert_eq!(q.put_timeout(2, Duration::from_millis(50)), Err(2));
+        assert!(now.elapsed() >= Duration::from_millis(50));
+        assert_eq!(q.take_timeout(Duration::from_millis(50)), Some(1));
+    }
+
+    #[test]
+    fn deadline() {
+        let q = BlockingQueue::new(1);
+        // a passed deadline returns immediately
+        assert_eq!(q.take_deadline(Instant::now()), None);
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        assert_eq!(q.take_deadline(deadline), None);
+        assert!(Instant::now() >= deadline);
+
+        q.put(1);
+        assert_eq!(q.put_deadline(2, Instant::now()), Err(2));
+        assert_eq!(q.take_deadline(Instant::now()), Some(1));
+    }
+
+    #[test]
+    fn put_wakeup_taker() {
+        let q = Arc::new(BlockingQueue::new(1));
+        let q2 = q.clone();
+        let h = go!(move || q2.take());
+        thread::sleep(Duration::from_millis(50));
+        q.put(42);
+        assert_eq!(h.join().unwrap(), 42);
+    }
+
+    #[test]
+    fn multi_producer_consumer() {
+        let q = Arc::new(BlockingQueue::new(4));
+        let producers: Vec<_> = (0..4)
+            .map(|i| {
+                let q = q.clone();
+                go!(move || {
+                    for j in 0..100 {
+                        q.put(i * 100 + j);
+                    }
+                })
+            })
+            .collect();
+
+        let consumers: Vec<_> = (0..4)
+            .map(|_| {
+                let q = q.clone();
+                thread::spawn(move || (0..100).map(|_| q.take()).sum::<usize>())
+            })
+            .collect();
+
+        for p in producers {
+            p.join().unwrap();
+        }
+        let sum: usize = consumers.into_iter().map(|c| c.join().unwrap()).sum();
+        assert_eq!(sum, (0..400).sum());
+        assert!(q.is_empty());
+    }
+}
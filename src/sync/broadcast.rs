@@ -0,0 +1,278 @@
+//! a broadcast channel that every receiver gets a copy of each value
+//!
+//! the channel keeps the latest `cap` values in a ring buffer. a receiver
+//! that falls behind more than `cap` values misses the overwritten ones,
+//! and the next receive reports the number of the skipped values by
+//! `Lagged`, then it continues from the oldest value still kept.
+//!
+//! it's useful for fanning out config updates or shutdown signals to many
+//! worker coroutines.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use may::sync::broadcast;
+//!
+//! let (tx, mut rx1) = broadcast::channel(16);
+//! let mut rx2 = rx1.clone();
+//! tx.send("reload").unwrap();
+//! assert_eq!(rx1.recv(), Ok("reload"));
+//! assert_eq!(rx2.recv(), Ok("reload"));
+//!
+//! drop(tx);
+//! assert_eq!(rx1.recv(), Err(broadcast::RecvError::Closed));
+//! ```
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::mpsc::SendError;
+use std::sync::Arc;
+
+use super::{Condvar, Mutex};
+
+/// the error returned by `Receiver::recv`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvError {
+    /// the sender is dropped and all the values are received
+    Closed,
+    /// the receiver fell behind, the number of the skipped values
+    Lagged(u64),
+}
+
+/// the error returned by `Receiver::try_recv`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// there is no new value at the moment
+    Empty,
+    /// the sender is dropped and all the values are received
+    Closed,
+    /// the receiver fell behind, the number of the skipped values
+    Lagged(u64),
+}
+
+struct State<T> {
+    // the latest values
+    buf: VecDeque<T>,
+    // the sequence number of the first value in the buffer
+    head: u64,
+    // the number of the receivers
+    receivers: usize,
+    // if the sender is dropped
+    closed: bool,
+}
+
+struct Inner<T> {
+    cap: usize,
+    state: Mutex<State<T>>,
+    cvar: Condvar,
+}
+
+impl<T: Clone> Inner<T> {
+    fn try_recv(&self, state: &State<T>, next: &mut u64) -> Result<T, TryRecvError> {
+        if *next < state.head {
+            let lagged = state.head - *next;
+            *next = state.head;
+            return Err(TryRecvError::Lagged(lagged));
+        }
+        match state.buf.get((*next - state.head) as usize) {
+            Some(v) => {
+                *next += 1;
+                Ok(v.clone())
+            }
+            None if state.closed => Err(TryRecvError::Closed),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+/// create a broadcast channel that keeps the latest `cap` values
+///
+/// panic if the `cap` is zero
+pub fn channel<T: Clone>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "broadcast channel capacity must be positive");
+    let inner = Arc::new(Inner {
+        cap,
+        state: Mutex::new(State {
+            buf: VecDeque::with_capacity(cap),
+            head: 0,
+            receivers: 1,
+            closed: false,
+        }),
+        cvar: Condvar::new(),
+    });
+    let rx = Receiver {
+        inner: inner.clone(),
+        next: 0,
+    };
+    (Sender { inner }, rx)
+}
+
+/// the sending half of a broadcast channel
+pub struct Sender<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: Clone> Sender<T> {
+    /// send the value to all the receivers
+    ///
+    /// the oldest value is overwritten if the buffer is full. return the
+    /// number of the receivers, or the value back if there is no receiver
+    pub fn send(&self, t: T) -> Result<usize, SendError<T>> {
+        let mut state = self.inner.state.lock().unwrap();
+        if state.receivers == 0 {
+            return Err(SendError(t));
+        }
+        if state.buf.len() == self.inner.cap {
+            state.buf.pop_front();
+            state.head += 1;
+        }
+        state.buf.push_back(t);
+        let receivers = state.receivers;
+        drop(state);
+        self.inner.cvar.notify_all();
+        Ok(receivers)
+    }
+
+    /// create a new receiver that gets the values sent after the call
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.inner.state.lock().unwrap();
+        state.receivers += 1;
+        Receiver {
+            inner: self.inner.clone(),
+            next: state.head + state.buf.len() as u64,
+        }
+    }
+
+    /// the number of the receivers
+    pub fn receiver_count(&self) -> usize {
+        self.inner.state.lock().unwrap().receivers
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.inner.state.lock().unwrap().closed = true;
+        self.inner.cvar.notify_all();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sender {{ .. }}")
+    }
+}
+
+/// the receiving half of a broadcast channel
+///
+/// a cloned receiver starts from the same position as the original one
+pub struct Receiver<T> {
+    inner: Arc<Inner<T>>,
+    // the sequence number of the next value to receive
+    next: u64,
+}
+
+impl<T: Clone> Receiver<T> {
+    /// receive the next value without blocking
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let state = self.inner.state.lock().unwrap();
+        self.inner.try_recv(&state, &mut self.next)
+    }
+
+    /// receive the next value
+    ///
+    /// block until there is a new value or the sender is dropped
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        let mut state = self.inner.state.lock().unwrap();
+        loop {
+            match self.inner.try_recv(&state, &mut self.next) {
+                Ok(v) => return Ok(v),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvError::Lagged(n)),
+                Err(TryRecvError::Empty) => state = self.inner.cvar.wait(state).unwrap(),
+            }
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.inner.state.lock().unwrap().receivers += 1;
+        Receiver {
+            inner: self.inner.clone(),
+            next: self.next,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.state.lock().unwrap().receivers -= 1;
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Receiver {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fan_out() {
+        let (tx, rx) = channel(16);
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let mut rx = rx.clone();
+                go!(move || {
+                    let mut sum = 0;
+                    while let Ok(v) = rx.recv() {
+                        sum += v;
+                    }
+                    sum
+                })
+            })
+            .collect();
+        drop(rx);
+        for i in 0..10 {
+            assert_eq!(tx.send(i), Ok(10));
+        }
+        drop(tx);
+        for h in handles {
+            assert_eq!(h.join().unwrap(), 45);
+        }
+    }
+
+    #[test]
+    fn lagged() {
+        let (tx, mut rx) = channel(4);
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Lagged(6)));
+        for i in 6..10 {
+            assert_eq!(rx.try_recv(), Ok(i));
+        }
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+
+        // a new subscriber only sees the later values
+        let mut rx2 = tx.subscribe();
+        tx.send(10).unwrap();
+        drop(tx);
+        assert_eq!(rx2.recv(), Ok(10));
+        assert_eq!(rx2.recv(), Err(RecvError::Closed));
+        assert_eq!(rx.recv(), Ok(10));
+        assert_eq!(rx.recv(), Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn no_receiver() {
+        let (tx, rx) = channel(4);
+        let rx2 = rx.clone();
+        assert_eq!(tx.receiver_count(), 2);
+        drop(rx);
+        drop(rx2);
+        assert_eq!(tx.send(1), Err(SendError(1)));
+    }
+}
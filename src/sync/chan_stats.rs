@@ -0,0 +1,88 @@
+//! per channel counters for the `mpsc` and `mpmc` channels
+//!
+//! the counting is only compiled in with the `chan_stats` feature,
+//! otherwise the counters are no-op and have no runtime cost
+
+#[cfg(feature = "chan_stats")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// a snapshot of the channel counters
+///
+/// all the counters are monotonic, and `received` never exceeds `sent`
+/// in the same snapshot
+#[cfg(feature = "chan_stats")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChanStats {
+    /// total values accepted by the channel
+    pub sent: u64,
+    /// total values handed out to the receivers
+    pub received: u64,
+    /// total values rejected because the receivers are gone or the
+    /// bounded channel is full, or discarded when the last receiver
+    /// is dropped
+    pub dropped: u64,
+}
+
+#[cfg(feature = "chan_stats")]
+pub(crate) struct Counters {
+    sent: AtomicU64,
+    received: AtomicU64,
+    dropped: AtomicU64,
+}
+
+#[cfg(feature = "chan_stats")]
+impl Counters {
+    pub fn new() -> Self {
+        Counters {
+            sent: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    // must be called before the value is pushed into the queue
+    #[inline]
+    pub fn sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn received(&self) {
+        // pair with the acquire load in snapshot so that the
+        // matching sent count is visible there
+        self.received.fetch_add(1, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ChanStats {
+        let received = self.received.load(Ordering::Acquire);
+        ChanStats {
+            sent: self.sent.load(Ordering::Relaxed),
+            received,
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(not(feature = "chan_stats"))]
+pub(crate) struct Counters;
+
+#[cfg(not(feature = "chan_stats"))]
+impl Counters {
+    pub fn new() -> Self {
+        Counters
+    }
+
+    #[inline(always)]
+    pub fn sent(&self) {}
+
+    #[inline(always)]
+    pub fn received(&self) {}
+
+    #[inline(always)]
+    pub fn dropped(&self) {}
+}
@@ -0,0 +1,292 @@
+//! a channel that coalesces the pending values by key
+//!
+//! sending a value for a key that is still queued replaces the old value
+//! in place instead of appending a new one, so a slow receiver always gets
+//! the latest value for each key and the channel never grows beyond the
+//! number of distinct keys. it's useful for state propagation like
+//! "the latest config for key K".
+//!
+//! the keys are received in the order they are first queued.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use may::sync::coalescing;
+//!
+//! let (tx, rx) = coalescing::channel();
+//! tx.send("a", 1).unwrap();
+//! tx.send("b", 1).unwrap();
+//! tx.send("a", 2).unwrap();
+//! assert_eq!(rx.recv(), Ok(("a", 2)));
+//! assert_eq!(rx.recv(), Ok(("b", 1)));
+//! assert!(rx.try_recv().is_err());
+//! ```
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::blocking::{deadline_after, recv_until};
+use super::{AtomicOption, Blocker};
+
+// the pending values, the key is shared by the map and the order queue
+struct Pending<K, V> {
+    values: HashMap<Arc<K>, V>,
+    order: VecDeque<Arc<K>>,
+}
+
+struct Inner<K, V> {
+    pending: Mutex<Pending<K, V>>,
+    // thread/coroutine for wake up
+    to_wake: AtomicOption<Arc<Blocker>>,
+    // The number of tx channels which are currently using this queue.
+    channels: AtomicUsize,
+    // if rx is dropped
+    port_dropped: AtomicBool,
+}
+
+impl<K: Eq + Hash, V> Inner<K, V> {
+    fn new() -> Self {
+        Inner {
+            pending: Mutex::new(Pending {
+                values: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+            to_wake: AtomicOption::none(),
+            channels: AtomicUsize::new(1),
+            port_dropped: AtomicBool::new(false),
+        }
+    }
+
+    fn send(&self, k: K, v: V) -> Result<(), (K, V)> {
+        if self.port_dropped.load(Ordering::Acquire) {
+            return Err((k, v));
+        }
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if let Some(old) = pending.values.get_mut(&k) {
+                // replace the queued value in place
+                *old = v;
+                return Ok(());
+            }
+            let k = Arc::new(k);
+            pending.values.insert(k.clone(), v);
+            pending.order.push_back(k);
+        }
+        if let Some(w) = self.to_wake.take(Ordering::Acquire) {
+            w.unpark();
+        }
+        Ok(())
+    }
+
+    fn try_recv(&self) -> Result<(K, V), TryRecvError> {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.order.pop_front() {
+            Some(k) => {
+                let v = pending
+                    .values
+                    .remove(&*k)
+                    .expect("coalescing value not found");
+                // the map entry is removed, there is no other reference
+                let k = Arc::try_unwrap(k).unwrap_or_else(|_| unreachable!());
+                Ok((k, v))
+            }
+            None if self.channels.load(Ordering::Acquire) == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn recv(&self, deadline: Option<Instant>) -> Result<(K, V), TryRecvError> {
+        match self.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            data => return data,
+        }
+
+        recv_until(&self.to_wake, deadline, || self.try_recv())
+    }
+
+    fn len(&self) -> usize {
+        self.pending.lock().unwrap().order.len()
+    }
+
+    fn drop_chan(&self) {
+        match self.channels.fetch_sub(1, Ordering::AcqRel) {
+            1 => self
+                .to_wake
+                .take(Ordering::Relaxed)
+                .map(|w| w.unpark())
+                .unwrap_or(()),
+            n if n > 1 => {}
+            n => panic!("bad number of channels left {}", n),
+        }
+    }
+
+    fn drop_port(&self) {
+        self.port_dropped.store(true, Ordering::Release);
+        // clear all the data
+        let mut pending = self.pending.lock().unwrap();
+        pending.order.clear();
+        pending.values.clear();
+    }
+}
+
+/// create a coalescing channel
+pub fn channel<K: Eq + Hash, V>() -> (Sender<K, V>, Receiver<K, V>) {
+    let a = Arc::new(Inner::new());
+    (Sender { inner: a.clone() }, Receiver { inner: a })
+}
+
+/// the sending half of a coalescing channel
+pub struct Sender<K: Eq + Hash, V> {
+    inner: Arc<Inner<K, V>>,
+}
+
+impl<K: Eq + Hash, V> Sender<K, V> {
+    /// send the value for the key
+    ///
+    /// if a value for the key is still queued, it's replaced by the new
+    /// value and keeps its position in the queue. return the key and
+    /// value back if the receiver is dropped
+    pub fn send(&self, k: K, v: V) -> Result<(), SendError<(K, V)>> {
+        self.inner.send(k, v).map_err(SendError)
+    }
+}
+
+impl<K: Eq + Hash, V> Clone for Sender<K, V> {
+    fn clone(&self) -> Self {
+        self.inner.channels.fetch_add(1, Ordering::AcqRel);
+        Sender {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> Drop for Sender<K, V> {
+    fn drop(&mut self) {
+        self.inner.drop_chan();
+    }
+}
+
+impl<K: Eq + Hash, V> fmt::Debug for Sender<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sender {{ .. }}")
+    }
+}
+
+/// the receiving half of a coalescing channel
+pub struct Receiver<K: Eq + Hash, V> {
+    inner: Arc<Inner<K, V>>,
+}
+
+impl<K: Eq + Hash, V> Receiver<K, V> {
+    /// receive the next key and its latest value without blocking
+    pub fn try_recv(&self) -> Result<(K, V), TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// receive the next key and its latest value
+    ///
+    /// block until there is one or all the senders are dropped
+    pub fn recv(&self) -> Result<(K, V), RecvError> {
+        loop {
+            match self.inner.recv(None) {
+                Err(TryRecvError::Empty) => {}
+                data => return data.map_err(|_| RecvError),
+            }
+        }
+    }
+
+    /// same as `recv` except that with a timeout
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<(K, V), RecvTimeoutError> {
+        match deadline_after(timeout) {
+            Some(deadline) => self.recv_deadline(deadline),
+            None => self.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        }
+    }
+
+    /// same as `recv` except that with a deadline
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<(K, V), RecvTimeoutError> {
+        loop {
+            match self.inner.recv(Some(deadline)) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {}
+            }
+
+            if Instant::now() >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+        }
+    }
+
+    /// the number of the distinct keys that are pending
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// return true if there is no pending key
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Eq + Hash, V> Drop for Receiver<K, V> {
+    fn drop(&mut self) {
+        self.inner.drop_port();
+    }
+}
+
+impl<K: Eq + Hash, V> fmt::Debug for Receiver<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Receiver {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn coalesce() {
+        let (tx, rx) = channel();
+        for i in 0..10 {
+            tx.send(i % 3, i).unwrap();
+        }
+        assert_eq!(rx.len(), 3);
+        assert_eq!(rx.try_recv(), Ok((0, 9)));
+        assert_eq!(rx.try_recv(), Ok((1, 7)));
+        // a new value for a received key is queued at the end
+        tx.send(0, 10).unwrap();
+        assert_eq!(rx.try_recv(), Ok((2, 8)));
+        assert_eq!(rx.try_recv(), Ok((0, 10)));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn recv_wakeup() {
+        let (tx, rx) = channel();
+        let h = go!(move || rx.recv());
+        thread::sleep(Duration::from_millis(50));
+        tx.send("k", 1).unwrap();
+        assert_eq!(h.join().unwrap(), Ok(("k", 1)));
+        assert!(tx.send("k", 2).is_err());
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (tx, rx) = channel::<u32, u32>();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        let h = thread::spawn(move || rx.recv());
+        drop(tx);
+        assert_eq!(h.join().unwrap(), Err(RecvError));
+    }
+}
@@ -3,13 +3,13 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::{LockResult, PoisonError};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::cancel::trigger_cancel_panic;
 use crate::park::ParkError;
 use may_queue::spsc;
 
-use super::blocking::SyncBlocker;
+use super::blocking::{deadline_after, park_until, SyncBlocker};
 use super::mutex::{self, Mutex, MutexGuard};
 
 /// A type indicating whether a timed wait on a condition variable returned
@@ -40,7 +40,11 @@ impl Condvar {
     }
 
     // return false if timeout happened
-    pub fn wait_impl<T>(&self, lock: &Mutex<T>, dur: Option<Duration>) -> Result<(), ParkError> {
+    pub fn wait_impl<T>(
+        &self,
+        lock: &Mutex<T>,
+        deadline: Option<Instant>,
+    ) -> Result<(), ParkError> {
         let cancel = if crate::coroutine_impl::is_coroutine() {
             Some(crate::coroutine_impl::current_cancel_data())
         } else {
@@ -49,23 +53,31 @@ impl Condvar {
         // enqueue the blocker
         let cur = SyncBlocker::current();
 
-        // we can't cancel panic here!!
-        if let Some(c) = cancel.as_ref() {
-            c.disable_cancel();
-        }
+        let mut registered = false;
+        // wait until coming back
+        let ret = park_until(&cur, deadline, |cur| {
+            // we can't cancel panic here!!
+            if let Some(c) = cancel.as_ref() {
+                c.disable_cancel();
+            }
 
-        let g = self.to_wake.lock().unwrap();
-        g.push(cur.clone());
-        drop(g);
+            let g = self.to_wake.lock().unwrap();
+            g.push(cur.clone());
+            drop(g);
 
-        // unlock the mutex to let other continue
-        mutex::unlock_mutex(lock);
-        if let Some(c) = cancel.as_ref() {
-            c.enable_cancel();
-        }
+            // unlock the mutex to let other continue
+            mutex::unlock_mutex(lock);
+            if let Some(c) = cancel.as_ref() {
+                c.enable_cancel();
+            }
+            registered = true;
+            true
+        });
 
-        // wait until coming back
-        let ret = cur.park(dur);
+        // the deadline is already passed, the lock is still held
+        if !registered {
+            return ret;
+        }
         // disable cancel panic
         if let Some(c) = cancel.as_ref() {
             c.disable_cancel();
@@ -124,15 +136,77 @@ impl Condvar {
         }
     }
 
+    /// block until the condition returns false
+    ///
+    /// the condition is checked before waiting and after every wakeup,
+    /// so the spurious wakeups are ignored
+    pub fn wait_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> LockResult<MutexGuard<'a, T>>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard)?;
+        }
+        Ok(guard)
+    }
+
+    /// same as `wait_while` except that with a timeout
+    ///
+    /// the result is timed out only when the duration elapsed and the
+    /// condition still returns true
+    pub fn wait_timeout_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+        mut condition: F,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let deadline = deadline_after(dur);
+        loop {
+            if !condition(&mut *guard) {
+                return Ok((guard, WaitTimeoutResult(false)));
+            }
+            if let Some(deadline) = deadline {
+                if Instant::now() >= deadline {
+                    return Ok((guard, WaitTimeoutResult(true)));
+                }
+            }
+            guard = self.wait_until(guard, deadline)?.0;
+        }
+    }
+
     pub fn wait_timeout<'a, T>(
         &self,
         guard: MutexGuard<'a, T>,
         dur: Duration,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        self.wait_until(guard, deadline_after(dur))
+    }
+
+    /// same as `wait_timeout` except that with a deadline
+    pub fn wait_deadline<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        deadline: Instant,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        self.wait_until(guard, Some(deadline))
+    }
+
+    fn wait_until<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        deadline: Option<Instant>,
     ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
         let (poisoned, result) = {
             let lock = mutex::guard_lock(&guard);
             self.verify(lock as *const _ as usize);
-            let ret = self.wait_impl(lock, Some(dur));
+            let ret = self.wait_impl(lock, deadline);
             if ret == Err(ParkError::Canceled) {
                 // don't set the poison flag
                 ::std::mem::forget(guard);
@@ -292,6 +366,38 @@ mod tests {
         drop(g);
     }
 
+    #[test]
+    fn wait_while() {
+        let pair = Arc::new((Mutex::new(0), Condvar::new()));
+        let pair2 = pair.clone();
+        let h = thread::spawn(move || {
+            let (lock, cond) = &*pair2;
+            for _ in 0..3 {
+                // the waiter is woken up before the condition is met
+                *lock.lock().unwrap() += 1;
+                cond.notify_one();
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let (lock, cond) = &*pair;
+        let g = cond.wait_while(lock.lock().unwrap(), |n| *n < 3).unwrap();
+        assert_eq!(*g, 3);
+        drop(g);
+        h.join().unwrap();
+
+        let g = lock.lock().unwrap();
+        let (g, res) = cond
+            .wait_timeout_while(g, Duration::from_millis(10), |n| *n < 4)
+            .unwrap();
+        assert!(res.timed_out());
+        let (g, res) = cond
+            .wait_timeout_while(g, Duration::from_millis(10), |n| *n < 3)
+            .unwrap();
+        assert!(!res.timed_out());
+        assert_eq!(*g, 3);
+    }
+
     #[test]
     #[should_panic]
     fn two_mutexes() {
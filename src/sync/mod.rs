@@ -1,21 +1,36 @@
 mod atomic_option;
+mod barrier;
 mod blocking;
+mod blocking_queue;
+mod chan_stats;
 mod condvar;
 mod mutex;
+mod once;
 mod poison;
 mod rwlock;
 mod semphore;
 mod sync_flag;
+mod wait_group;
 
 pub(crate) mod atomic_dur;
+pub mod broadcast;
+pub mod coalescing;
 #[cfg(not(unix))]
 pub(crate) mod delay_drop;
 pub mod mpmc;
 pub mod mpsc;
 pub use self::atomic_option::AtomicOption;
+pub use self::barrier::{Barrier, BarrierWaitResult};
+pub(crate) use self::blocking::park_until;
 pub use self::blocking::{Blocker, FastBlocker};
+pub use self::blocking_queue::BlockingQueue;
+#[cfg(feature = "chan_stats")]
+pub use self::chan_stats::ChanStats;
 pub use self::condvar::{Condvar, WaitTimeoutResult};
+pub use self::mpsc::select_recv;
 pub use self::mutex::{Mutex, MutexGuard};
-pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use self::once::Once;
+pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
 pub use self::semphore::Semphore;
 pub use self::sync_flag::SyncFlag;
+pub use self::wait_group::WaitGroup;
@@ -1,17 +1,23 @@
 //! mpmc synchronized channel implementation
 //! support an unbounded mpmc queue, senders would not block
+//! and a bounded one by `bounded`, senders would block when it's full
 //! receivers would block if there is no data until sender send data
 //! it's almost the same as `mpsc` except that we support multi receivers
 //! each receiver would consume one data each time so that other receivers
 //! would not see that the same data any more
 
 use std::fmt;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
+use super::blocking::deadline_after;
+use super::chan_stats::Counters;
+#[cfg(feature = "chan_stats")]
+use super::ChanStats;
 use super::Semphore;
+use crate::park_reason::{self, ParkReason};
 use crossbeam::queue::SegQueue;
 
 /// /////////////////////////////////////////////////////////////////////////////
@@ -21,71 +27,155 @@ struct InnerQueue<T> {
     queue: SegQueue<T>,
     // thread/coroutine for wake up
     sem: Semphore,
+    // the free slots of a bounded queue, senders wait on it
+    slots: Option<Semphore>,
     // The number of tx channels which are currently using this queue.
     tx_ports: AtomicUsize,
     // if rx is dropped
     rx_ports: AtomicUsize,
+    // if the channel is closed by a sender
+    closed: AtomicBool,
+    stats: Counters,
 }
 
 impl<T> InnerQueue<T> {
-    pub fn new() -> InnerQueue<T> {
+    pub fn new(cap: Option<usize>) -> InnerQueue<T> {
         InnerQueue {
             queue: SegQueue::new(),
             sem: Semphore::new(0),
+            slots: cap.map(Semphore::new),
             tx_ports: AtomicUsize::new(1),
             rx_ports: AtomicUsize::new(1),
+            closed: AtomicBool::new(false),
+            stats: Counters::new(),
         }
     }
 
     pub fn send(&self, t: T) -> Result<(), SendError<T>> {
-        if self.rx_ports.load(Ordering::Acquire) == 0 {
+        if self.is_rx_gone() {
+            self.stats.dropped();
             return Err(SendError(t));
         }
 
+        if let Some(slots) = self.slots.as_ref() {
+            // block until a receiver drains an item
+            if !slots.wait_or_cancel() {
+                // canceled, give the value back to the sender
+                return Err(SendError(t));
+            }
+            if self.is_rx_gone() {
+                // pass the wakeup to other blocked senders
+                slots.post();
+                self.stats.dropped();
+                return Err(SendError(t));
+            }
+        }
+
+        self.push(t);
+        Ok(())
+    }
+
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        if self.is_rx_gone() {
+            self.stats.dropped();
+            return Err(TrySendError::Disconnected(t));
+        }
+
+        if let Some(slots) = self.slots.as_ref() {
+            if !slots.try_wait() {
+                self.stats.dropped();
+                return Err(TrySendError::Full(t));
+            }
+        }
+
+        self.push(t);
+        Ok(())
+    }
+
+    fn push(&self, t: T) {
+        self.stats.sent();
         self.queue.push(t);
         self.sem.post();
-        Ok(())
     }
 
-    pub fn recv(&self, dur: Option<Duration>) -> Result<T, RecvTimeoutError> {
+    // return the slot of a received item to the senders
+    fn pop(&self) -> Option<T> {
+        let data = self.queue.pop()?;
+        self.stats.received();
+        if let Some(slots) = self.slots.as_ref() {
+            slots.post();
+        }
+        Some(data)
+    }
+
+    fn is_rx_gone(&self) -> bool {
+        self.rx_ports.load(Ordering::Acquire) == 0 || self.closed.load(Ordering::Acquire)
+    }
+
+    pub fn recv(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
         match self.try_recv() {
             Ok(data) => return Ok(data),
             Err(TryRecvError::Empty) => {}
             Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
         }
 
-        match dur {
+        let _reason = park_reason::record(ParkReason::ChannelRecv);
+        match deadline {
             None => self.sem.wait(),
-            Some(t) => {
-                if !self.sem.wait_timeout(t) {
+            Some(d) => {
+                if !self.sem.wait_deadline(d) {
                     return Err(RecvTimeoutError::Timeout);
                 }
             }
         }
 
-        match self.queue.pop() {
+        match self.pop() {
             Some(data) => Ok(data),
-            None => match self.tx_ports.load(Ordering::Acquire) {
-                0 => Err(RecvTimeoutError::Disconnected),
-                _n => unreachable!("mpmc recv found no data"),
-            },
+            None if self.is_disconnected() => Err(RecvTimeoutError::Disconnected),
+            None => unreachable!("mpmc recv found no data"),
         }
     }
 
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
         if !self.sem.try_wait() {
-            return match self.tx_ports.load(Ordering::Acquire) {
-                0 => Err(TryRecvError::Disconnected),
-                _ => Err(TryRecvError::Empty),
+            return if self.is_disconnected() {
+                Err(TryRecvError::Disconnected)
+            } else {
+                Err(TryRecvError::Empty)
             };
         }
 
-        match self.queue.pop() {
+        match self.pop() {
             Some(data) => Ok(data),
-            None => match self.tx_ports.load(Ordering::Acquire) {
-                0 => Err(TryRecvError::Disconnected),
-                _ => unreachable!("mpmc try_recv found no data"),
-            },
+            None if self.is_disconnected() => Err(TryRecvError::Disconnected),
+            None => unreachable!("mpmc try_recv found no data"),
+        }
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.tx_ports.load(Ordering::Acquire) == 0 || self.closed.load(Ordering::Acquire)
+    }
+
+    // tell all the waited rx to come back
+    fn wake_all_rx(&self) {
+        while self.sem.get_value() == 0 {
+            self.sem.post();
+        }
+    }
+
+    // tell all the blocked tx to come back
+    fn wake_all_tx(&self) {
+        if let Some(slots) = self.slots.as_ref() {
+            while slots.get_value() == 0 {
+                slots.post();
+            }
+        }
+    }
+
+    pub fn close(&self) {
+        if !self.closed.swap(true, Ordering::AcqRel) {
+            self.wake_all_rx();
+            self.wake_all_tx();
         }
     }
 
@@ -97,10 +187,7 @@ impl<T> InnerQueue<T> {
         match self.tx_ports.fetch_sub(1, Ordering::SeqCst) {
             1 => {
                 // there is no tx port any more
-                // should tell all the waited rx to come back
-                while self.sem.get_value() == 0 {
-                    self.sem.post();
-                }
+                self.wake_all_rx();
             }
             n if n > 1 => {}
             n => panic!("bad number of tx_ports left {}", n),
@@ -115,7 +202,10 @@ impl<T> InnerQueue<T> {
         match self.rx_ports.fetch_sub(1, Ordering::SeqCst) {
             1 => {
                 // there is no receiver any more, clear the data
-                while self.queue.pop().is_some() {}
+                while self.queue.pop().is_some() {
+                    self.stats.dropped();
+                }
+                self.wake_all_tx();
             }
             n if n > 1 => {}
             n => panic!("bad number of rx_ports left {}", n),
@@ -157,7 +247,17 @@ unsafe impl<T: Send> Send for Sender<T> {}
 // impl<T> !Sync for Sender<T> {}
 
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
-    let a = Arc::new(InnerQueue::new());
+    let a = Arc::new(InnerQueue::new(None));
+    (Sender::new(a.clone()), Receiver::new(a))
+}
+
+/// create a bounded channel that holds at most `cap` items
+///
+/// `send` blocks when the channel is full until a receiver drains an item,
+/// and fails when all the receivers are dropped
+pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "bounded channel with zero capacity");
+    let a = Arc::new(InnerQueue::new(Some(cap)));
     (Sender::new(a.clone()), Receiver::new(a))
 }
 
@@ -170,14 +270,39 @@ impl<T> Sender<T> {
         Sender { inner }
     }
 
+    /// send the value, block when a bounded channel is full
+    ///
+    /// if the coroutine is canceled while blocked, the value is given back
+    /// in the error, and the cancel panic happens at the next blocking call.
+    /// so a losing send arm of `select!` keeps the value in its slot
     pub fn send(&self, t: T) -> Result<(), SendError<T>> {
         self.inner.send(t)
     }
 
+    /// send without blocking, return `Full` if a bounded channel is full
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        self.inner.try_send(t)
+    }
+
     /// return how many elements in the queue that are not consumed by receivers
     pub fn pressure(&self) -> usize {
         self.inner.sem.get_value()
     }
+
+    /// close the channel while the senders are still alive
+    ///
+    /// all the parked receivers are woken up immediately, they would still
+    /// get the values that are already sent and then `Disconnected`. the
+    /// following sends from any sender would fail
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// return a snapshot of the channel counters
+    #[cfg(feature = "chan_stats")]
+    pub fn stats(&self) -> ChanStats {
+        self.inner.stats.snapshot()
+    }
 }
 
 impl<T> Clone for Sender<T> {
@@ -212,6 +337,12 @@ impl<T> Receiver<T> {
         self.inner.try_recv()
     }
 
+    /// return a snapshot of the channel counters
+    #[cfg(feature = "chan_stats")]
+    pub fn stats(&self) -> ChanStats {
+        self.inner.stats.snapshot()
+    }
+
     pub fn recv(&self) -> Result<T, RecvError> {
         match self.inner.recv(None) {
             Err(RecvTimeoutError::Timeout) => unreachable!("mpmc recv timeout"),
@@ -220,7 +351,12 @@ impl<T> Receiver<T> {
     }
 
     pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
-        self.inner.recv(Some(timeout))
+        self.inner.recv(deadline_after(timeout))
+    }
+
+    /// same as `recv_timeout` except that with a deadline
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        self.inner.recv(Some(deadline))
     }
 
     pub fn iter(&self) -> Iter<T> {
@@ -356,6 +492,42 @@ mod tests {
         assert_eq!(rx.recv().unwrap(), 1);
     }
 
+    #[test]
+    fn bounded_backpressure() {
+        let (tx, rx) = bounded::<i32>(2);
+        tx.send(1).unwrap();
+        tx.try_send(2).unwrap();
+        assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+
+        let h = go!(move || {
+            // blocked until the receiver drains an item
+            tx.send(3).unwrap();
+            tx.send(4).unwrap();
+        });
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(rx.inner.queue.len(), 2);
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv().unwrap(), 2);
+        h.join().unwrap();
+        assert_eq!(rx.iter().collect::<Vec<_>>(), [3, 4]);
+    }
+
+    #[test]
+    fn bounded_rx_gone() {
+        let (tx, rx) = bounded::<i32>(1);
+        tx.send(1).unwrap();
+        let h1 = {
+            let tx = tx.clone();
+            go!(move || tx.send(2))
+        };
+        let h2 = thread::spawn(move || tx.send(3));
+        thread::sleep(Duration::from_millis(50));
+        drop(rx);
+        // the pending senders fail
+        assert_eq!(h1.join().unwrap(), Err(SendError(2)));
+        assert_eq!(h2.join().unwrap(), Err(SendError(3)));
+    }
+
     #[test]
     fn smoke_port_gone() {
         let (tx, rx) = channel::<i32>();
@@ -1036,4 +1208,72 @@ mod tests {
         }
         assert_eq!(rx1.try_recv().is_err(), true);
     }
+
+    #[cfg(feature = "chan_stats")]
+    #[test]
+    fn stats() {
+        use crate::sync::ChanStats;
+
+        let (tx, rx) = channel::<i32>();
+        let rx2 = rx.clone();
+        for i in 0..4 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.recv(), Ok(0));
+        assert_eq!(rx2.try_recv(), Ok(1));
+        assert_eq!(
+            rx.stats(),
+            ChanStats {
+                sent: 4,
+                received: 2,
+                dropped: 0,
+            }
+        );
+
+        drop(rx);
+        drop(rx2);
+        assert!(tx.send(4).is_err());
+        assert_eq!(
+            tx.stats(),
+            ChanStats {
+                sent: 4,
+                received: 2,
+                dropped: 3,
+            }
+        );
+
+        // the rejections of a full bounded channel
+        let (tx, _rx) = bounded::<i32>(1);
+        tx.try_send(0).unwrap();
+        assert!(tx.try_send(1).is_err());
+        assert!(tx.try_send(2).is_err());
+        assert_eq!(
+            tx.stats(),
+            ChanStats {
+                sent: 1,
+                received: 0,
+                dropped: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn close_wakes_all_receivers() {
+        let (tx, rx) = channel::<i32>();
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let rx = rx.clone();
+                go!(move || rx.recv())
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(50));
+        let start = std::time::Instant::now();
+        tx.close();
+        for w in waiters {
+            assert_eq!(w.join().unwrap(), Err(RecvError));
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(tx.send(1).is_err());
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
 }
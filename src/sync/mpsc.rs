@@ -1,13 +1,24 @@
 //! compatible with std::sync::mpsc except for both thread and coroutine
 //! please ref the doc from std::sync::mpsc
+//!
+//! the values sent by one sender are always received in the send order,
+//! no matter the sender and receiver coroutines migrate between workers.
+//! there is no ordering between the values from different senders.
 use std::fmt;
 use std::panic::{RefUnwindSafe, UnwindSafe};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
-use std::sync::Arc;
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
+use super::blocking::{deadline_after, recv_until};
+use super::chan_stats::Counters;
+#[cfg(feature = "chan_stats")]
+use super::ChanStats;
 use super::{AtomicOption, Blocker};
+use crate::cancel::trigger_cancel_panic;
+use crate::park::ParkError;
+use crate::park_reason::{self, ParkReason};
 use may_queue::mpsc_list::Queue as WaitList;
 // TODO: SyncSender
 /// /////////////////////////////////////////////////////////////////////////////
@@ -21,6 +32,9 @@ struct InnerQueue<T> {
     channels: AtomicUsize,
     // if rx is dropped
     port_dropped: AtomicBool,
+    // if the channel is closed by a sender
+    closed: AtomicBool,
+    stats: Counters,
 }
 
 impl<T> InnerQueue<T> {
@@ -30,13 +44,17 @@ impl<T> InnerQueue<T> {
             to_wake: AtomicOption::none(),
             channels: AtomicUsize::new(1),
             port_dropped: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            stats: Counters::new(),
         }
     }
 
     pub fn send(&self, t: T) -> Result<(), T> {
-        if self.port_dropped.load(Ordering::Acquire) {
+        if self.port_dropped.load(Ordering::Acquire) || self.closed.load(Ordering::Acquire) {
+            self.stats.dropped();
             return Err(t);
         }
+        self.stats.sent();
         self.queue.push(t);
         if let Some(w) = self.to_wake.take(Ordering::Acquire) {
             w.unpark();
@@ -44,44 +62,41 @@ impl<T> InnerQueue<T> {
         Ok(())
     }
 
-    pub fn recv(&self, dur: Option<Duration>) -> Result<T, TryRecvError> {
+    pub fn recv(&self, deadline: Option<Instant>) -> Result<T, TryRecvError> {
         match self.try_recv() {
             Err(TryRecvError::Empty) => {}
             data => return data,
         }
 
-        let cur = Blocker::current();
-        // register the waiter
-        self.to_wake.swap(cur.clone(), Ordering::Release);
-        // re-check the queue
-        match self.try_recv() {
-            Err(TryRecvError::Empty) => {
-                cur.park(dur).ok();
-            }
-            data => {
-                // no need to park, contention with send
-                if let Some(w) = self.to_wake.take(Ordering::Acquire) {
-                    w.unpark();
-                }
-                cur.park(dur).ok();
-                return data;
-            }
-        }
-
-        // after come back try recv again
-        self.try_recv()
+        let _reason = park_reason::record(ParkReason::ChannelRecv);
+        recv_until(&self.to_wake, deadline, || self.try_recv())
     }
 
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
-        match self.queue.pop() {
-            Some(data) => Ok(data),
+        let data = match self.queue.pop() {
+            Some(data) => data,
             None => {
-                match self.channels.load(Ordering::Acquire) {
+                if self.is_disconnected() {
                     // there is no sender any more, should re-check
-                    0 => self.queue.pop().ok_or(TryRecvError::Disconnected),
-                    _ => Err(TryRecvError::Empty),
+                    self.queue.pop().ok_or(TryRecvError::Disconnected)?
+                } else {
+                    return Err(TryRecvError::Empty);
                 }
             }
+        };
+        self.stats.received();
+        Ok(data)
+    }
+
+    fn is_disconnected(&self) -> bool {
+        self.channels.load(Ordering::Acquire) == 0 || self.closed.load(Ordering::Acquire)
+    }
+
+    pub fn close(&self) {
+        if !self.closed.swap(true, Ordering::AcqRel) {
+            if let Some(w) = self.to_wake.take(Ordering::Acquire) {
+                w.unpark();
+            }
         }
     }
 
@@ -89,6 +104,24 @@ impl<T> InnerQueue<T> {
         self.channels.fetch_add(1, Ordering::AcqRel);
     }
 
+    // add a new tx channel only if there is still a live one
+    // return false if all the tx channels are already dropped
+    pub fn upgrade_chan(&self) -> bool {
+        let mut cnt = self.channels.load(Ordering::Acquire);
+        while cnt != 0 {
+            match self.channels.compare_exchange_weak(
+                cnt,
+                cnt + 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return true,
+                Err(x) => cnt = x,
+            }
+        }
+        false
+    }
+
     pub fn drop_chan(&self) {
         match self.channels.fetch_sub(1, Ordering::AcqRel) {
             1 => self
@@ -104,7 +137,9 @@ impl<T> InnerQueue<T> {
     pub fn drop_port(&self) {
         self.port_dropped.store(true, Ordering::Release);
         // clear all the data
-        while self.queue.pop().is_some() {}
+        while self.queue.pop().is_some() {
+            self.stats.dropped();
+        }
     }
 }
 
@@ -122,6 +157,61 @@ pub struct Receiver<T> {
 unsafe impl<T: Send> Send for Receiver<T> {}
 // impl<T> !Sync for Receiver<T> {}
 
+/// receive from whichever receiver is ready first
+///
+/// park the current context on all the receivers until any of them has
+/// data or is disconnected, then return its index and the recv result.
+/// when more than one are ready the lowest index wins. the other receivers
+/// are left untouched, no value is consumed from them
+///
+/// # Panics
+///
+/// panic if `receivers` is empty
+pub fn select_recv<T>(receivers: &[Receiver<T>]) -> (usize, Result<T, RecvError>) {
+    assert!(!receivers.is_empty(), "select_recv on empty receivers");
+
+    let try_all = || {
+        receivers
+            .iter()
+            .enumerate()
+            .find_map(|(i, rx)| match rx.try_recv() {
+                Err(TryRecvError::Empty) => None,
+                data => Some((i, data.map_err(|_| RecvError))),
+            })
+    };
+
+    loop {
+        if let Some(ret) = try_all() {
+            return ret;
+        }
+
+        let _reason = park_reason::record(ParkReason::ChannelRecv);
+        let cur = Blocker::current();
+        // register the waiter to all the receivers
+        for rx in receivers {
+            rx.inner.to_wake.swap(cur.clone(), Ordering::Release);
+        }
+        // re-check after register the waiter
+        let ret = try_all();
+        let park = match ret {
+            Some(_) => Ok(()),
+            None => cur.park(None),
+        };
+
+        // deregister the waiter from all the receivers
+        for rx in receivers {
+            rx.inner.to_wake.take(Ordering::Acquire);
+        }
+
+        if let Some(ret) = ret {
+            return ret;
+        }
+        if park == Err(ParkError::Canceled) {
+            trigger_cancel_panic();
+        }
+    }
+}
+
 pub struct Iter<'a, T: 'a> {
     rx: &'a Receiver<T>,
 }
@@ -143,6 +233,16 @@ unsafe impl<T: Send> Send for Sender<T> {}
 impl<T: Send> UnwindSafe for Sender<T> {}
 impl<T: Send> RefUnwindSafe for Sender<T> {}
 
+/// A sender that doesn't keep the channel alive
+///
+/// it's created by `Sender::downgrade`, the receiver would see the channel
+/// disconnected when only weak senders are left
+pub struct WeakSender<T> {
+    inner: Weak<InnerQueue<T>>,
+}
+
+unsafe impl<T: Send> Send for WeakSender<T> {}
+
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
     let a = Arc::new(InnerQueue::new());
     (Sender::new(a.clone()), Receiver::new(a))
@@ -160,6 +260,37 @@ impl<T> Sender<T> {
     pub fn send(&self, t: T) -> Result<(), SendError<T>> {
         self.inner.send(t).map_err(SendError)
     }
+
+    /// reserve capacity for at least `additional` more messages
+    ///
+    /// this is a performance hint for bursty producers, the next sends would
+    /// use the pre-allocated storage instead of allocating one by one. the
+    /// channel would still grow beyond the reservation as usual
+    pub fn reserve(&self, additional: usize) {
+        self.inner.queue.reserve(additional);
+    }
+
+    /// return a snapshot of the channel counters
+    #[cfg(feature = "chan_stats")]
+    pub fn stats(&self) -> ChanStats {
+        self.inner.stats.snapshot()
+    }
+
+    /// close the channel while the senders are still alive
+    ///
+    /// the parked receiver is woken up immediately, it would still get the
+    /// values that are already sent and then `Disconnected`. the following
+    /// sends from any sender would fail
+    pub fn close(&self) {
+        self.inner.close();
+    }
+
+    /// create a weak sender that doesn't keep the channel alive
+    pub fn downgrade(&self) -> WeakSender<T> {
+        WeakSender {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
 }
 
 impl<T> Clone for Sender<T> {
@@ -181,6 +312,37 @@ impl<T> fmt::Debug for Sender<T> {
     }
 }
 
+/// /////////////////////////////////////////////////////////////////////////////
+/// WeakSender
+/// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> WeakSender<T> {
+    /// get a `Sender` back if there is still any live sender for the channel
+    /// return `None` if all the senders are dropped
+    pub fn upgrade(&self) -> Option<Sender<T>> {
+        let inner = self.inner.upgrade()?;
+        if inner.upgrade_chan() {
+            Some(Sender::new(inner))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Clone for WeakSender<T> {
+    fn clone(&self) -> WeakSender<T> {
+        WeakSender {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for WeakSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WeakSender {{ .. }}")
+    }
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// Receiver
 /// /////////////////////////////////////////////////////////////////////////////
@@ -194,6 +356,12 @@ impl<T> Receiver<T> {
         self.inner.try_recv()
     }
 
+    /// return a snapshot of the channel counters
+    #[cfg(feature = "chan_stats")]
+    pub fn stats(&self) -> ChanStats {
+        self.inner.stats.snapshot()
+    }
+
     pub fn recv(&self) -> Result<T, RecvError> {
         loop {
             match self.inner.recv(None) {
@@ -209,14 +377,25 @@ impl<T> Receiver<T> {
         match self.try_recv() {
             Ok(result) => Ok(result),
             Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
-            Err(TryRecvError::Empty) => self.recv_max_until(timeout),
+            Err(TryRecvError::Empty) => match deadline_after(timeout) {
+                Some(deadline) => self.recv_max_until(deadline),
+                None => self.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            },
+        }
+    }
+
+    /// same as `recv_timeout` except that with a deadline
+    pub fn recv_deadline(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
+        match self.try_recv() {
+            Ok(result) => Ok(result),
+            Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
+            Err(TryRecvError::Empty) => self.recv_max_until(deadline),
         }
     }
 
-    fn recv_max_until(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
-        let deadline = Instant::now() + timeout;
+    fn recv_max_until(&self, deadline: Instant) -> Result<T, RecvTimeoutError> {
         loop {
-            match self.inner.recv(Some(timeout)) {
+            match self.inner.recv(Some(deadline)) {
                 Ok(t) => return Ok(t),
                 Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
                 Err(TryRecvError::Empty) => {}
@@ -759,6 +938,45 @@ mod tests {
         assert_eq!(recv_count, stress);
     }
 
+    #[test]
+    fn recv_deadline() {
+        let (tx, rx) = channel::<i32>();
+        let past = Instant::now() - Duration::from_millis(10);
+        assert_eq!(rx.recv_deadline(past), Err(RecvTimeoutError::Timeout));
+
+        let deadline = Instant::now() + Duration::from_millis(20);
+        assert_eq!(rx.recv_deadline(deadline), Err(RecvTimeoutError::Timeout));
+        assert!(Instant::now() >= deadline);
+
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv_deadline(past), Ok(1));
+        drop(tx);
+        assert_eq!(rx.recv_deadline(past), Err(RecvTimeoutError::Disconnected));
+    }
+
+    #[test]
+    fn recv_timeout_retry_coroutine() {
+        let (tx, rx) = channel::<i32>();
+        let sender = go!(move || {
+            crate::coroutine::sleep(Duration::from_millis(100));
+            tx.send(1).unwrap();
+        });
+        go!(move || {
+            let timeout = Duration::from_millis(20);
+            assert_eq!(rx.recv_timeout(timeout), Err(RecvTimeoutError::Timeout));
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+            assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(1));
+            // all the senders are dropped
+            assert_eq!(
+                rx.recv_timeout(Duration::from_secs(5)),
+                Err(RecvTimeoutError::Disconnected)
+            );
+        })
+        .join()
+        .unwrap();
+        sender.join().unwrap();
+    }
+
     #[test]
     fn recv_timeout_upgrade() {
         let (tx, rx) = channel::<()>();
@@ -974,6 +1192,186 @@ mod tests {
         assert_eq!(rx1.try_recv(), Err(TryRecvError::Disconnected));
     }
 
+    #[test]
+    fn weak_sender() {
+        let (tx, rx) = channel::<i32>();
+        let weak = tx.downgrade();
+        weak.upgrade().unwrap().send(1).unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+
+        // only weak senders left, the channel is closed
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        assert!(weak.upgrade().is_none());
+        assert!(weak.clone().upgrade().is_none());
+    }
+
+    #[test]
+    fn weak_sender_wakeup_receiver() {
+        let (tx, rx) = channel::<i32>();
+        let weak = tx.downgrade();
+        let h = go!(move || rx.recv());
+        thread::sleep(Duration::from_millis(10));
+        drop(tx);
+        assert_eq!(h.join().unwrap(), Err(RecvError));
+        drop(weak);
+    }
+
+    #[test]
+    fn weak_sender_rx_gone() {
+        let (tx, rx) = channel::<i32>();
+        let weak = tx.downgrade();
+        drop(rx);
+        let tx1 = weak.upgrade().unwrap();
+        assert!(tx1.send(1).is_err());
+    }
+
+    #[test]
+    fn reserve() {
+        let (tx, rx) = channel::<i32>();
+        tx.reserve(10);
+        for i in 0..20 {
+            tx.send(i).unwrap();
+        }
+        for i in 0..20 {
+            assert_eq!(rx.recv().unwrap(), i);
+        }
+        // the unused reservation is released with the channel
+        tx.reserve(10);
+    }
+
+    #[test]
+    fn single_producer_order() {
+        const N: usize = 10_000;
+        let (tx, rx) = channel();
+        // yield to let the coroutines migrate between workers
+        let producer = go!(move || {
+            for i in 0..N {
+                tx.send(i).unwrap();
+                if i % 16 == 0 {
+                    crate::coroutine::yield_now();
+                }
+            }
+        });
+        let consumer = go!(move || {
+            for i in 0..N {
+                assert_eq!(rx.recv().unwrap(), i);
+                if i % 7 == 0 {
+                    crate::coroutine::yield_now();
+                }
+            }
+            assert!(rx.recv().is_err());
+        });
+        producer.join().unwrap();
+        consumer.join().unwrap();
+    }
+
+    #[test]
+    fn multi_producer_order() {
+        const N: usize = 2_000;
+        let (tx, rx) = channel();
+        let producers: Vec<_> = (0..4)
+            .map(|p| {
+                let tx = tx.clone();
+                go!(move || {
+                    for i in 0..N {
+                        tx.send((p, i)).unwrap();
+                        crate::coroutine::yield_now();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        // each producer's values are in order
+        let mut next = [0; 4];
+        for (p, i) in rx.iter() {
+            assert_eq!(next[p], i);
+            next[p] += 1;
+        }
+        assert_eq!(next, [N; 4]);
+        for p in producers {
+            p.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn select_recv() {
+        let (txs, rxs): (Vec<_>, Vec<_>) = (0..4).map(|_| channel::<i32>()).unzip();
+        let tx = txs[2].clone();
+        let h = go!(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx.send(2).unwrap();
+        });
+        assert_eq!(super::select_recv(&rxs), (2, Ok(2)));
+        h.join().unwrap();
+
+        // the lowest ready index wins, the others are untouched
+        txs[3].send(3).unwrap();
+        txs[1].send(1).unwrap();
+        assert_eq!(super::select_recv(&rxs), (1, Ok(1)));
+        assert_eq!(super::select_recv(&rxs), (3, Ok(3)));
+
+        // disconnected receiver is ready with error
+        drop(txs);
+        assert_eq!(super::select_recv(&rxs), (0, Err(RecvError)));
+    }
+
+    #[cfg(feature = "chan_stats")]
+    #[test]
+    fn stats() {
+        use crate::sync::ChanStats;
+
+        let (tx, rx) = channel::<i32>();
+        let tx2 = tx.clone();
+        tx.send(1).unwrap();
+        tx2.send(2).unwrap();
+        tx.send(3).unwrap();
+        assert_eq!(rx.recv(), Ok(1));
+        let stats = ChanStats {
+            sent: 3,
+            received: 1,
+            dropped: 0,
+        };
+        assert_eq!(tx.stats(), stats);
+        assert_eq!(rx.stats(), stats);
+
+        // the pending values and the late sends are dropped
+        drop(rx);
+        assert!(tx2.send(4).is_err());
+        assert_eq!(
+            tx.stats(),
+            ChanStats {
+                sent: 3,
+                received: 1,
+                dropped: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn close_wakes_receiver() {
+        let (tx, rx) = channel::<i32>();
+        tx.send(1).unwrap();
+        let h = go!(move || {
+            assert_eq!(rx.recv(), Ok(1));
+            let start = Instant::now();
+            assert_eq!(rx.recv(), Err(RecvError));
+            start.elapsed()
+        });
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        tx.close();
+        let parked = h.join().unwrap();
+        assert!(parked >= Duration::from_millis(20));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // all the senders see the closed channel
+        let tx2 = tx.clone();
+        assert!(tx.send(2).is_err());
+        assert!(tx2.send(2).is_err());
+    }
+
     // This bug used to end up in a livelock inside of the Receiver destructor
     // because the internal state of the Shared packet was corrupted
     #[test]
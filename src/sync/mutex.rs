@@ -12,6 +12,7 @@ use super::blocking::SyncBlocker;
 use super::poison;
 use crate::cancel::trigger_cancel_panic;
 use crate::park::ParkError;
+use crate::park_reason::{self, ParkReason};
 use may_queue::mpsc_list::Queue as WaitList;
 
 pub struct Mutex<T: ?Sized> {
@@ -58,6 +59,7 @@ impl<T: ?Sized> Mutex<T> {
             Err(TryLockError::Poisoned(e)) => return Err(e),
         }
 
+        let _reason = park_reason::record(ParkReason::MutexLock);
         let cur = SyncBlocker::current();
         // register blocker first
         self.to_wake.push(cur.clone());
@@ -147,6 +149,10 @@ impl<T: ?Sized> Mutex<T> {
         self.poison.get()
     }
 
+    /// Consumes this mutex, returning the underlying data.
+    ///
+    /// An error is returned if the mutex is poisoned, the data is still
+    /// accessible from the error.
     pub fn into_inner(self) -> LockResult<T>
     where
         T: Sized,
@@ -155,6 +161,10 @@ impl<T: ?Sized> Mutex<T> {
         poison::map_result(self.poison.borrow(), |_| data)
     }
 
+    /// Returns a mutable reference to the underlying data without locking.
+    ///
+    /// An error is returned if the mutex is poisoned, the data is still
+    /// accessible from the error.
     pub fn get_mut(&mut self) -> LockResult<&mut T> {
         // We know statically that there are no other references to `self`, so
         // there's no need to lock the inner lock.
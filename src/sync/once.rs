@@ -0,0 +1,185 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::blocking::park_until;
+use crate::cancel::trigger_cancel_panic;
+use crate::park::ParkError;
+use crate::sync::Blocker;
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+const POISONED: u8 = 3;
+
+/// Once primitive
+///
+/// compatible with `std::sync::Once` except for both thread and coroutine.
+/// the callers that lose the race are parked until the winner completes,
+/// a coroutine never blocks the worker thread. `Once::new` is a `const fn`,
+/// so it can be used in a `static`.
+///
+/// # Examples
+///
+/// ```rust
+/// use may::sync::Once;
+///
+/// static INIT: Once = Once::new();
+///
+/// let mut cnt = 0;
+/// for _ in 0..3 {
+///     INIT.call_once(|| cnt += 1);
+/// }
+/// assert_eq!(cnt, 1);
+/// assert!(INIT.is_completed());
+/// ```
+pub struct Once {
+    state: AtomicU8,
+    // the losers parked on the running closure, the lock is only held to
+    // register or wake them up
+    waiters: Mutex<Vec<Arc<Blocker>>>,
+}
+
+// set the state when the closure returns or panics
+struct Finish<'a> {
+    once: &'a Once,
+    state: u8,
+}
+
+impl<'a> Drop for Finish<'a> {
+    fn drop(&mut self) {
+        self.once.state.store(self.state, Ordering::Release);
+        // a waiter registers after checking the state under the lock,
+        // so it either sees the new state or is woken up here
+        let waiters = std::mem::take(&mut *self.once.waiters.lock().unwrap());
+        for w in waiters {
+            w.unpark();
+        }
+    }
+}
+
+impl Once {
+    /// create a new `Once` value
+    pub const fn new() -> Once {
+        Once {
+            state: AtomicU8::new(INCOMPLETE),
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// run the closure if it's the first call
+    ///
+    /// the other callers are blocked until the closure completes. if the
+    /// closure panics, the `Once` is poisoned and all the following calls
+    /// panic as well
+    pub fn call_once<F: FnOnce()>(&self, f: F) {
+        loop {
+            match self.state.compare_exchange(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(COMPLETE) => return,
+                Err(POISONED) => panic!("Once instance has previously been poisoned"),
+                Err(_) => self.wait(),
+            }
+        }
+
+        let mut finish = Finish {
+            once: self,
+            state: POISONED,
+        };
+        f();
+        finish.state = COMPLETE;
+    }
+
+    // park until the running closure finishes
+    fn wait(&self) {
+        let cur = Blocker::current();
+        let ret = park_until(&cur, None, |cur| {
+            let mut waiters = self.waiters.lock().unwrap();
+            if self.state.load(Ordering::Acquire) != RUNNING {
+                return false;
+            }
+            waiters.push(cur.clone());
+            true
+        });
+        if let Err(ParkError::Canceled) = ret {
+            // deregister before unwinding
+            self.waiters
+                .lock()
+                .unwrap()
+                .retain(|w| !Arc::ptr_eq(w, &cur));
+            trigger_cancel_panic();
+        }
+    }
+
+    /// return true if a `call_once` has completed successfully
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Once::new()
+    }
+}
+
+impl fmt::Debug for Once {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Once").finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn call_once_race() {
+        let once = Arc::new(Once::new());
+        let cnt = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..100)
+            .map(|_| {
+                let once = once.clone();
+                let cnt = cnt.clone();
+                go!(move || {
+                    once.call_once(|| {
+                        crate::coroutine::sleep(Duration::from_millis(10));
+                        cnt.fetch_add(1, Ordering::SeqCst);
+                    });
+                    // the losers see the result of the winner
+                    assert_eq!(cnt.load(Ordering::SeqCst), 1);
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(cnt.load(Ordering::SeqCst), 1);
+        assert!(once.is_completed());
+    }
+
+    #[test]
+    fn poison() {
+        let once = Once::new();
+        let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            once.call_once(|| panic!("init failed"));
+        }));
+        assert!(ret.is_err());
+        assert!(!once.is_completed());
+
+        // the following calls panic as well
+        let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            once.call_once(|| {});
+        }));
+        assert!(ret.is_err());
+    }
+}
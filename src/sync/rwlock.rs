@@ -2,9 +2,11 @@
 //! please ref the doc from std::sync::rwlock
 use std::cell::UnsafeCell;
 use std::fmt;
+use std::mem::{self, ManuallyDrop};
 use std::ops::{Deref, DerefMut};
 use std::panic::{RefUnwindSafe, UnwindSafe};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
 
@@ -13,8 +15,9 @@ use crate::park::ParkError;
 use may_queue::mpsc_list::Queue as WaitList;
 
 use super::blocking::SyncBlocker;
-use super::mutex::{self, Mutex};
+use super::mutex::{self, Mutex, MutexGuard};
 use super::poison;
+use super::Condvar;
 
 /// A reader-writer lock
 ///
@@ -30,6 +33,13 @@ pub struct RwLock<T: ?Sized> {
     // the reader mutex that track the reader count
     rlock: Mutex<usize>,
 
+    // only one upgradable reader at a time
+    ulock: Mutex<()>,
+    // the upgrading reader waits on it for the other readers to drain
+    drained: Condvar,
+    // if there is an upgrading reader, only accessed with the rlock held
+    upgrading: AtomicBool,
+
     poison: poison::Flag,
     data: UnsafeCell<T>,
 }
@@ -54,12 +64,25 @@ pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
 
 // impl<'a, T: ?Sized> !marker::Send for RwLockWriteGuard<'a, T> {}
 
+/// a read guard that could be upgraded to a write guard
+///
+/// it coexists with the regular readers, but there is at most one
+/// upgradable reader at a time
+#[must_use]
+pub struct RwLockUpgradableReadGuard<'a, T: ?Sized + 'a> {
+    __lock: &'a RwLock<T>,
+    __upgrade: MutexGuard<'a, ()>,
+}
+
 impl<T> RwLock<T> {
     pub fn new(t: T) -> RwLock<T> {
         RwLock {
             to_wake: WaitList::new(),
             cnt: AtomicUsize::new(0),
             rlock: Mutex::new(0),
+            ulock: Mutex::new(()),
+            drained: Condvar::new(),
+            upgrading: AtomicBool::new(false),
             poison: poison::Flag::new(),
             data: UnsafeCell::new(t),
         }
@@ -70,10 +93,8 @@ impl<T: ?Sized> RwLock<T> {
     // global mutex lock without return a guard
     fn lock(&self) -> Result<(), ParkError> {
         // try lock first
-        match self.try_lock() {
-            Ok(_) => return Ok(()),
-            Err(TryLockError::WouldBlock) => {}
-            Err(TryLockError::Poisoned(_)) => return Err(ParkError::Timeout),
+        if self.try_lock() {
+            return Ok(());
         }
 
         let cur = SyncBlocker::current();
@@ -106,24 +127,13 @@ impl<T: ?Sized> RwLock<T> {
         }
     }
 
-    fn try_lock(&self) -> TryLockResult<()> {
-        if self.cnt.load(Ordering::SeqCst) == 0 {
-            match self
+    // global mutex try lock, the poison is checked by the guards
+    fn try_lock(&self) -> bool {
+        self.cnt.load(Ordering::SeqCst) == 0
+            && self
                 .cnt
                 .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
-            {
-                Ok(_) => Ok(()),
-                Err(_) => {
-                    if self.poison.get() {
-                        Err(TryLockError::Poisoned(PoisonError::new(())))
-                    } else {
-                        Err(TryLockError::WouldBlock)
-                    }
-                }
-            }
-        } else {
-            Err(TryLockError::WouldBlock)
-        }
+                .is_ok()
     }
 
     fn unlock(&self) {
@@ -170,16 +180,14 @@ impl<T: ?Sized> RwLock<T> {
             Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
         };
 
-        if *r == 0 {
-            if let Err(TryLockError::WouldBlock) = self.try_lock() {
-                return Err(TryLockError::WouldBlock);
-            }
+        if *r == 0 && !self.try_lock() {
+            return Err(TryLockError::WouldBlock);
         }
 
-        let g = RwLockReadGuard::new(self)?;
-        // finally we add rlock
+        // the poisoned guard also holds the read lock
         *r += 1;
-        Ok(g)
+        drop(r);
+        Ok(RwLockReadGuard::new(self)?)
     }
 
     fn read_unlock(&self) {
@@ -187,6 +195,33 @@ impl<T: ?Sized> RwLock<T> {
         *r -= 1;
         if *r == 0 {
             self.unlock();
+        } else if *r == 1 && self.upgrading.load(Ordering::Relaxed) {
+            // only the upgrading reader is left
+            self.drained.notify_one();
+        }
+    }
+
+    /// lock with shared read access that could be upgraded later
+    ///
+    /// it blocks until the other upgradable reader is released, the
+    /// regular readers are not blocked by it
+    pub fn upgradable_read(&self) -> LockResult<RwLockUpgradableReadGuard<'_, T>> {
+        // the ulock protects nothing, ignore the poison
+        let upgrade = self.ulock.lock().unwrap_or_else(|e| e.into_inner());
+        let (read, poisoned) = match self.read() {
+            Ok(g) => (g, false),
+            Err(e) => (e.into_inner(), true),
+        };
+        // the read lock is now owned by the upgradable guard
+        mem::forget(read);
+        let guard = RwLockUpgradableReadGuard {
+            __lock: self,
+            __upgrade: upgrade,
+        };
+        if poisoned {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
         }
     }
 
@@ -199,7 +234,7 @@ impl<T: ?Sized> RwLock<T> {
     }
 
     pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<T>> {
-        if let Err(TryLockError::WouldBlock) = self.try_lock() {
+        if !self.try_lock() {
             return Err(TryLockError::WouldBlock);
         }
         Ok(RwLockWriteGuard::new(self)?)
@@ -273,6 +308,38 @@ impl<'a, T: fmt::Debug> fmt::Debug for RwLockReadGuard<'a, T> {
     }
 }
 
+impl<'rwlock, T: ?Sized> RwLockUpgradableReadGuard<'rwlock, T> {
+    /// upgrade to the write guard
+    ///
+    /// it blocks until all the other readers are released, the new readers
+    /// could still get in while waiting
+    pub fn upgrade(self) -> RwLockWriteGuard<'rwlock, T> {
+        let lock = self.__lock;
+        let mut r = lock.rlock.lock().expect("rwlock upgrade");
+        lock.upgrading.store(true, Ordering::Relaxed);
+        let ret = lock.drained.wait_while(r, |n| *n > 1);
+        r = ret.expect("rwlock upgrade");
+        lock.upgrading.store(false, Ordering::Relaxed);
+        // take over the global lock from the readers without releasing it
+        *r = 0;
+        drop(r);
+
+        // release the upgradable lock without the read unlock
+        let this = ManuallyDrop::new(self);
+        drop(unsafe { ptr::read(&this.__upgrade) });
+        // the poison is already reported by `upgradable_read`
+        RwLockWriteGuard::new(lock).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for RwLockUpgradableReadGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RwLockUpgradableReadGuard")
+            .field("lock", &self.__lock)
+            .finish()
+    }
+}
+
 impl<'a, T: fmt::Debug> fmt::Debug for RwLockWriteGuard<'a, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("RwLockWriteGuard")
@@ -289,6 +356,14 @@ impl<'rwlock, T: ?Sized> Deref for RwLockReadGuard<'rwlock, T> {
     }
 }
 
+impl<'rwlock, T: ?Sized> Deref for RwLockUpgradableReadGuard<'rwlock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.__lock.data.get() }
+    }
+}
+
 impl<'rwlock, T: ?Sized> Deref for RwLockWriteGuard<'rwlock, T> {
     type Target = T;
 
@@ -309,6 +384,12 @@ impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
     }
 }
 
+impl<'a, T: ?Sized> Drop for RwLockUpgradableReadGuard<'a, T> {
+    fn drop(&mut self) {
+        self.__lock.read_unlock();
+    }
+}
+
 impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
     fn drop(&mut self) {
         self.__lock.poison.done(&self.__poison);
@@ -323,6 +404,7 @@ mod tests {
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::{Arc, TryLockError};
     use std::thread;
+    use std::time::Duration;
 
     #[derive(Eq, PartialEq, Debug)]
     struct NonCopy(i32);
@@ -508,6 +590,109 @@ mod tests {
         drop(read_guard);
     }
 
+    #[test]
+    fn test_rwlock_try_read_write_locked() {
+        let lock = Arc::new(RwLock::new(0));
+        let (tx, rx) = channel();
+        let (done_tx, done_rx) = channel();
+        let l = lock.clone();
+        let writer = go!(move || {
+            let _w = l.write().unwrap();
+            tx.send(()).unwrap();
+            done_rx.recv().unwrap();
+        });
+        rx.recv().unwrap();
+
+        let l = lock.clone();
+        go!(move || {
+            assert!(matches!(l.try_read(), Err(TryLockError::WouldBlock)));
+            assert!(matches!(l.try_write(), Err(TryLockError::WouldBlock)));
+        })
+        .join()
+        .unwrap();
+        done_tx.send(()).unwrap();
+        writer.join().unwrap();
+
+        let r = lock.try_read().unwrap();
+        assert!(lock.try_read().is_ok());
+        assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+        drop(r);
+        assert!(lock.try_write().is_ok());
+    }
+
+    #[test]
+    fn test_rwlock_try_poisoned() {
+        let arc = Arc::new(RwLock::new(1));
+        let arc2 = arc.clone();
+        let _: Result<(), _> = thread::spawn(move || {
+            let _lock = arc2.write().unwrap();
+            panic!();
+        })
+        .join();
+
+        match arc.try_read() {
+            Err(TryLockError::Poisoned(e)) => assert_eq!(**e.get_ref(), 1),
+            _ => panic!("try_read of poisoned RwLock is not Poisoned"),
+        }
+        match arc.try_write() {
+            Err(TryLockError::Poisoned(e)) => assert_eq!(**e.get_ref(), 1),
+            _ => panic!("try_write of poisoned RwLock is not Poisoned"),
+        }
+        // the poisoned guards release the lock on drop
+        assert!(arc.write().is_err());
+        assert!(arc.read().is_err());
+    }
+
+    #[test]
+    fn test_rwlock_upgradable_read() {
+        let lock = Arc::new(RwLock::new(0));
+        let held = Arc::new(AtomicUsize::new(0));
+
+        let u = lock.upgradable_read().unwrap();
+        // the regular readers are not blocked
+        drop(lock.read().unwrap());
+
+        // the other upgradable reader waits
+        let h = {
+            let lock = lock.clone();
+            let held = held.clone();
+            go!(move || {
+                let u = lock.upgradable_read().unwrap();
+                held.fetch_add(1, Ordering::SeqCst);
+                let mut w = u.upgrade();
+                *w += 1;
+            })
+        };
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(held.load(Ordering::SeqCst), 0);
+
+        // the upgrade waits for the other readers
+        let released = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = channel();
+        let reader = {
+            let lock = lock.clone();
+            let released = released.clone();
+            go!(move || {
+                let r = lock.read().unwrap();
+                tx.send(()).unwrap();
+                crate::coroutine::sleep(Duration::from_millis(20));
+                released.store(1, Ordering::SeqCst);
+                drop(r);
+            })
+        };
+        rx.recv().unwrap();
+        let mut w = u.upgrade();
+        assert_eq!(released.load(Ordering::SeqCst), 1);
+        *w += 1;
+        drop(w);
+        reader.join().unwrap();
+
+        h.join().unwrap();
+        assert_eq!(held.load(Ordering::SeqCst), 1);
+        assert_eq!(*lock.read().unwrap(), 2);
+        assert!(lock.try_write().is_ok());
+    }
+
     #[test]
     fn test_into_inner() {
         let m = RwLock::new(NonCopy(10));
@@ -1,9 +1,9 @@
 use std::fmt;
 use std::sync::atomic::{AtomicIsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use super::blocking::SyncBlocker;
+use super::blocking::{deadline_after, park_until, SyncBlocker};
 use crate::cancel::trigger_cancel_panic;
 use crate::park::ParkError;
 use crossbeam::queue::SegQueue as WaitList;
@@ -71,22 +71,40 @@ impl Semphore {
     }
 
     // return false if timeout
-    fn wait_timeout_impl(&self, dur: Option<Duration>) -> bool {
+    fn wait_until(&self, deadline: Option<Instant>) -> bool {
+        match self.wait_inner(deadline) {
+            Ok(_) => true,
+            // now we can safely go with the cancel panic
+            Err(ParkError::Canceled) => trigger_cancel_panic(),
+            Err(ParkError::Timeout) => false,
+        }
+    }
+
+    // same as `wait` but return false instead of the cancel panic
+    // so the caller could give back what it holds before the panic
+    pub(crate) fn wait_or_cancel(&self) -> bool {
+        self.wait_inner(None).is_ok()
+    }
+
+    fn wait_inner(&self, deadline: Option<Instant>) -> Result<(), ParkError> {
         // try wait first
         if self.try_wait() {
-            return true;
+            return Ok(());
         }
 
         let cur = SyncBlocker::current();
-        // register blocker first
-        self.to_wake.push(cur.clone());
-        // dec the cnt, if it's positive, unpark one waiter
-        if self.cnt.fetch_sub(1, Ordering::SeqCst) > 0 {
-            self.wakeup_one();
-        }
+        let ret = park_until(&cur, deadline, |cur| {
+            // register blocker first
+            self.to_wake.push(cur.clone());
+            // dec the cnt, if it's positive, unpark one waiter
+            if self.cnt.fetch_sub(1, Ordering::SeqCst) > 0 {
+                self.wakeup_one();
+            }
+            true
+        });
 
-        match cur.park(dur) {
-            Ok(_) => true,
+        match ret {
+            Ok(_) => Ok(()),
             Err(err) => {
                 // check the unpark status
                 if cur.is_unparked() {
@@ -100,11 +118,7 @@ impl Semphore {
                     }
                 }
 
-                // now we can safely go with the cancel panic
-                if err == ParkError::Canceled {
-                    trigger_cancel_panic();
-                }
-                false
+                Err(err)
             }
         }
     }
@@ -113,13 +127,19 @@ impl Semphore {
     /// if the semphore value is bigger than zero the function returns immediately
     /// otherwise it would block the until a `post` is executed
     pub fn wait(&self) {
-        self.wait_timeout_impl(None);
+        self.wait_until(None);
     }
 
     /// same as `wait` except that with an extra timeout value
     /// return false if timeout happened
     pub fn wait_timeout(&self, dur: Duration) -> bool {
-        self.wait_timeout_impl(Some(dur))
+        self.wait_until(deadline_after(dur))
+    }
+
+    /// same as `wait` except that with a deadline
+    /// return false if the deadline is reached
+    pub fn wait_deadline(&self, deadline: Instant) -> bool {
+        self.wait_until(Some(deadline))
     }
 
     /// return false if would block
@@ -140,6 +160,21 @@ impl Semphore {
         false
     }
 
+    /// same as `try_wait`, acquire one resource without blocking
+    #[inline]
+    pub fn try_acquire(&self) -> bool {
+        self.try_wait()
+    }
+
+    /// same as `wait_timeout`, acquire one resource within the duration
+    ///
+    /// a timed out waiter passes the resource it's woken up with to the
+    /// other waiters, so it never steals a later `post`
+    #[inline]
+    pub fn acquire_timeout(&self, dur: Duration) -> bool {
+        self.wait_timeout(dur)
+    }
+
     /// increment the semphore value
     /// and would wakeup a thread/coroutine that is calling `wait`
     pub fn post(&self) {
@@ -313,4 +348,26 @@ mod tests {
         sem1.post();
         h2.join().unwrap();
     }
+
+    #[test]
+    fn test_semphore_acquire_timeout() {
+        let sem = Semphore::new(0);
+        assert!(!sem.try_acquire());
+        assert!(!sem.acquire_timeout(Duration::from_millis(10)));
+        assert!(!sem.try_acquire());
+
+        // the timed out waiter doesn't steal the release
+        sem.post();
+        assert_eq!(sem.get_value(), 1);
+        assert!(sem.try_acquire());
+        assert!(!sem.try_acquire());
+
+        let sem = Arc::new(sem);
+        let sem2 = sem.clone();
+        let h = go!(move || sem2.acquire_timeout(Duration::from_secs(5)));
+        thread::sleep(Duration::from_millis(10));
+        sem.post();
+        assert!(h.join().unwrap());
+        assert_eq!(sem.get_value(), 0);
+    }
 }
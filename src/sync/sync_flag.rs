@@ -1,9 +1,9 @@
 use std::fmt;
 use std::sync::atomic::{AtomicIsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use super::blocking::SyncBlocker;
+use super::blocking::{deadline_after, park_until, SyncBlocker};
 use crate::cancel::trigger_cancel_panic;
 use crate::park::ParkError;
 use crossbeam::queue::SegQueue;
@@ -76,21 +76,24 @@ impl SyncFlag {
     }
 
     // return false if timeout
-    fn wait_timeout_impl(&self, dur: Option<Duration>) -> bool {
+    fn wait_until(&self, deadline: Option<Instant>) -> bool {
         // try wait first
         if self.is_fired() {
             return true;
         }
 
         let cur = SyncBlocker::current();
-        // register blocker first
-        self.to_wake.push(cur.clone());
-        // dec the cnt, if it's positive, unpark one waiter
-        if self.cnt.fetch_sub(1, Ordering::SeqCst) > 0 {
-            self.wakeup_all();
-        }
+        let ret = park_until(&cur, deadline, |cur| {
+            // register blocker first
+            self.to_wake.push(cur.clone());
+            // dec the cnt, if it's positive, unpark one waiter
+            if self.cnt.fetch_sub(1, Ordering::SeqCst) > 0 {
+                self.wakeup_all();
+            }
+            true
+        });
 
-        match cur.park(dur) {
+        match ret {
             Ok(_) => true,
             Err(err) => {
                 // check the unpark status
@@ -118,13 +121,19 @@ impl SyncFlag {
     /// if the SyncFlag value is bigger than zero the function returns immediately
     /// otherwise it would block the until a `fire` is executed
     pub fn wait(&self) {
-        self.wait_timeout_impl(None);
+        self.wait_until(None);
     }
 
     /// same as `wait` except that with an extra timeout value
     /// return false if timeout happened
     pub fn wait_timeout(&self, dur: Duration) -> bool {
-        self.wait_timeout_impl(Some(dur))
+        self.wait_until(deadline_after(dur))
+    }
+
+    /// same as `wait` except that with a deadline
+    /// return false if the deadline is reached
+    pub fn wait_deadline(&self, deadline: Instant) -> bool {
+        self.wait_until(Some(deadline))
     }
 
     /// set the SyncFlag to true
@@ -0,0 +1,206 @@
+use std::fmt;
+use std::sync::Arc;
+
+use super::{Condvar, Mutex};
+
+// the pending members of the group
+#[derive(Default)]
+struct Count {
+    // the live handles, including the original one
+    handles: usize,
+    // the members added by `add` that are not done yet
+    added: usize,
+}
+
+impl Count {
+    fn pending(&self) -> usize {
+        self.handles + self.added
+    }
+}
+
+struct Inner {
+    cnt: Mutex<Count>,
+    cvar: Condvar,
+}
+
+/// WaitGroup primitive
+///
+/// A WaitGroup waits for a group of threads or coroutines to finish.
+/// like crossbeam's `WaitGroup`, each handle is a member of the group,
+/// a clone joins the group and leaves it when dropped. `wait` consumes
+/// the handle and blocks until all the other members are finished.
+///
+/// the members can also be added by `add` without a handle, each of them
+/// must be finished by calling `done`.
+///
+/// # Examples
+///
+/// ```rust
+/// use may::sync::WaitGroup;
+///
+/// let wg = WaitGroup::new();
+/// for _ in 0..10 {
+///     let wg = wg.clone();
+///     may::go!(move || {
+///         // do some work
+///         drop(wg);
+///     });
+/// }
+/// // wait for all the coroutines to finish
+/// wg.wait();
+/// ```
+pub struct WaitGroup {
+    inner: Arc<Inner>,
+}
+
+impl Default for WaitGroup {
+    fn default() -> Self {
+        WaitGroup {
+            inner: Arc::new(Inner {
+                cnt: Mutex::new(Count {
+                    handles: 1,
+                    added: 0,
+                }),
+                cvar: Condvar::new(),
+            }),
+        }
+    }
+}
+
+impl WaitGroup {
+    /// create a WaitGroup, the handle is the only pending member
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// add `n` pending members that are finished by `done`
+    pub fn add(&self, n: usize) {
+        let mut cnt = self.inner.cnt.lock().unwrap();
+        cnt.added = cnt
+            .added
+            .checked_add(n)
+            .expect("WaitGroup counter overflow");
+    }
+
+    /// mark one member added by `add` as finished
+    ///
+    /// panic if it's called more times than the members are added
+    pub fn done(&self) {
+        let mut cnt = self.inner.cnt.lock().unwrap();
+        if cnt.added == 0 {
+            // release the lock before panic to not poison it
+            drop(cnt);
+            panic!("WaitGroup::done called more times than add");
+        }
+        cnt.added -= 1;
+        if cnt.pending() == 0 {
+            self.inner.cvar.notify_all();
+        }
+    }
+
+    /// drop the handle and block until all the other members are finished
+    pub fn wait(self) {
+        let inner = self.inner.clone();
+        drop(self);
+        let cnt = inner.cnt.lock().unwrap();
+        drop(inner.cvar.wait_while(cnt, |n| n.pending() > 0).unwrap());
+    }
+
+    /// return the number of the pending members, including the handles
+    pub fn count(&self) -> usize {
+        self.inner.cnt.lock().unwrap().pending()
+    }
+}
+
+impl Clone for WaitGroup {
+    /// the clone is a new pending member of the group
+    fn clone(&self) -> Self {
+        let mut cnt = self.inner.cnt.lock().unwrap();
+        cnt.handles += 1;
+        drop(cnt);
+        WaitGroup {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl Drop for WaitGroup {
+    /// the handle leaves the group
+    fn drop(&mut self) {
+        let mut cnt = self.inner.cnt.lock().unwrap();
+        cnt.handles -= 1;
+        if cnt.pending() == 0 {
+            self.inner.cvar.notify_all();
+        }
+    }
+}
+
+impl fmt::Debug for WaitGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WaitGroup")
+            .field("count", &self.count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn wait_all() {
+        let wg = WaitGroup::new();
+        assert_eq!(wg.count(), 1);
+        let finished = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100 {
+            let wg = wg.clone();
+            let finished = finished.clone();
+            go!(move || {
+                crate::coroutine::sleep(Duration::from_millis(10));
+                finished.fetch_add(1, Ordering::SeqCst);
+                drop(wg);
+            });
+        }
+        wg.wait();
+        assert_eq!(finished.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn add_done() {
+        let wg = WaitGroup::new();
+        wg.add(100);
+        for _ in 0..100 {
+            let wg = wg.clone();
+            go!(move || wg.done());
+        }
+        let wg1 = wg.clone();
+
+        // wait in a coroutine for the added members
+        wg.add(1);
+        let h = {
+            let wg = wg.clone();
+            go!(move || wg.wait())
+        };
+        thread::sleep(Duration::from_millis(10));
+        assert!(!h.is_done());
+        wg.done();
+        // the other handles are still pending
+        thread::sleep(Duration::from_millis(10));
+        assert!(!h.is_done());
+        drop(wg1);
+        drop(wg);
+        h.join().unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn done_too_many() {
+        let wg = WaitGroup::new();
+        wg.add(1);
+        wg.done();
+        wg.done();
+    }
+}
@@ -0,0 +1,60 @@
+//! helpers for testing code that runs in coroutines on the shared scheduler
+//!
+//! only available with the `test-util` feature
+use std::panic;
+
+use crate::coroutine::Builder;
+use crate::scheduler::get_scheduler;
+
+/// run the closure in a coroutine confined to the first `n` workers
+///
+/// the closure and all the coroutines spawned from it, directly or not,
+/// would only run on the first `n` workers of the global scheduler, e.g.
+/// `n = 1` makes them run one at a time on a single worker, only switching
+/// at the yield points.
+///
+/// this is not an isolated scheduler. there is no new scheduler instance,
+/// `n` is bounded by the process-wide worker number, and the coroutines
+/// that are not spawned from the closure, e.g. from the other tests, may
+/// run on the same workers. the coroutines spawned from plain threads
+/// inside the closure are not confined either.
+///
+/// block the current context until the closure returns, the panic of the
+/// closure is propagated
+///
+/// # Panics
+///
+/// panic if `n` is zero or greater than the configured workers
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use]
+/// extern crate may;
+///
+/// # fn main() {
+/// let v = may::test::with_workers(1, || {
+///     let h = go!(|| 1);
+///     h.join().unwrap() + 1
+/// });
+/// assert_eq!(v, 2);
+/// # }
+/// ```
+pub fn with_workers<F, T>(n: usize, f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let workers = get_scheduler().worker_count();
+    assert!(
+        n > 0 && n <= workers,
+        "with_workers({}) out of the {} workers",
+        n,
+        workers
+    );
+    let h = unsafe { Builder::new().worker_group(n).spawn(f) }.expect("failed to spawn coroutine");
+    match h.join() {
+        Ok(v) => v,
+        Err(e) => panic::resume_unwind(e),
+    }
+}
@@ -0,0 +1,270 @@
+//! Timer primitives
+//!
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cancel::trigger_cancel_panic;
+use crate::park::ParkError;
+use crate::sync::{park_until, AtomicOption, Blocker};
+
+/// A reusable timer that can be reset and canceled
+///
+/// one context waits on the timer by `wait`, while others can `reset` or
+/// `cancel` it. pushing the deadline later is cheap, it only updates the
+/// deadline and the waiter re-parks on the new one when the old expires.
+/// this is useful for idle timeout that is reset on every activity.
+///
+/// only one waiter is supported at a time
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use may::time::Timer;
+///
+/// let timer = Arc::new(Timer::new(Duration::from_millis(100)));
+/// let t = timer.clone();
+/// let h = may::go!(move || t.wait());
+/// timer.cancel();
+/// assert!(!h.join().unwrap());
+/// ```
+pub struct Timer {
+    // the deadline, None if canceled
+    deadline: Mutex<Option<Instant>>,
+    // the waiter that parks on the timer
+    to_wake: AtomicOption<Arc<Blocker>>,
+}
+
+impl Timer {
+    /// create a timer that fires after `dur`
+    pub fn new(dur: Duration) -> Self {
+        Timer {
+            deadline: Mutex::new(Some(Instant::now() + dur)),
+            to_wake: AtomicOption::none(),
+        }
+    }
+
+    /// return the deadline of the timer, None if canceled
+    pub fn deadline(&self) -> Option<Instant> {
+        *self.deadline.lock().unwrap()
+    }
+
+    /// re-arm the timer to fire after `dur` from now
+    ///
+    /// this also re-arms a canceled or fired timer
+    pub fn reset(&self, dur: Duration) {
+        let new = Instant::now() + dur;
+        let earlier = {
+            let mut deadline = self.deadline.lock().unwrap();
+            let earlier = !matches!(*deadline, Some(d) if d <= new);
+            *deadline = Some(new);
+            earlier
+        };
+        // the waiter only need to know an earlier deadline
+        if earlier {
+            self.wake();
+        }
+    }
+
+    /// cancel the timer, the waiter returns immediately
+    pub fn cancel(&self) {
+        *self.deadline.lock().unwrap() = None;
+        self.wake();
+    }
+
+    /// park until the timer fires or is canceled
+    ///
+    /// return true if fired, false if canceled
+    pub fn wait(&self) -> bool {
+        loop {
+            let deadline = match self.deadline() {
+                None => return false,
+                Some(d) => d,
+            };
+
+            let cur = Blocker::current();
+            let ret = park_until(&cur, Some(deadline), |cur| {
+                // register the waiter
+                self.to_wake.swap(cur.clone(), Ordering::Release);
+                // re-check the deadline after register, a wake up after this
+                // point would be recorded by the blocker
+                self.deadline() == Some(deadline)
+            });
+            // deregister the waiter
+            self.to_wake.take(Ordering::Acquire);
+
+            match ret {
+                // fired if the deadline is not changed in the meantime
+                Err(ParkError::Timeout) if self.deadline() == Some(deadline) => return true,
+                Err(ParkError::Canceled) => trigger_cancel_panic(),
+                _ => {}
+            }
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(w) = self.to_wake.take(Ordering::Acquire) {
+            w.unpark();
+        }
+    }
+}
+
+impl fmt::Debug for Timer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Timer")
+            .field("deadline", &self.deadline())
+            .finish()
+    }
+}
+
+/// A repeating timer that ticks at a fixed period
+///
+/// the ticks are aligned to the creation time, the time spent between the
+/// ticks doesn't accumulate. if the consumer falls behind, the missed ticks
+/// are collapsed into one that returns immediately, and the following tick
+/// is scheduled on the next future boundary.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::{Duration, Instant};
+/// use may::time::interval;
+///
+/// let start = Instant::now();
+/// let mut interval = interval(Duration::from_millis(10));
+/// for _ in 0..3 {
+///     interval.tick();
+/// }
+/// assert!(start.elapsed() >= Duration::from_millis(30));
+/// ```
+#[derive(Debug)]
+pub struct Interval {
+    period: Duration,
+    // the deadline of the next tick
+    next: Instant,
+}
+
+/// create an interval that first ticks after `period`
+///
+/// panic if the period is zero
+pub fn interval(period: Duration) -> Interval {
+    assert!(period > Duration::from_secs(0), "zero interval period");
+    Interval {
+        period,
+        next: Instant::now() + period,
+    }
+}
+
+impl Interval {
+    /// park until the next tick, return the scheduled time of the tick
+    pub fn tick(&mut self) -> Instant {
+        let deadline = self.next;
+        crate::coroutine::sleep_until(deadline);
+
+        let now = Instant::now();
+        self.next = deadline + self.period;
+        if self.next <= now {
+            // skip the missed ticks
+            let behind = (now - self.next).as_nanos() / self.period.as_nanos() + 1;
+            let behind = u32::try_from(behind).unwrap_or(u32::MAX);
+            self.next += self.period * behind;
+        }
+        deadline
+    }
+
+    /// the period of the interval
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn fire() {
+        let timer = Timer::new(Duration::from_millis(50));
+        let now = Instant::now();
+        assert!(timer.wait());
+        assert!(now.elapsed() >= Duration::from_millis(50));
+        // already fired
+        assert!(timer.wait());
+    }
+
+    #[test]
+    fn cancel() {
+        let timer = Arc::new(Timer::new(Duration::from_secs(10)));
+        let t = timer.clone();
+        let h = go!(move || t.wait());
+        thread::sleep(Duration::from_millis(20));
+        timer.cancel();
+        assert!(!h.join().unwrap());
+        assert_eq!(timer.deadline(), None);
+        assert!(!timer.wait());
+    }
+
+    #[test]
+    fn reset() {
+        let timer = Arc::new(Timer::new(Duration::from_millis(50)));
+        let t = timer.clone();
+        let now = Instant::now();
+        let h = go!(move || t.wait());
+        // keep pushing the deadline later
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(20));
+            timer.reset(Duration::from_millis(50));
+        }
+        assert!(h.join().unwrap());
+        assert!(now.elapsed() >= Duration::from_millis(150));
+
+        // an earlier deadline wakes up the waiter
+        timer.reset(Duration::from_secs(10));
+        let t = timer.clone();
+        let now = Instant::now();
+        let h = go!(move || t.wait());
+        thread::sleep(Duration::from_millis(20));
+        timer.reset(Duration::from_millis(10));
+        assert!(h.join().unwrap());
+        assert!(now.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn interval_no_drift() {
+        let period = Duration::from_millis(50);
+        let mut interval = interval(period);
+        let first = interval.tick();
+        for i in 1..5 {
+            // the work between the ticks doesn't delay the next one
+            thread::sleep(Duration::from_millis(10));
+            let tick = interval.tick();
+            assert_eq!(tick, first + period * i);
+            assert!(Instant::now() >= tick);
+        }
+    }
+
+    #[test]
+    fn interval_skip() {
+        let h = go!(|| {
+            let mut interval = interval(Duration::from_millis(50));
+            let first = interval.tick();
+            // fall behind for more than two periods
+            crate::coroutine::sleep(Duration::from_millis(120));
+            let now = Instant::now();
+            let late = interval.tick();
+            assert_eq!(late, first + Duration::from_millis(50));
+            assert!(now.elapsed() < Duration::from_millis(20));
+            // the next tick is on the next future boundary
+            let next = interval.tick();
+            assert_eq!(next, first + Duration::from_millis(150));
+            assert!(Instant::now() >= next);
+        });
+        h.join().unwrap();
+    }
+}
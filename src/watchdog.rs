@@ -0,0 +1,163 @@
+//! the watchdog that flags the coroutines living too long
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::coroutine_impl::{Coroutine, CoroutineId};
+
+// the scan interval is bounded to keep the overhead low
+const MIN_SCAN_INTERVAL: Duration = Duration::from_millis(50);
+const MAX_SCAN_INTERVAL: Duration = Duration::from_secs(10);
+// the registry is sharded by the coroutine id to spread the lock contention
+const SHARDS: usize = 64;
+
+/// what the watchdog does with a coroutine that lives too long
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogAction {
+    /// log the coroutine id, name, age and park reason as a warning
+    Log,
+    /// log and cancel the coroutine, see [`Coroutine::cancel`] for the caveats
+    Cancel,
+}
+
+struct Setting {
+    max_age: Duration,
+    action: WatchdogAction,
+}
+
+struct Entry {
+    co: Coroutine,
+    spawned: Instant,
+    // already handled by the watchdog
+    flagged: bool,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SETTING: AtomicPtr<Setting> = AtomicPtr::new(std::ptr::null_mut());
+// the live coroutines spawned after the watchdog is enabled
+static LIVE: [Mutex<Option<HashMap<CoroutineId, Entry>>>; SHARDS] =
+    [const { Mutex::new(None) }; SHARDS];
+
+type Shard = std::sync::MutexGuard<'static, Option<HashMap<CoroutineId, Entry>>>;
+
+// lock the shard of the coroutine
+fn shard(id: CoroutineId) -> Shard {
+    lock(&LIVE[id.as_u64() as usize % SHARDS])
+}
+
+fn lock(shard: &'static Mutex<Option<HashMap<CoroutineId, Entry>>>) -> Shard {
+    shard.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn get_setting() -> &'static Setting {
+    unsafe { &*SETTING.load(Ordering::Acquire) }
+}
+
+// enable the watchdog or replace its setting
+pub(crate) fn set_watchdog(max_age: Duration, action: WatchdogAction) {
+    let setting = Box::new(Setting { max_age, action });
+    // the old one may still be used by the watchdog, just leak it
+    SETTING.swap(Box::into_raw(setting), Ordering::AcqRel);
+    ENABLED.store(true, Ordering::Release);
+
+    static START: Once = Once::new();
+    START.call_once(|| {
+        thread::Builder::new()
+            .name("may-watchdog".into())
+            .spawn(run)
+            .expect("failed to spawn watchdog thread");
+    });
+}
+
+// register the newly spawned coroutine
+#[inline]
+pub(crate) fn register(co: &Coroutine) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let entry = Entry {
+        co: co.clone(),
+        spawned: Instant::now(),
+        flagged: false,
+    };
+    shard(co.id())
+        .get_or_insert_with(HashMap::new)
+        .insert(co.id(), entry);
+}
+
+// remove the exited coroutine
+#[inline]
+pub(crate) fn unregister(id: CoroutineId) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    if let Some(map) = shard(id).as_mut() {
+        map.remove(&id);
+    }
+}
+
+/// dump the live coroutines, one line for each with its park reason
+///
+/// the live coroutines are tracked by the watchdog, so only the ones
+/// spawned after `Config::coroutine_watchdog` is set are listed
+pub fn dump_live() -> String {
+    let mut entries = Vec::new();
+    for shard in LIVE.iter() {
+        if let Some(map) = lock(shard).as_ref() {
+            entries.extend(map.values().map(|e| (e.co.clone(), e.spawned.elapsed())));
+        }
+    }
+    entries.sort_by_key(|(co, _)| co.id());
+
+    let mut out = String::new();
+    for (co, age) in entries {
+        out.push_str(&format!(
+            "coroutine id={}, name={:?}, age={:?}, park={:?}\n",
+            co.id(),
+            co.name(),
+            age,
+            co.park_reason()
+        ));
+    }
+    out
+}
+
+fn run() {
+    loop {
+        let setting = get_setting();
+        let interval = (setting.max_age / 2).clamp(MIN_SCAN_INTERVAL, MAX_SCAN_INTERVAL);
+        thread::sleep(interval);
+        scan(get_setting());
+    }
+}
+
+fn scan(setting: &Setting) {
+    let mut expired = Vec::new();
+    for shard in LIVE.iter() {
+        if let Some(map) = lock(shard).as_mut() {
+            for entry in map.values_mut() {
+                let age = entry.spawned.elapsed();
+                if !entry.flagged && age >= setting.max_age {
+                    entry.flagged = true;
+                    expired.push((entry.co.clone(), age));
+                }
+            }
+        }
+    }
+
+    // handle them out of the lock, cancel may need to schedule the coroutine
+    for (co, age) in expired {
+        warn!(
+            "coroutine lives too long, id={}, name={:?}, age={:?}, park={:?}",
+            co.id(),
+            co.name(),
+            age,
+            co.park_reason()
+        );
+        if setting.action == WatchdogAction::Cancel {
+            unsafe { co.cancel() };
+        }
+    }
+}
@@ -1,10 +1,23 @@
+use std::cell::Cell;
 use std::thread;
+use std::time::Instant;
 
-use crate::coroutine_impl::{current_cancel_data, is_coroutine};
+use crate::config::config;
+use crate::coroutine_impl::{current_cancel_data, in_critical, is_coroutine};
 use crate::coroutine_impl::{CoroutineImpl, EventResult, EventSource, EventSubscriber};
+use crate::local::select_claim;
 use crate::scheduler::get_scheduler;
 use generator::{co_get_yield, co_set_para, co_yield_with};
 
+// when the running coroutine of the worker is first checked by `maybe_yield`
+thread_local! { static SLICE_START: Cell<Option<Instant>> = const { Cell::new(None) }; }
+
+// start a new run slice, called each time a coroutine is resumed
+#[inline]
+pub(crate) fn reset_slice() {
+    SLICE_START.with(|s| s.set(None));
+}
+
 struct Yield {}
 
 impl EventSource for Yield {
@@ -32,12 +45,36 @@ pub fn yield_with<T: EventSource>(resource: &T) {
         }
     }
 
+    // a select arm gives up the claim while it's blocked
+    let claim = select_claim();
+    if let Some(claim) = claim {
+        claim.release();
+    }
+
     let r = resource as &dyn EventSource as *const _ as *mut _;
     let es = EventSubscriber::new(r);
     co_yield_with(es);
 
-    resource.yield_back(cancel);
     cancel.clear();
+    if let Some(claim) = claim {
+        // another arm has won, cancel this one before it consumes anything
+        if !claim.acquire() {
+            cancel.set_canceled();
+            co_set_para(::std::io::Error::new(
+                ::std::io::ErrorKind::Other,
+                "Canceled",
+            ));
+        }
+    }
+
+    resource.yield_back(cancel);
+}
+
+// yield the coroutine without the select claim and cancel process
+pub(crate) fn yield_plain() {
+    let y = Yield {};
+    let r = &y as &dyn EventSource as *const _ as *mut _;
+    co_yield_with(EventSubscriber::new(r));
 }
 
 /// set the coroutine para that passed into it
@@ -61,3 +98,74 @@ pub fn yield_now() {
     // it's safe to use the stack value here
     yield_with(&y);
 }
+
+/// yield the coroutine only if it has been running too long
+///
+/// it's meant to be called inside the hot loop of CPU-bound code, it
+/// returns immediately in the common case, and yields once the coroutine
+/// has been running longer than the preempt interval (see
+/// `Config::set_preempt_interval`). the run time is measured from the first
+/// call after the coroutine is resumed, so it costs nothing if not used.
+///
+/// ```
+/// # use may::coroutine::maybe_yield;
+/// let mut sum = 0u64;
+/// for i in 0..1_000_000 {
+///     sum += i;
+///     maybe_yield();
+/// }
+/// # assert!(sum > 0);
+/// ```
+///
+/// return true if it actually yielded. it never yields in thread context
+/// or a critical section
+pub fn maybe_yield() -> bool {
+    if !is_coroutine() || in_critical() {
+        return false;
+    }
+    let now = Instant::now();
+    let expired = SLICE_START.with(|s| match s.get() {
+        None => {
+            s.set(Some(now));
+            false
+        }
+        Some(start) => now - start >= config().get_preempt_interval(),
+    });
+    if expired {
+        let y = Yield {};
+        yield_with(&y);
+    }
+    expired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn maybe_yield() {
+        // never yield in thread context
+        assert!(!super::maybe_yield());
+
+        let h = go!(|| {
+            // the first call only starts the slice
+            assert!(!super::maybe_yield());
+            assert!(!super::maybe_yield());
+            let start = Instant::now();
+            while start.elapsed() < config().get_preempt_interval() {}
+            assert!(super::maybe_yield());
+            // a new slice after resumed
+            assert!(!super::maybe_yield());
+        });
+        h.join().unwrap();
+
+        // no yield in a critical section
+        let h = go!(|| crate::coroutine::critical(|| {
+            assert!(!super::maybe_yield());
+            std::thread::sleep(Duration::from_millis(20));
+            assert!(!super::maybe_yield());
+        }));
+        h.join().unwrap();
+    }
+}
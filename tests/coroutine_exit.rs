@@ -0,0 +1,45 @@
+#[macro_use]
+extern crate may;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use may::coroutine::{self, CoroutineId};
+
+#[test]
+fn on_coroutine_exit() {
+    let exits = Arc::new(Mutex::new(HashMap::<CoroutineId, usize>::new()));
+    let e = exits.clone();
+    may::config().on_coroutine_exit(move |id| {
+        *e.lock().unwrap().entry(id).or_insert(0) += 1;
+        // a panic in the hook would not kill the worker
+        if e.lock().unwrap().len() == 1 {
+            panic!("exit hook panic");
+        }
+    });
+
+    let normal = go!(|| {});
+    let panicked = go!(|| panic!("coroutine panic"));
+    let canceled = go!(coroutine::park);
+    thread::sleep(Duration::from_millis(10));
+    unsafe { canceled.coroutine().cancel() };
+
+    let ids: Vec<_> = [&normal, &panicked, &canceled]
+        .iter()
+        .map(|h| h.coroutine().id())
+        .collect();
+    assert!(normal.join().is_ok());
+    assert!(panicked.join().is_err());
+    assert!(canceled.join().is_err());
+
+    // the hook runs after the join is triggered
+    let now = Instant::now();
+    while !ids.iter().all(|id| exits.lock().unwrap().contains_key(id)) {
+        assert!(now.elapsed() < Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(1));
+    }
+    let exits = exits.lock().unwrap();
+    assert!(ids.iter().all(|id| exits[id] == 1));
+}
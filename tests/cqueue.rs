@@ -165,6 +165,116 @@ fn cqueue_select() {
     assert_eq!(rx1.recv(), Ok(42));
 }
 
+#[test]
+fn cqueue_select_io_no_data_lost() {
+    use may::net::{TcpListener, TcpStream};
+    use may::sync::mpsc::channel;
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+
+    let (tx, rx) = channel();
+    go!(move || {
+        coroutine::sleep(Duration::from_millis(100));
+        tx.send(()).unwrap();
+    });
+
+    let mut buf = [0u8; 5];
+    let id = select!(
+        _ = server.read(&mut buf) => panic!("read arm should lose"),
+        _ = rx.recv() => {}
+    );
+    assert_eq!(id, 1);
+
+    // the canceled read must not consume anything from the socket
+    client.write_all(b"hello").unwrap();
+    server.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+}
+
+#[test]
+fn cqueue_select_default() {
+    use may::sync::mpsc::channel;
+    use std::time::Instant;
+
+    // all the channels stay empty
+    let (_tx1, rx1) = channel::<i32>();
+    let (_tx2, rx2) = channel::<i32>();
+    let mut timeout = false;
+    let now = Instant::now();
+    let id = select!(
+        _ = rx1.recv() => panic!("rx1 should not fire"),
+        _ = rx2.recv() => panic!("rx2 should not fire"),
+        default(Duration::from_millis(100)) => timeout = true
+    );
+    assert_eq!(id, 2);
+    assert!(timeout);
+    assert!(now.elapsed() >= Duration::from_millis(100));
+
+    // a channel fires first
+    let (tx, rx) = channel();
+    go!(move || {
+        coroutine::sleep(Duration::from_millis(20));
+        tx.send(1).unwrap();
+    });
+    let mut timeout = false;
+    let now = Instant::now();
+    let id = select!(
+        _ = rx1.recv() => {},
+        v = rx.recv() => assert_eq!(v, Ok(1)),
+        default(Duration::from_secs(10)) => timeout = true,
+    );
+    assert_eq!(id, 1);
+    assert!(!timeout);
+    assert!(now.elapsed() < Duration::from_secs(5));
+
+    // a lone timeout arm
+    let now = Instant::now();
+    let id = select!(default(Duration::from_millis(50)) => {});
+    assert_eq!(id, 0);
+    assert!(now.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn cqueue_select_nonblocking() {
+    use may::sync::mpsc::channel;
+
+    let h = go!(|| {
+        let (tx1, rx1) = channel();
+        let (_tx2, rx2) = channel::<i32>();
+        let mut defaults = 0;
+        let id = select!(
+            _ = rx1.recv() => panic!("rx1 should not fire"),
+            _ = rx2.recv() => panic!("rx2 should not fire"),
+            default => defaults += 1
+        );
+        assert_eq!(id, 2);
+        assert_eq!(defaults, 1);
+
+        // a ready arm wins over the default
+        tx1.send(1).unwrap();
+        let id = select!(
+            v = rx1.recv() => assert_eq!(v, Ok(1)),
+            _ = rx2.recv() => {},
+            default => defaults += 1,
+        );
+        assert_eq!(id, 0);
+        assert_eq!(defaults, 1);
+    });
+    h.join().unwrap();
+
+    // the empty arms never park the caller
+    let (_tx, rx) = channel::<i32>();
+    let now = std::time::Instant::now();
+    for _ in 0..100 {
+        let id = select!(_ = rx.recv() => {}, default => {});
+        assert_eq!(id, 1);
+    }
+    assert!(now.elapsed() < Duration::from_secs(1));
+}
+
 #[test]
 fn cqueue_timeout() {
     cqueue::scope(|cqueue| {
@@ -228,3 +338,282 @@ fn cqueue_loop() {
 
     assert_eq!(result, 50);
 }
+
+#[test]
+fn cqueue_select_arm_panic() {
+    use may::net::{TcpListener, TcpStream};
+    use may::sync::mpsc::channel;
+    use std::io::{Read, Write};
+    use std::panic;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+    let (tx, rx) = channel::<u32>();
+
+    fn arm_panic() -> u32 {
+        panic!("select arm panic")
+    }
+
+    let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let mut buf = [0u8; 5];
+        select!(
+            _ = server.read(&mut buf) => {},
+            _ = rx.recv() => {},
+            _ = arm_panic() => {}
+        )
+    }));
+    assert!(ret.is_err());
+
+    // the other arms are canceled and the resources are still usable
+    tx.send(42).unwrap();
+    assert_eq!(rx.recv().unwrap(), 42);
+    let mut buf = [0u8; 5];
+    client.write_all(b"hello").unwrap();
+    server.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"hello");
+
+    // panic in the scope while an arm already panicked should not abort
+    let ret = panic::catch_unwind(|| {
+        cqueue::scope(|cqueue| {
+            cqueue_add_oneshot!(cqueue, 0, _ = arm_panic() => {});
+            std::thread::sleep(Duration::from_millis(50));
+            arm_panic()
+        })
+    });
+    assert!(ret.is_err());
+}
+
+#[test]
+fn cqueue_ready_complete() {
+    use may::sync::mpsc::channel;
+
+    let (tx1, rx1) = channel();
+    let (tx2, rx2) = channel();
+    tx1.send(1).unwrap();
+    tx2.send(2).unwrap();
+
+    cqueue::scope(|cqueue| {
+        cqueue_add_oneshot!(cqueue, 0, x = rx1.recv() => assert_eq!(x, Ok(1)));
+        cqueue_add_oneshot!(cqueue, 1, x = rx2.recv() => assert_eq!(x, Ok(2)));
+
+        // wait until both of them are ready
+        let first = cqueue.ready(None).unwrap();
+        assert!(first < 2);
+        let mut tokens = cqueue.ready_tokens();
+        while tokens.len() < 2 {
+            coroutine::sleep(Duration::from_millis(10));
+            tokens = cqueue.ready_tokens();
+        }
+        // peek again would not consume
+        assert_eq!(cqueue.ready(None).unwrap(), first);
+
+        // complete in a custom order
+        assert_eq!(cqueue.complete(1).unwrap().token, 1);
+        assert!(cqueue.complete(1).is_none());
+        assert_eq!(cqueue.ready_tokens(), vec![0]);
+        assert_eq!(cqueue.complete(0).unwrap().token, 0);
+
+        match cqueue.poll(None) {
+            Err(x) => assert_eq!(x, Finished),
+            _ => unreachable!(),
+        }
+    });
+}
+
+#[test]
+fn select_send_forward() {
+    use may::sync::mpsc::channel;
+    use std::collections::VecDeque;
+
+    let (tx1, rx1) = channel();
+    let (tx2, rx2) = channel();
+    let (_never_tx, never) = channel::<()>();
+    go!(move || {
+        for i in 0..100 {
+            tx1.send(i).unwrap();
+            if i % 10 == 0 {
+                coroutine::yield_now();
+            }
+        }
+    });
+
+    // forward between the two channels with one select
+    let proxy = go!(move || {
+        let mut queue = VecDeque::new();
+        loop {
+            if queue.is_empty() {
+                match rx1.recv() {
+                    Ok(v) => queue.push_back(v),
+                    Err(_) => break,
+                }
+            }
+            while let Ok(v) = rx1.try_recv() {
+                queue.push_back(v);
+            }
+            let mut slot = queue.pop_front();
+            let id = select!(
+                send tx2, &mut slot => {},
+                _ = never.recv() => unreachable!("the losing arm never runs")
+            );
+            assert_eq!(id, 0);
+            // the value not sent is kept in order
+            if let Some(v) = slot {
+                queue.push_front(v);
+            }
+        }
+    });
+
+    proxy.join().unwrap();
+    let got: Vec<i32> = rx2.iter().collect();
+    assert_eq!(got, (0..100).collect::<Vec<_>>());
+}
+
+#[test]
+fn cqueue_select_one_body() {
+    use may::sync::mpsc::channel;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let bodies = Arc::new(AtomicUsize::new(0));
+    for _ in 0..100 {
+        let (tx1, rx1) = channel();
+        let (tx2, rx2) = channel();
+        tx1.send(1).unwrap();
+        tx2.send(2).unwrap();
+        // both arms are ready, but only one body runs
+        let b = bodies.clone();
+        select!(
+            _ = rx1.recv() => {
+                b.fetch_add(1, Ordering::Relaxed);
+            },
+            _ = rx2.recv() => {
+                b.fetch_add(1, Ordering::Relaxed);
+            }
+        );
+    }
+    assert_eq!(bodies.load(Ordering::Relaxed), 100);
+}
+
+#[test]
+fn cqueue_select_io_both_ready() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    for _ in 0..20 {
+        let mut c1 = TcpStream::connect(addr).unwrap();
+        let (mut s1, _) = listener.accept().unwrap();
+        let mut c2 = TcpStream::connect(addr).unwrap();
+        let (mut s2, _) = listener.accept().unwrap();
+        c1.write_all(b"hello").unwrap();
+        c2.write_all(b"world").unwrap();
+        // let the data arrive on both sockets
+        coroutine::sleep(Duration::from_millis(10));
+
+        let mut buf1 = [0u8; 5];
+        let mut buf2 = [0u8; 5];
+        let id = select!(
+            n = s1.read(&mut buf1) => assert_eq!(n.unwrap(), 5),
+            n = s2.read(&mut buf2) => assert_eq!(n.unwrap(), 5)
+        );
+        // the losing read must not consume anything
+        let mut buf = [0u8; 5];
+        if id == 0 {
+            assert_eq!(&buf1, b"hello");
+            s2.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"world");
+        } else {
+            assert_eq!(&buf2, b"world");
+            s1.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, b"hello");
+        }
+    }
+}
+
+#[test]
+fn cqueue_select_default_no_data_lost() {
+    use may::sync::mpsc::channel;
+
+    let h = go!(|| {
+        for _ in 0..100 {
+            let (tx1, rx1) = channel();
+            let (tx2, rx2) = channel();
+            tx1.send(1).unwrap();
+            tx2.send(2).unwrap();
+            // both arms are ready, only the winner takes its message
+            let mut defaulted = false;
+            let id = select!(
+                v = rx1.recv() => assert_eq!(v, Ok(1)),
+                v = rx2.recv() => assert_eq!(v, Ok(2)),
+                default => defaulted = true
+            );
+            assert!(!defaulted);
+            match id {
+                0 => assert_eq!(rx2.try_recv(), Ok(2)),
+                1 => assert_eq!(rx1.try_recv(), Ok(1)),
+                _ => unreachable!(),
+            }
+        }
+    });
+    h.join().unwrap();
+}
+
+#[test]
+fn select_send_recv_forward() {
+    use may::sync::mpmc;
+    use may::sync::mpsc::channel;
+    use std::collections::VecDeque;
+
+    let (tx1, rx1) = channel();
+    let (tx2, rx2) = mpmc::bounded(1);
+    go!(move || {
+        for i in 0..100 {
+            tx1.send(i).unwrap();
+            if i % 5 == 0 {
+                coroutine::sleep(Duration::from_millis(1));
+            }
+        }
+    });
+
+    // forward between the two channels with one select
+    let proxy = go!(move || {
+        let mut queue = VecDeque::new();
+        let mut slot = None;
+        let mut done = false;
+        while !done || slot.is_some() || !queue.is_empty() {
+            if slot.is_none() {
+                slot = queue.pop_front();
+            }
+            let mut v = None;
+            if done {
+                select!(send tx2, &mut slot => {});
+            } else {
+                select!(
+                    r = rx1.recv() => v = Some(r),
+                    send tx2, &mut slot => {}
+                );
+            }
+            match v {
+                Some(Ok(v)) => queue.push_back(v),
+                Some(Err(_)) => done = true,
+                None => {}
+            }
+        }
+    });
+
+    // drain slowly, so the send arm is often blocked on the full channel
+    let mut got = Vec::new();
+    while let Ok(v) = rx2.recv() {
+        got.push(v);
+        if got.len() % 3 == 0 {
+            coroutine::sleep(Duration::from_millis(1));
+        }
+        if got.len() == 100 {
+            break;
+        }
+    }
+    proxy.join().unwrap();
+    assert_eq!(got, (0..100).collect::<Vec<_>>());
+}
@@ -50,6 +50,45 @@ fn cancel_coroutine() {
     }
 }
 
+#[test]
+fn cancel_accept_coroutine() {
+    use may::net::TcpListener;
+    use may::sync::Mutex;
+    use std::sync::{Arc, TryLockError};
+
+    let lock = Arc::new(Mutex::new(0));
+    let l = lock.clone();
+    let j = go!(move || {
+        let _guard = l.lock().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.accept().unwrap();
+        unreachable!("canceled, should not come here");
+    });
+
+    thread::sleep(Duration::from_millis(50));
+    unsafe { j.cancel() };
+    let panic = j.join().unwrap_err();
+    assert!(matches!(
+        panic.downcast_ref::<generator::Error>(),
+        Some(&generator::Error::Cancel)
+    ));
+    // the guard is released by the unwinding
+    assert!(!matches!(lock.try_lock(), Err(TryLockError::WouldBlock)));
+
+    // cooperative cancellation of a pure-CPU loop
+    assert!(!coroutine::is_canceled());
+    let j = go!(|| {
+        let mut n = 0u64;
+        while !coroutine::is_canceled() {
+            n = n.wrapping_add(1);
+        }
+        n
+    });
+    thread::sleep(Duration::from_millis(10));
+    unsafe { j.cancel() };
+    j.join().unwrap();
+}
+
 #[test]
 fn cancel_io_coroutine() {
     let j = go!(move || {
@@ -191,6 +230,54 @@ fn scoped_coroutine() {
     assert_eq!(array[2], 4);
 }
 
+#[test]
+fn scoped_coroutine_chunks() {
+    use std::panic;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let mut data: Vec<usize> = (0..100).collect();
+    coroutine::scope(|scope| {
+        for chunk in data.chunks_mut(10) {
+            go!(scope, move || {
+                for v in chunk {
+                    *v *= 2;
+                }
+            });
+        }
+    });
+    assert_eq!(data, (0..100).map(|v| v * 2).collect::<Vec<_>>());
+
+    // the children are joined even if the body panics
+    let joined = AtomicUsize::new(0);
+    let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        coroutine::scope(|scope| {
+            for _ in 0..4 {
+                go!(scope, || {
+                    coroutine::sleep(Duration::from_millis(20));
+                    joined.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            panic!("scope body panic");
+        })
+    }));
+    assert!(ret.is_err());
+    assert_eq!(joined.load(Ordering::SeqCst), 4);
+
+    // the panic of a child is propagated after all joined
+    let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        coroutine::scope(|scope| {
+            go!(scope, || panic!("scoped child panic"));
+            go!(scope, || {
+                coroutine::sleep(Duration::from_millis(20));
+                joined.fetch_add(1, Ordering::SeqCst);
+            });
+        })
+    }));
+    let err = ret.unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&"scoped child panic"));
+    assert_eq!(joined.load(Ordering::SeqCst), 5);
+}
+
 #[test]
 fn yield_from_gen() {
     let mut a = 0;
@@ -240,6 +327,50 @@ fn unpark() {
     assert_eq!(a, 10);
 }
 
+#[test]
+fn park_token() {
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // more parked coroutines than the workers
+    let ready = Arc::new(AtomicBool::new(false));
+    let woken = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<_> = (0..64)
+        .map(|_| {
+            let ready = ready.clone();
+            let woken = woken.clone();
+            go!(move || {
+                // tolerate the spurious wakeups
+                while !ready.load(Ordering::SeqCst) {
+                    coroutine::park();
+                }
+                woken.fetch_add(1, Ordering::SeqCst);
+            })
+        })
+        .collect();
+
+    // the workers are still able to run other coroutines
+    go!(|| 42).join().unwrap();
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(woken.load(Ordering::SeqCst), 0);
+
+    ready.store(true, Ordering::SeqCst);
+    for h in handles.iter() {
+        h.unpark();
+    }
+    for h in handles {
+        h.join().unwrap();
+    }
+    assert_eq!(woken.load(Ordering::SeqCst), 64);
+
+    // the unpark before park is remembered
+    let h = go!(|| {
+        coroutine::current().unpark();
+        coroutine::park();
+    });
+    h.join().unwrap();
+}
+
 #[test]
 fn park_timeout() {
     let mut a = 0;
@@ -312,6 +443,35 @@ fn join_macro() {
     assert_eq!(rx2.try_recv().is_err(), true);
 }
 
+#[test]
+fn builder_large_stack() {
+    // about 1k bytes for each frame
+    fn recurse(n: usize) -> usize {
+        let buf = std::hint::black_box([n as u8; 1024]);
+        if n == 0 {
+            return buf[0] as usize;
+        }
+        recurse(n - 1) + buf[1023] as usize % 2
+    }
+
+    // 8MB stack
+    let size = (8 << 20) / std::mem::size_of::<usize>();
+    let h = unsafe {
+        coroutine::Builder::new()
+            .name("parser".to_owned())
+            .stack_size(size)
+            .spawn(move || {
+                let co = coroutine::current();
+                assert_eq!(co.name(), Some("parser"));
+                assert_eq!(co.stack_size(), size);
+                // far beyond the default stack
+                recurse(4000)
+            })
+            .unwrap()
+    };
+    assert_eq!(h.join().unwrap(), 2000);
+}
+
 #[test]
 fn go_with_macro() {
     use may::sync::mpsc::channel;
@@ -345,3 +505,1488 @@ fn go_with_macro() {
         assert_eq!(stack_size, 10240);
     }
 }
+
+#[test]
+fn tcp_finish_writing() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        let mut req = String::new();
+        // read until the client half close the stream
+        s.read_to_string(&mut req).unwrap();
+        s.write_all(req.to_uppercase().as_bytes()).unwrap();
+    });
+
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_all(b"hello").unwrap();
+        s.finish_writing().unwrap();
+        let mut rsp = String::new();
+        s.read_to_string(&mut rsp).unwrap();
+        rsp
+    });
+
+    server.join().unwrap();
+    assert_eq!(client.join().unwrap(), "HELLO");
+}
+
+#[test]
+fn no_migrate() {
+    let j: Vec<_> = (0..10)
+        .map(|_| {
+            go!(|| {
+                coroutine::no_migrate(|| {
+                    let id = thread::current().id();
+                    for i in 0..100 {
+                        if i % 10 == 0 {
+                            coroutine::sleep(Duration::from_millis(1));
+                        } else {
+                            yield_now();
+                        }
+                        assert_eq!(thread::current().id(), id);
+                    }
+                })
+            })
+        })
+        .collect();
+
+    for h in j {
+        h.join().unwrap();
+    }
+}
+
+#[test]
+fn tcp_connect_refused() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::ErrorKind;
+
+    // get a free port and then close it
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let j = go!(move || TcpStream::connect(addr).map(|_| ()));
+    let err = j.join().unwrap().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ConnectionRefused);
+}
+
+#[test]
+fn tcp_read_deadline() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{ErrorKind, Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        // each chunk arrives before the read timeout
+        for _ in 0..10 {
+            if s.write_all(b"a").is_err() {
+                break;
+            }
+            coroutine::sleep(Duration::from_millis(50));
+        }
+    });
+
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.set_read_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_millis(200);
+        let mut buf = [0; 1];
+        let err = s
+            .with_read_deadline(deadline, |s| loop {
+                if let Err(e) = s.read(&mut buf) {
+                    return e;
+                }
+            })
+            .kind();
+        assert!(Instant::now() >= deadline);
+
+        // a closure that unwinds doesn't leave the deadline behind
+        let ret = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            s.with_read_deadline(Instant::now(), |_| panic!("unwind in deadline"))
+        }));
+        assert!(ret.is_err());
+        s.read_exact(&mut buf).unwrap();
+        err
+    });
+
+    assert_eq!(client.join().unwrap(), ErrorKind::TimedOut);
+    server.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn udp_raw_fd_round_trip() {
+    use may::net::UdpSocket;
+    use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd};
+
+    let j = go!(|| {
+        let s = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = s.local_addr().unwrap();
+        let fd = s.as_raw_fd();
+        let fd2 = s.into_raw_fd();
+        assert_eq!(fd, fd2);
+
+        // the socket is registered again to the selector
+        let s = unsafe { UdpSocket::from_raw_fd(fd2) };
+        let c = UdpSocket::bind("127.0.0.1:0").unwrap();
+        c.send_to(b"ping", addr).unwrap();
+        let mut buf = [0; 4];
+        let (n, _) = s.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"ping");
+    });
+    j.join().unwrap();
+}
+
+#[test]
+fn critical_section() {
+    let j = go!(|| {
+        coroutine::critical(|| {
+            coroutine::critical(|| assert!(!coroutine::maybe_yield()));
+            // still in the outer section after the nested one ends
+            let start = Instant::now();
+            while start.elapsed() < may::config().get_preempt_interval() * 2 {
+                assert!(!coroutine::maybe_yield());
+            }
+        });
+        // an explicit yield in a section still yields
+        coroutine::critical(yield_now);
+    });
+    j.join().unwrap();
+}
+
+#[test]
+fn join_all() {
+    let handles = (0..10).map(|i| go!(move || i * 2)).collect();
+    let results: Vec<_> = coroutine::join_all(handles)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(results, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+}
+
+#[test]
+fn try_join() {
+    let (tx, rx) = may::sync::mpsc::channel();
+    let h = go!(move || {
+        rx.recv().unwrap();
+        42
+    });
+    assert!(!h.is_finished());
+    let h = h.try_join().unwrap_err();
+    tx.send(()).unwrap();
+    h.wait();
+    assert!(h.is_finished());
+    assert_eq!(h.try_join().ok().unwrap().unwrap(), 42);
+
+    let h = go!(|| panic!("try_join panic"));
+    h.wait();
+    let err = h.try_join().ok().unwrap().unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&"try_join panic"));
+}
+
+#[test]
+fn try_join_all() {
+    let handles = (0..10)
+        .map(|i| {
+            go!(move || {
+                coroutine::sleep(Duration::from_millis(10 * (10 - i)));
+                i
+            })
+        })
+        .collect();
+    let results = unsafe { coroutine::try_join_all(handles) }.unwrap();
+    assert_eq!(results, (0..10).collect::<Vec<_>>());
+
+    let now = Instant::now();
+    let handles = vec![
+        go!(|| coroutine::sleep(Duration::from_secs(10))),
+        go!(|| panic!("try_join_all panic")),
+    ];
+    let err = unsafe { coroutine::try_join_all(handles) }.unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&"try_join_all panic"));
+    // the sleeping coroutine is canceled instead of waited
+    assert!(now.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(unix)]
+#[test]
+fn udp_recv_into_ring() {
+    use may::net::{DatagramRing, UdpSocket};
+
+    let rx = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let tx = UdpSocket::bind("127.0.0.1:0").unwrap();
+    tx.connect(rx.local_addr().unwrap()).unwrap();
+    rx.connect(tx.local_addr().unwrap()).unwrap();
+
+    let j = go!(move || {
+        let mut ring = DatagramRing::new(4, 8);
+        // block until the first datagram arrives
+        assert_eq!(rx.recv_into_ring(&mut ring).unwrap(), 1);
+        assert_eq!(ring.get(0).unwrap(), b"first");
+
+        // drain the available ones, truncated to the slot size
+        coroutine::sleep(Duration::from_millis(50));
+        assert_eq!(rx.recv_into_ring(&mut ring).unwrap(), 4);
+        let datagrams: Vec<_> = ring.iter().collect();
+        assert_eq!(datagrams, vec![&b"0"[..], b"1", b"2", b"33333333"]);
+        assert_eq!(rx.recv_into_ring(&mut ring).unwrap(), 1);
+        assert_eq!(ring.get(0).unwrap(), b"4");
+    });
+
+    thread::sleep(Duration::from_millis(50));
+    tx.send(b"first").unwrap();
+    thread::sleep(Duration::from_millis(10));
+    for d in &["0", "1", "2", "333333333", "4"] {
+        tx.send(d.as_bytes()).unwrap();
+    }
+    j.join().unwrap();
+}
+
+#[test]
+fn file_sync() {
+    use may::fs::File;
+    use std::io::{Read, Write};
+
+    let path = std::env::temp_dir().join(format!("may_file_sync_{}", std::process::id()));
+    let p = path.clone();
+    go!(move || {
+        let mut f = File::create(&p).unwrap();
+        f.write_all(b"hello may").unwrap();
+        f.sync_data().unwrap();
+        f.sync_all().unwrap();
+    })
+    .join()
+    .unwrap();
+
+    let mut s = String::new();
+    File::open(&path).unwrap().read_to_string(&mut s).unwrap();
+    assert_eq!(s, "hello may");
+    // thread context would sync directly
+    File::open(&path).unwrap().sync_all().unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn file_read_write_at() {
+    use may::fs::File;
+    use std::io::{Read, Seek, Write};
+
+    let path = std::env::temp_dir().join(format!("may_file_at_{}", std::process::id()));
+    let p = path.clone();
+    go!(move || {
+        let mut f = File::create(&p).unwrap();
+        f.write_all(b"0123456789").unwrap();
+        assert_eq!(f.write_at(b"ab", 2).unwrap(), 2);
+        // the cursor is still at the end
+        assert_eq!(f.stream_position().unwrap(), 10);
+    })
+    .join()
+    .unwrap();
+
+    let f = std::sync::Arc::new(File::open(&path).unwrap());
+    let handles: Vec<_> = (0..4u64)
+        .map(|i| {
+            let f = f.clone();
+            go!(move || {
+                let mut buf = [0u8; 3];
+                let n = f.read_at(&mut buf, i * 3).unwrap();
+                buf[..n].to_vec()
+            })
+        })
+        .collect();
+    let parts: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(parts.concat(), b"01ab456789");
+
+    let mut f = std::sync::Arc::try_unwrap(f).unwrap();
+    go!(move || {
+        // past the end of file
+        let mut buf = [0u8; 4];
+        assert_eq!(f.read_at(&mut buf, 100).unwrap(), 0);
+        // the sequential read is not affected
+        let mut s = String::new();
+        f.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "01ab456789");
+        assert_eq!(f.stream_position().unwrap(), 10);
+    })
+    .join()
+    .unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn serve_set_handler() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let serve = listener.serve(|mut s| s.write_all(b"v1").unwrap());
+
+    let fetch = move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        let mut buf = Vec::new();
+        s.read_to_end(&mut buf).unwrap();
+        buf
+    };
+
+    assert_eq!(go!(fetch).join().unwrap(), b"v1");
+    serve.set_handler(|mut s| s.write_all(b"v2").unwrap());
+    assert_eq!(go!(fetch).join().unwrap(), b"v2");
+
+    unsafe { serve.join_handle().coroutine().cancel() };
+    assert!(serve.join().is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn tcp_recv_with_flags() {
+    use may::net::{RecvFlags, TcpListener, TcpStream};
+    use std::io::{ErrorKind, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        coroutine::sleep(Duration::from_millis(50));
+        s.write_all(b"hello").unwrap();
+        coroutine::sleep(Duration::from_millis(50));
+        s.write_all(b" world").unwrap();
+    });
+
+    let client = go!(move || {
+        let s = TcpStream::connect(addr).unwrap();
+        let mut buf = [0; 11];
+        let err = s
+            .recv_with_flags(&mut buf, RecvFlags::DONTWAIT)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        // wait for the two separated writes
+        let n = s.recv_with_flags(&mut buf, RecvFlags::WAITALL).unwrap();
+        assert_eq!(&buf[..n], b"hello world");
+        // closed connection
+        assert_eq!(s.recv_with_flags(&mut buf, RecvFlags::WAITALL).unwrap(), 0);
+    });
+
+    server.join().unwrap();
+    client.join().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn tcp_info() {
+    use may::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || listener.accept().unwrap().0);
+    let client = go!(move || TcpStream::connect(addr).unwrap());
+    let s = server.join().unwrap();
+    let c = client.join().unwrap();
+
+    for info in [s.tcp_info().unwrap(), c.tcp_info().unwrap()] {
+        // TCP_ESTABLISHED
+        assert_eq!(info.state, 1);
+        assert!(info.snd_cwnd > 0);
+        assert_eq!(info.total_retrans, 0);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn bytes_readable() {
+    use may::net::{TcpListener, TcpStream, UdpSocket};
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = go!(move || listener.accept().unwrap().0);
+    let mut c = TcpStream::connect(addr).unwrap();
+    let mut s = server.join().unwrap();
+    assert_eq!(s.peek_len().unwrap(), 0);
+    c.write_all(b"hello").unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(s.peek_len().unwrap(), 5);
+    let mut buf = [0; 5];
+    s.read_exact(&mut buf).unwrap();
+    assert_eq!(s.peek_len().unwrap(), 0);
+
+    let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+    assert_eq!(a.next_datagram_len().unwrap(), 0);
+    b.send_to(&[1; 10], a.local_addr().unwrap()).unwrap();
+    b.send_to(&[2; 20], a.local_addr().unwrap()).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(a.next_datagram_len().unwrap(), 10);
+    let mut buf = vec![0; a.next_datagram_len().unwrap()];
+    assert_eq!(a.recv_from(&mut buf).unwrap().0, 10);
+    assert_eq!(a.next_datagram_len().unwrap(), 20);
+}
+
+#[test]
+fn tcp_framed() {
+    use may::io::Framed;
+    use may::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let mut s = Framed::new(listener.accept().unwrap().0);
+        while let Ok(frame) = s.read_frame() {
+            s.write_frame(&frame).unwrap();
+        }
+    });
+
+    let client = go!(move || {
+        let mut c = Framed::new(TcpStream::connect(addr).unwrap());
+        for i in 0..10 {
+            let frame = vec![i as u8; i * 1000];
+            c.write_frame(&frame).unwrap();
+            assert_eq!(c.read_frame().unwrap(), frame);
+        }
+    });
+
+    client.join().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn tcp_connect_timeout() {
+    use may::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let timeout = Duration::from_millis(500);
+    let h = go!(move || TcpStream::connect_timeout(addr, timeout));
+    assert!(h.join().unwrap().is_ok());
+    // host name in both contexts, all the resolved addresses are tried
+    let port = addr.port();
+    assert!(TcpStream::connect_timeout(("localhost", port), timeout).is_ok());
+    let h = go!(move || TcpStream::connect_timeout(("localhost", port), timeout));
+    assert!(h.join().unwrap().is_ok());
+    // the refused address is skipped
+    let closed = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let h = go!(move || TcpStream::connect_timeout(&[closed, addr][..], timeout));
+    assert_eq!(h.join().unwrap().unwrap().peer_addr().unwrap(), addr);
+
+    // a blackholed address either times out or fails right away
+    // when there is no route, but it never hangs
+    let h = go!(move || {
+        let start = Instant::now();
+        let ret = TcpStream::connect_timeout("10.255.255.1:80", timeout);
+        (ret, start.elapsed())
+    });
+    let (ret, elapsed) = h.join().unwrap();
+    if let Err(e) = ret {
+        if elapsed >= timeout {
+            assert_eq!(e.kind(), std::io::ErrorKind::TimedOut);
+        }
+    }
+    assert!(elapsed < timeout + Duration::from_millis(500));
+}
+
+#[test]
+fn tcp_connector() {
+    use may::net::{TcpConnector, TcpListener};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let local = {
+        // find a free local port
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        l.local_addr().unwrap()
+    };
+
+    let server = go!(move || listener.accept().unwrap().1);
+    let client = go!(move || {
+        TcpConnector::new()
+            .bind(local)
+            .nodelay(true)
+            .ttl(42)
+            .timeout(Duration::from_secs(1))
+            .connect(addr)
+            .unwrap()
+    });
+
+    let s = client.join().unwrap();
+    assert_eq!(server.join().unwrap(), local);
+    assert_eq!(s.local_addr().unwrap(), local);
+    assert_eq!(s.ttl().unwrap(), 42);
+    assert!(s.inner().nodelay().unwrap());
+
+    // thread context
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let s = TcpConnector::new()
+        .ttl(43)
+        .connect(listener.local_addr().unwrap())
+        .unwrap();
+    assert_eq!(s.ttl().unwrap(), 43);
+}
+
+#[test]
+fn tcp_fast_open() {
+    use may::net::{TcpConnector, TcpListenerBuilder};
+    use std::io::Read;
+
+    let mut builder = TcpListenerBuilder::new();
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        builder = builder.fast_open(16);
+    }
+    let listener = builder.bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let mut v = Vec::new();
+        for _ in 0..2 {
+            // the data is always delivered, with or without a cookie
+            let (mut s, _) = listener.accept().unwrap();
+            let mut buf = [0; 5];
+            s.read_exact(&mut buf).unwrap();
+            v.push(buf);
+        }
+        v
+    });
+
+    let client = go!(move || {
+        TcpConnector::new()
+            .fast_open(b"hello")
+            .connect(addr)
+            .unwrap()
+    });
+    let _s1 = client.join().unwrap();
+    // thread context
+    let _s2 = TcpConnector::new()
+        .fast_open(b"world")
+        .connect(addr)
+        .unwrap();
+
+    assert_eq!(server.join().unwrap(), vec![*b"hello", *b"world"]);
+}
+
+#[test]
+fn tcp_accept_when() {
+    use may::net::{TcpListener, TcpStream};
+    use may::sync::Semphore;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let gate = Arc::new(Semphore::new(1));
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let done = Arc::new(AtomicUsize::new(0));
+
+    let (a, d) = (accepted.clone(), done.clone());
+    let server = go!(move || {
+        let mut handlers = Vec::new();
+        for _ in 0..3 {
+            let (s, _, permit) = listener.accept_when(&gate).unwrap();
+            // never accept more than the finished handlers plus the gate
+            let n = a.fetch_add(1, Ordering::SeqCst);
+            assert!(n <= d.load(Ordering::SeqCst));
+            let d = d.clone();
+            handlers.push(go!(move || {
+                coroutine::sleep(Duration::from_millis(20));
+                drop(s);
+                d.fetch_add(1, Ordering::SeqCst);
+                drop(permit);
+            }));
+        }
+        for h in handlers {
+            h.join().unwrap();
+        }
+    });
+
+    // the connections are held in the backlog until accepted
+    let clients: Vec<_> = (0..3).map(|_| TcpStream::connect(addr).unwrap()).collect();
+    thread::sleep(Duration::from_millis(10));
+    assert!(accepted.load(Ordering::SeqCst) <= 1);
+    server.join().unwrap();
+    assert_eq!(done.load(Ordering::SeqCst), 3);
+    drop(clients);
+}
+
+#[test]
+fn tcp_accept_timeout() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let listener = go!(move || {
+        let now = Instant::now();
+        let err = listener
+            .accept_timeout(Duration::from_millis(100))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        let dur = now.elapsed();
+        assert!(dur >= Duration::from_millis(100) && dur < Duration::from_secs(1));
+        listener
+    })
+    .join()
+    .unwrap();
+
+    // a pending connection is accepted without waiting
+    let _s = TcpStream::connect(addr).unwrap();
+    go!(move || {
+        listener.accept_timeout(Duration::from_millis(100)).unwrap();
+        // the same in thread context
+        let now = Instant::now();
+        let err = thread::spawn(move || listener.accept_timeout(Duration::from_millis(100)))
+            .join()
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(now.elapsed() < Duration::from_secs(1));
+    })
+    .join()
+    .unwrap();
+}
+
+#[test]
+fn tcp_listener_builder() {
+    use may::net::{TcpListenerBuilder, TcpStream};
+
+    let listener = TcpListenerBuilder::new()
+        .reuse_port(false)
+        .backlog(16)
+        .ttl(42)
+        .bind("127.0.0.1:0")
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    // the port is not reusable
+    assert!(TcpListenerBuilder::new()
+        .reuse_port(false)
+        .bind(addr)
+        .is_err());
+
+    let client = go!(move || TcpStream::connect(addr).unwrap());
+    let (s, _) = listener.accept().unwrap();
+    // the ttl is inherited
+    assert_eq!(s.ttl().unwrap(), 42);
+    client.join().unwrap();
+
+    // only v6 listener is not reachable from v4
+    if let Ok(l) = TcpListenerBuilder::new().only_v6(true).bind("[::]:0") {
+        let port = l.local_addr().unwrap().port();
+        assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_err());
+    }
+}
+
+#[test]
+fn tcp_incoming_with_addr() {
+    use may::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = go!(move || TcpStream::connect(addr).unwrap());
+    let (s, peer) = listener.incoming_with_addr().next().unwrap().unwrap();
+    let client = client.join().unwrap();
+    assert_eq!(peer, client.local_addr().unwrap());
+    assert_eq!(s.peer_addr().unwrap(), peer);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn tcp_listener_reuse_port() {
+    use may::net::{TcpListenerBuilder, TcpStream};
+    use std::io;
+
+    let builder = TcpListenerBuilder::new().reuse_port(true);
+    let a = builder.bind("127.0.0.1:0").unwrap();
+    let addr = a.local_addr().unwrap();
+    let b = builder.bind(addr).unwrap();
+
+    let workers: Vec<_> = vec![a, b]
+        .into_iter()
+        .map(|listener| {
+            go!(move || {
+                let mut streams = Vec::new();
+                loop {
+                    match listener.accept_timeout(Duration::from_millis(500)) {
+                        Ok((s, _)) => streams.push(s),
+                        Err(ref e) if e.kind() == io::ErrorKind::TimedOut => break,
+                        Err(e) => panic!("accept failed: {}", e),
+                    }
+                }
+                streams.len()
+            })
+        })
+        .collect();
+
+    let clients: Vec<_> = (0..32).map(|_| TcpStream::connect(addr).unwrap()).collect();
+    let counts: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+    // the connections are spread across both listeners
+    assert_eq!(counts.iter().sum::<usize>(), 32);
+    assert!(counts.iter().all(|&n| n > 0), "{:?}", counts);
+    drop(clients);
+}
+
+#[test]
+fn udp_connected_send_recv() {
+    use may::net::UdpSocket;
+    use std::io::ErrorKind;
+
+    let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut buf = [0; 8];
+    // not connected yet
+    assert_eq!(a.send(b"ping").unwrap_err().kind(), ErrorKind::NotConnected);
+    assert_eq!(
+        a.recv(&mut buf).unwrap_err().kind(),
+        ErrorKind::NotConnected
+    );
+
+    // the clone taken before connect sees the connection too
+    let a2 = a.try_clone().unwrap();
+    a.connect(b.local_addr().unwrap()).unwrap();
+    b.connect(a.local_addr().unwrap()).unwrap();
+    let h = go!(move || {
+        let mut buf = [0; 8];
+        for _ in 0..2 {
+            let n = b.recv(&mut buf).unwrap();
+            b.send(&buf[..n]).unwrap();
+        }
+    });
+    assert_eq!(a.send(b"ping").unwrap(), 4);
+    assert_eq!(a.recv(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], b"ping");
+    assert_eq!(a2.send(b"pong").unwrap(), 4);
+    assert_eq!(a2.recv(&mut buf).unwrap(), 4);
+    assert_eq!(&buf[..4], b"pong");
+    h.join().unwrap();
+}
+
+#[test]
+fn udp_bind_v6only() {
+    use may::net::UdpSocket;
+    use std::net::{Ipv6Addr, SocketAddrV6};
+
+    let any = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0);
+    // ipv6 may be disabled on the test machine
+    let server = match UdpSocket::bind_v6only(any, false) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    let port = server.local_addr().unwrap().port();
+
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.send_to(b"v4", ("127.0.0.1", port)).unwrap();
+    let (n, from) = go!(move || server.recv_from(&mut [0; 8]).unwrap())
+        .join()
+        .unwrap();
+    assert_eq!(n, 2);
+    // the peer is an ipv4-mapped address
+    match from.ip() {
+        std::net::IpAddr::V6(ip) => assert_eq!(ip.to_ipv4(), Some([127, 0, 0, 1].into())),
+        ip => panic!("unexpected peer {}", ip),
+    }
+
+    // the v6 only socket doesn't receive ipv4 traffic
+    let server = UdpSocket::bind_v6only(any, true).unwrap();
+    let port = server.local_addr().unwrap().port();
+    client.send_to(b"v4", ("127.0.0.1", port)).unwrap();
+    server
+        .set_read_timeout(Some(Duration::from_millis(50)))
+        .unwrap();
+    assert!(go!(move || server.recv_from(&mut [0; 8]).is_err())
+        .join()
+        .unwrap());
+}
+
+#[test]
+fn tcp_idle_guard() {
+    use may::net::{IdleGuard, TcpListener, TcpStream};
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        // answer the ping, then stay silent
+        let mut buf = [0; 4];
+        s.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+        s.write_all(b"pong").unwrap();
+        let mut rest = Vec::new();
+        s.read_to_end(&mut rest).unwrap();
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let pings = Arc::new(AtomicUsize::new(0));
+    let p = pings.clone();
+    let mut guard = IdleGuard::new(
+        stream,
+        Duration::from_millis(50),
+        Duration::from_millis(200),
+        move |s| {
+            p.fetch_add(1, Ordering::Relaxed);
+            s.write_all(b"ping")
+        },
+    )
+    .unwrap();
+
+    let start = Instant::now();
+    let mut buf = [0; 4];
+    guard.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"pong");
+    assert_eq!(pings.load(Ordering::Relaxed), 1);
+    // ping once more after the pong, then the parked read
+    // is woken up by the shutdown
+    assert_eq!(guard.read(&mut buf).unwrap(), 0);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(pings.load(Ordering::Relaxed), 2);
+    drop(guard);
+    client.join().unwrap();
+}
+
+#[test]
+fn tcp_idle_guard_drop_after_soft() {
+    use may::net::{IdleGuard, TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let _client = TcpStream::connect(addr).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+
+    let fired = Arc::new(AtomicBool::new(false));
+    let f = fired.clone();
+    let guard = IdleGuard::new(
+        stream,
+        Duration::from_millis(20),
+        Duration::from_secs(10),
+        move |_| {
+            f.store(true, Ordering::SeqCst);
+            // the guard is dropped before the watcher re-arms the timer
+            may::coroutine::sleep(Duration::from_millis(50));
+            Ok(())
+        },
+    )
+    .unwrap();
+
+    while !fired.load(Ordering::SeqCst) {
+        may::coroutine::sleep(Duration::from_millis(1));
+    }
+    let start = Instant::now();
+    drop(guard);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn tcp_drain_and_close() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{ErrorKind, Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // the unread request is drained, the client sees the response and EOF
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_all(&[1; 3000]).unwrap();
+        let mut rsp = Vec::new();
+        s.read_to_end(&mut rsp).unwrap();
+        rsp
+    });
+    let (mut s, _) = listener.accept().unwrap();
+    s.write_all(b"done").unwrap();
+    s.drain_and_close(4096, Duration::from_secs(5)).unwrap();
+    assert_eq!(client.join().unwrap(), b"done");
+
+    // the peer sends more than allowed
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_all(&[1; 100]).unwrap();
+        s.read_to_end(&mut Vec::new()).ok();
+    });
+    let (s, _) = listener.accept().unwrap();
+    coroutine::sleep(Duration::from_millis(50));
+    let err = s.drain_and_close(10, Duration::from_secs(5)).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    client.join().unwrap();
+
+    // no limit on the drained data
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_all(&[1; 3000]).unwrap();
+        s.read_to_end(&mut Vec::new()).unwrap();
+    });
+    let (s, _) = listener.accept().unwrap();
+    s.drain_and_close(usize::MAX, Duration::from_secs(5))
+        .unwrap();
+    client.join().unwrap();
+
+    // the peer never closes
+    let client = go!(move || {
+        let s = TcpStream::connect(addr).unwrap();
+        coroutine::sleep(Duration::from_millis(500));
+        drop(s);
+    });
+    let (s, _) = listener.accept().unwrap();
+    let err = s
+        .drain_and_close(1024, Duration::from_millis(50))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    client.join().unwrap();
+
+    // a nonblocking stream still waits for the peer to close
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        coroutine::sleep(Duration::from_millis(50));
+        s.write_all(&[1; 100]).unwrap();
+    });
+    let (s, _) = listener.accept().unwrap();
+    s.set_nonblocking(true).unwrap();
+    s.drain_and_close(1024, Duration::from_secs(5)).unwrap();
+    client.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn tcp_io_token_cancel() {
+    use may::io::IoToken;
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{self, Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut client = TcpStream::connect(addr).unwrap();
+    let (mut s, _) = listener.accept().unwrap();
+    let token = IoToken::new(&s);
+
+    let reader = go!(move || {
+        let mut buf = [0; 8];
+        let err = s.read(&mut buf).unwrap_err();
+        // the stream is still usable after the cancel
+        let n = s.read(&mut buf).unwrap();
+        (err, n)
+    });
+
+    // wait until the reader is parked on the stream
+    while !token.cancel() {
+        thread::sleep(Duration::from_millis(1));
+    }
+    client.write_all(b"hello").unwrap();
+
+    let (err, n) = reader.join().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::Other);
+    assert_eq!(n, 5);
+}
+
+#[test]
+fn tcp_read_into_ring() {
+    use may::net::{RingBuf, TcpListener, TcpStream};
+    use std::io::Write;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let writer = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        s.write_all(b"0123456789").unwrap();
+        s
+    });
+
+    let mut s = TcpStream::connect(addr).unwrap();
+    let mut ring = RingBuf::new(8);
+    let h = go!(move || {
+        let mut n = 0;
+        while n < 6 {
+            n += s.read_into_ring(&mut ring).unwrap();
+        }
+        ring.consume(6);
+        // the free space wraps around the end of the ring
+        while ring.len() < 4 {
+            s.read_into_ring(&mut ring).unwrap();
+        }
+        assert_eq!(ring.as_slices(), (&b"67"[..], &b"89"[..]));
+        ring.advance(ring.free_len());
+        // a full ring returns 0 without reading
+        assert_eq!(s.read_into_ring(&mut ring).unwrap(), 0);
+    });
+    h.join().unwrap();
+    writer.join().unwrap();
+}
+
+#[test]
+fn tcp_stream_context() {
+    use may::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let _client = TcpStream::connect(addr).unwrap();
+    let (mut s, _) = listener.accept().unwrap();
+    assert!(s.context::<u32>().is_none());
+
+    s.set_context(0u32);
+    *s.context_mut::<u32>().unwrap() += 1;
+    assert_eq!(s.context::<u32>(), Some(&1));
+    // mismatched type
+    assert!(s.context::<u64>().is_none());
+    assert!(s.take_context::<u64>().is_none());
+    assert_eq!(s.take_context::<u32>(), Some(1));
+    assert!(s.context::<u32>().is_none());
+
+    // the context is dropped with the stream
+    let rc = Arc::new(());
+    s.set_context(rc.clone());
+    assert!(s.context::<Arc<()>>().is_some());
+    assert_eq!(Arc::strong_count(&rc), 2);
+    drop(s);
+    assert_eq!(Arc::strong_count(&rc), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn tcp_is_healthy() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+    use std::net::Shutdown;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut client = TcpStream::connect(addr).unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+    assert!(client.is_healthy());
+
+    // the pending data is not consumed
+    server.write_all(b"x").unwrap();
+    coroutine::sleep(Duration::from_millis(20));
+    assert!(client.is_healthy());
+    let mut buf = [0; 1];
+    client.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"x");
+
+    // our own half close doesn't matter
+    client.shutdown(Shutdown::Write).unwrap();
+    assert!(client.is_healthy());
+
+    // the peer half close is not reusable
+    server.shutdown(Shutdown::Write).unwrap();
+    coroutine::sleep(Duration::from_millis(20));
+    assert!(!client.is_healthy());
+    drop(server);
+}
+
+#[test]
+fn tcp_write_more() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        // finished by a plain write
+        s.write_more(b"a").unwrap();
+        s.write_more(b"b").unwrap();
+        s.write_all(b"c").unwrap();
+        coroutine::sleep(Duration::from_millis(20));
+        // finished by a flush
+        s.write_more(b"de").unwrap();
+        s.flush().unwrap();
+        coroutine::sleep(Duration::from_millis(500));
+    });
+
+    let (mut s, _) = listener.accept().unwrap();
+    let mut buf = [0; 3];
+    s.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"abc");
+    let start = Instant::now();
+    let mut buf = [0; 2];
+    s.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"de");
+    // not delayed by the kernel
+    assert!(start.elapsed() < Duration::from_millis(150));
+    client.join().unwrap();
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn tcp_urgent_data() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let mut client = TcpStream::connect(addr).unwrap();
+    let (mut server, _) = listener.accept().unwrap();
+    assert!(!server.oob_inline().unwrap());
+    assert_eq!(server.recv_urgent().unwrap(), None);
+
+    let s = server.try_clone().unwrap();
+    let waiter = go!(move || s.wait_urgent().unwrap());
+    coroutine::sleep(Duration::from_millis(50));
+    client.write_all(b"ab").unwrap();
+    let ret = unsafe { libc::send(client.as_raw_fd(), b"!".as_ptr() as _, 1, libc::MSG_OOB) };
+    assert_eq!(ret, 1);
+    assert_eq!(waiter.join().unwrap(), b'!');
+
+    // the normal data is untouched
+    let mut buf = [0; 2];
+    server.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ab");
+    assert_eq!(server.recv_urgent().unwrap(), None);
+}
+
+#[test]
+fn tcp_peek() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        // park until the data arrives
+        let mut peeked = [0u8; 16];
+        let n = s.peek(&mut peeked).unwrap();
+        assert!(n > 0);
+        // the peeked data is still there
+        let mut buf = [0u8; 16];
+        s.read_exact(&mut buf[..n]).unwrap();
+        assert_eq!(&peeked[..n], &buf[..n]);
+        n
+    });
+
+    let mut s = TcpStream::connect(addr).unwrap();
+    thread::sleep(Duration::from_millis(50));
+    assert!(!server.is_done());
+    s.write_all(b"hello").unwrap();
+    assert_eq!(server.join().unwrap(), 5);
+}
+
+#[test]
+fn tcp_write_vectored_partial() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{IoSlice, Read, Write};
+
+    // many frames that overflow the socket buffers, so the writev is short
+    let frames: Vec<Vec<u8>> = (0..64u8).map(|i| vec![i; 64 * 1024 + 7]).collect();
+    let expected: Vec<u8> = frames.concat();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        // let the writer fill up the buffers and park first
+        coroutine::sleep(Duration::from_millis(50));
+        let mut data = Vec::new();
+        s.read_to_end(&mut data).unwrap();
+        data
+    });
+
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        let mut bufs: Vec<IoSlice> = frames.iter().map(|f| IoSlice::new(f)).collect();
+        let mut bufs = &mut bufs[..];
+        let mut short = false;
+        while !bufs.is_empty() {
+            let total: usize = bufs.iter().map(|b| b.len()).sum();
+            let n = s.write_vectored(bufs).unwrap();
+            assert!(n > 0);
+            short |= n < total;
+            // resume from the offset in the middle of a slice
+            IoSlice::advance_slices(&mut bufs, n);
+        }
+        s.shutdown(std::net::Shutdown::Write).unwrap();
+        short
+    });
+
+    assert!(client.join().unwrap());
+    assert_eq!(server.join().unwrap(), expected);
+}
+
+#[test]
+fn tcp_socket_options() {
+    use may::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let j = go!(move || listener.accept().unwrap());
+
+    let check = |s: &TcpStream| {
+        s.set_ttl(42).unwrap();
+        assert_eq!(s.ttl().unwrap(), 42);
+        assert_eq!(s.linger().unwrap(), None);
+        s.set_linger(Some(Duration::from_secs(3))).unwrap();
+        assert_eq!(s.linger().unwrap(), Some(Duration::from_secs(3)));
+        s.set_linger(None).unwrap();
+        assert_eq!(s.linger().unwrap(), None);
+    };
+
+    // the coroutine context socket
+    let s = go!(move || TcpStream::connect(addr).unwrap())
+        .join()
+        .unwrap();
+    check(&s);
+    // the options work no matter it's nonblocking or not
+    s.set_nonblocking(true).unwrap();
+    check(&s);
+    check(&j.join().unwrap().0);
+}
+
+#[test]
+fn sleep_until() {
+    let h = go!(|| {
+        let start = Instant::now();
+        coroutine::sleep_until(start + Duration::from_millis(200));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+
+        // a passed deadline returns immediately
+        let now = Instant::now();
+        coroutine::sleep_until(start);
+        assert!(now.elapsed() < Duration::from_millis(100));
+    });
+    h.join().unwrap();
+
+    // the thread context
+    let start = Instant::now();
+    coroutine::sleep_until(start + Duration::from_millis(200));
+    assert!(start.elapsed() >= Duration::from_millis(200));
+}
+
+#[test]
+fn current_coroutine_id() {
+    let spawn = || {
+        go!(|| {
+            let id = coroutine::current().id();
+            for _ in 0..100 {
+                // the id is stable across the yields and migrations
+                yield_now();
+                assert_eq!(coroutine::current().id(), id);
+            }
+            id
+        })
+    };
+    let (a, b) = (spawn(), spawn());
+    let (id_a, id_b) = (a.coroutine().id(), b.coroutine().id());
+    assert_ne!(id_a, id_b);
+    assert_eq!(a.join().unwrap(), id_a);
+    assert_eq!(b.join().unwrap(), id_b);
+
+    let h = unsafe {
+        coroutine::Builder::new()
+            .name("named".to_owned())
+            .spawn(|| coroutine::current().name().map(str::to_owned))
+            .unwrap()
+    };
+    assert_eq!(h.join().unwrap().as_deref(), Some("named"));
+}
+
+#[test]
+fn udp_multicast_v4() {
+    use may::net::UdpSocket;
+    use std::net::Ipv4Addr;
+
+    let group = Ipv4Addr::new(239, 255, 42, 99);
+    let rx = UdpSocket::bind("0.0.0.0:0").unwrap();
+    let port = rx.local_addr().unwrap().port();
+    // the options work on the registered nonblocking socket
+    rx.join_multicast_v4(&group, &Ipv4Addr::LOCALHOST).unwrap();
+    rx.set_multicast_loop_v4(true).unwrap();
+    assert!(rx.multicast_loop_v4().unwrap());
+
+    // don't hang if the group traffic is dropped
+    rx.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+    let tx = UdpSocket::bind("127.0.0.1:0").unwrap();
+    // send the group traffic on loopback instead of the default route
+    socket2::SockRef::from(tx.inner())
+        .set_multicast_if_v4(&Ipv4Addr::LOCALHOST)
+        .unwrap();
+    tx.set_multicast_ttl_v4(1).unwrap();
+    assert_eq!(tx.multicast_ttl_v4().unwrap(), 1);
+    tx.set_broadcast(true).unwrap();
+    assert!(tx.broadcast().unwrap());
+
+    let j = go!(move || {
+        let mut buf = [0; 16];
+        let (n, _) = rx.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"hello group");
+        rx.leave_multicast_v4(&group, &Ipv4Addr::LOCALHOST).unwrap();
+    });
+    tx.send_to(b"hello group", (group, port)).unwrap();
+    j.join().unwrap();
+}
+
+#[test]
+fn tcp_shutdown_and_drain() {
+    use may::net::{TcpListener, TcpStream};
+    use std::io::{Read, Write};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        // read the whole request until the client half closes
+        let mut req = Vec::new();
+        s.read_to_end(&mut req).unwrap();
+        s.write_all(b"response").unwrap();
+        // nothing left from the client
+        assert!(s.shutdown_and_drain().unwrap().is_empty());
+        req
+    });
+
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.write_all(b"request").unwrap();
+        s.shutdown_and_drain().unwrap()
+    });
+    assert_eq!(client.join().unwrap(), b"response");
+    assert_eq!(server.join().unwrap(), b"request");
+
+    // a peer that closes immediately
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let j = go!(move || drop(listener.accept().unwrap()));
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let now = Instant::now();
+        let data = s.shutdown_and_drain().unwrap();
+        assert!(now.elapsed() < Duration::from_secs(1));
+        data
+    });
+    j.join().unwrap();
+    assert!(client.join().unwrap().is_empty());
+
+    // a nonblocking stream still waits for the peer's data and EOF
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let j = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        coroutine::sleep(Duration::from_millis(50));
+        s.write_all(b"late").unwrap();
+    });
+    let client = go!(move || {
+        let mut s = TcpStream::connect(addr).unwrap();
+        s.set_nonblocking(true).unwrap();
+        s.shutdown_and_drain().unwrap()
+    });
+    j.join().unwrap();
+    assert_eq!(client.join().unwrap(), b"late");
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn udp_send_recv_msg_ecn() {
+    use may::net::{SendMsg, UdpSocket};
+
+    for addr in &["127.0.0.1:0", "[::1]:0"] {
+        // ipv6 may be disabled on the test machine
+        let rx = match UdpSocket::bind(addr) {
+            Ok(s) => s,
+            Err(_) => continue,
+        };
+        let tx = UdpSocket::bind(addr).unwrap();
+        rx.set_recv_tos(true).unwrap();
+        let to = rx.local_addr().unwrap();
+
+        // receive in a coroutine to go through the blocking path
+        let h = go!(move || {
+            let mut buf = [0; 8];
+            let msg = rx.recv_msg(&mut buf).unwrap();
+            (msg, buf)
+        });
+        let n = tx.send_msg(b"ecn", &SendMsg::new(to).ecn(0b10)).unwrap();
+        assert_eq!(n, 3);
+        let (msg, buf) = h.join().unwrap();
+        assert_eq!(&buf[..msg.len], b"ecn");
+        assert_eq!(msg.addr, tx.local_addr().unwrap());
+        assert_eq!(msg.ecn(), Some(0b10));
+    }
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn udp_pacing_rate() {
+    use may::net::UdpSocket;
+    use std::os::unix::io::AsRawFd;
+
+    let sock = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sock.set_pacing_rate(1_000_000).unwrap();
+
+    let mut rate = 0u64;
+    let mut len = std::mem::size_of::<u64>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            sock.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_MAX_PACING_RATE,
+            &mut rate as *mut u64 as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    assert_eq!(ret, 0);
+    assert_eq!(rate, 1_000_000);
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn tcp_defer_accept() {
+    use may::net::{TcpListener, TcpStream};
+    use may::sync::mpsc::channel;
+    use std::io::{Read, Write};
+    use std::os::unix::io::AsRawFd;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_defer_accept(1).unwrap();
+    let mut secs: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            listener.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_DEFER_ACCEPT,
+            &mut secs as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    assert_eq!(ret, 0);
+    // the kernel rounds it up to the syn-ack retransmits
+    assert!(secs >= 1, "{}", secs);
+
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = channel();
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        tx.send(()).unwrap();
+        let mut buf = [0; 4];
+        s.read_exact(&mut buf).unwrap();
+        buf
+    });
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    // no data yet, the connection is held back
+    thread::sleep(Duration::from_millis(300));
+    assert!(rx.try_recv().is_err());
+
+    client.write_all(b"data").unwrap();
+    rx.recv().unwrap();
+    assert_eq!(&server.join().unwrap(), b"data");
+}
@@ -50,3 +50,63 @@ fn coroutine_local_many() {
         assert_eq!(f.load(Ordering::Relaxed), 0);
     });
 }
+
+#[test]
+fn coroutine_local_isolation() {
+    use may::sync::Barrier;
+    use std::cell::Cell;
+    use std::sync::Arc;
+
+    coroutine_local!(static TRACE_ID: Cell<u32> = Cell::new(0));
+
+    // both coroutines are alive while the other one sets its value
+    let barrier = Arc::new(Barrier::new(2));
+    let handles: Vec<_> = (1..=2)
+        .map(|id| {
+            let barrier = barrier.clone();
+            go!(move || {
+                TRACE_ID.with(|t| t.set(id));
+                barrier.wait();
+                coroutine::yield_now();
+                barrier.wait();
+                TRACE_ID.with(|t| t.get())
+            })
+        })
+        .collect();
+    let ids: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(ids, [1, 2]);
+}
+
+#[test]
+fn coroutine_local_drop() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    struct Buf;
+    impl Drop for Buf {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+    coroutine_local!(static BUF: Buf = Buf);
+
+    go!(|| BUF.with(|_| {})).join().unwrap();
+    assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+
+    // dropped on panic
+    go!(|| BUF.with(|_| panic!("local panic")))
+        .join()
+        .unwrap_err();
+    assert_eq!(DROPS.load(Ordering::SeqCst), 2);
+
+    // dropped on cancel
+    let h = go!(|| {
+        BUF.with(|_| {});
+        coroutine::park();
+    });
+    std::thread::sleep(Duration::from_millis(10));
+    unsafe { h.cancel() };
+    h.join().unwrap_err();
+    assert_eq!(DROPS.load(Ordering::SeqCst), 3);
+}
@@ -0,0 +1,40 @@
+#[macro_use]
+extern crate may;
+
+use std::io::{ErrorKind, Read};
+use std::time::Duration;
+
+use may::net::{TcpListener, TcpStream};
+
+#[test]
+fn default_timeouts() {
+    let dur = Duration::from_millis(100);
+    may::net::config()
+        .default_read_timeout(Some(dur))
+        .default_write_timeout(Some(dur));
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = go!(move || {
+        let (mut s, _) = listener.accept().unwrap();
+        assert_eq!(s.read_timeout().unwrap(), Some(dur));
+        assert_eq!(s.write_timeout().unwrap(), Some(dur));
+        // the client never write, the read should timeout
+        let mut buf = [0; 8];
+        let err = s.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::TimedOut);
+    });
+
+    let client = go!(move || {
+        let s = TcpStream::connect(addr).unwrap();
+        assert_eq!(s.read_timeout().unwrap(), Some(dur));
+        // override the default
+        s.set_read_timeout(None).unwrap();
+        assert_eq!(s.read_timeout().unwrap(), None);
+        s
+    });
+
+    server.join().unwrap();
+    drop(client.join().unwrap());
+}
@@ -0,0 +1,74 @@
+extern crate may;
+
+use std::panic;
+use std::sync::{Arc, Mutex};
+
+use may::coroutine;
+
+#[test]
+fn panic_hook_coroutine_name() {
+    // start the runtime first so that this hook is the outermost one
+    unsafe { coroutine::spawn(|| {}) }.join().unwrap();
+
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let n = names.clone();
+    let old = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // the hook runs on the panicked coroutine
+        if coroutine::is_coroutine() {
+            let name = coroutine::current().name().map(str::to_owned);
+            n.lock().unwrap().push(name);
+        }
+        old(info);
+    }));
+
+    let h = unsafe {
+        coroutine::Builder::new()
+            .name("parser".to_owned())
+            .spawn(|| -> () { panic!("parse error") })
+            .unwrap()
+    };
+    assert!(h.join().is_err());
+    let h = unsafe { coroutine::Builder::new().spawn(|| -> () { panic!("no name") }) }.unwrap();
+    assert!(h.join().is_err());
+
+    let _ = panic::take_hook();
+    let names = names.lock().unwrap();
+    assert_eq!(*names, vec![Some("parser".to_owned()), None]);
+}
+
+#[test]
+fn panic_message_coroutine_name() {
+    use std::process::Command;
+
+    // the panicking part runs in a child process, the parent reads its stderr
+    if std::env::var("MAY_PANIC_NAME_CHILD").is_ok() {
+        let h = unsafe {
+            coroutine::Builder::new()
+                .name("parser".to_owned())
+                .spawn(|| -> () { panic!("parse error") })
+                .unwrap()
+        };
+        assert!(h.join().is_err());
+        return;
+    }
+
+    let out = Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "panic_message_coroutine_name", "--nocapture"])
+        .env("MAY_PANIC_NAME_CHILD", "1")
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let mut lines = stderr
+        .lines()
+        .skip_while(|l| !l.starts_with("coroutine 'parser' panicked at "));
+    let line = lines
+        .next()
+        .unwrap_or_else(|| panic!("no coroutine name in {}", stderr));
+    assert!(line.contains("panic_name.rs:"), "{}", line);
+    assert_eq!(lines.next(), Some("parse error"), "{}", stderr);
+    // the panic is reported once, not again by the default hook
+    assert!(!stderr.contains("panicked at src"), "{}", stderr);
+    assert!(!stderr.contains("thread '"), "{}", stderr);
+}
@@ -0,0 +1,83 @@
+#[macro_use]
+extern crate may;
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use may::coroutine::{self, ParkReason};
+use may::net::{TcpListener, TcpStream, UdpSocket};
+use may::sync::{mpsc, Mutex};
+use may::WatchdogAction;
+
+#[test]
+fn park_reason() {
+    // track the live coroutines for the dump
+    may::config().coroutine_watchdog(Duration::from_secs(3600), WatchdogAction::Log);
+
+    let (tx, rx) = mpsc::channel::<()>();
+    let recv = go!(move || rx.recv());
+
+    let lock = Arc::new(Mutex::new(()));
+    let guard = lock.lock().unwrap();
+    let lock2 = lock.clone();
+    let locker = go!(move || drop(lock2.lock().unwrap()));
+
+    let sleeper = go!(|| coroutine::sleep(Duration::from_millis(300)));
+    let sleeper_co = sleeper.coroutine().clone();
+    let joiner = go!(move || sleeper.join().unwrap());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let acceptor = go!(move || listener.accept().map(|_| ()));
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let udp_addr = socket.local_addr().unwrap();
+    let udp = go!(move || socket.recv_from(&mut [0; 16]).map(|_| ()));
+
+    let running = go!(|| coroutine::current().park_reason());
+
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(
+        recv.coroutine().park_reason(),
+        Some(ParkReason::ChannelRecv)
+    );
+    assert_eq!(
+        locker.coroutine().park_reason(),
+        Some(ParkReason::MutexLock)
+    );
+    assert!(matches!(
+        sleeper_co.park_reason(),
+        Some(ParkReason::Sleep(_))
+    ));
+    assert_eq!(joiner.coroutine().park_reason(), Some(ParkReason::Join));
+    assert!(matches!(
+        acceptor.coroutine().park_reason(),
+        Some(ParkReason::IoRead(_))
+    ));
+    assert!(matches!(
+        udp.coroutine().park_reason(),
+        Some(ParkReason::IoRead(_))
+    ));
+
+    let dump = coroutine::dump_live();
+    assert!(dump.contains("park=Some(ChannelRecv)"), "{}", dump);
+    assert!(dump.contains("park=Some(MutexLock)"), "{}", dump);
+
+    // a running coroutine is not parked
+    assert_eq!(running.join().unwrap(), None);
+
+    // the reason is cleared after the call returns
+    tx.send(()).unwrap();
+    drop(guard);
+    recv.join().unwrap().unwrap();
+    locker.join().unwrap();
+    joiner.join().unwrap();
+    let _client = TcpStream::connect(addr).unwrap();
+    acceptor.join().unwrap().unwrap();
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.send_to(b"wake", udp_addr).unwrap();
+    udp.join().unwrap().unwrap();
+    assert_eq!(sleeper_co.park_reason(), None);
+    assert!(!coroutine::dump_live().contains("ChannelRecv"));
+}
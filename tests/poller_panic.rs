@@ -0,0 +1,39 @@
+#[macro_use]
+extern crate may;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use may::PollerPanicPolicy;
+
+#[test]
+fn poller_panic_restart() {
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let t = ticks.clone();
+    may::config()
+        .set_workers(1)
+        .on_poller_panic(PollerPanicPolicy::Restart)
+        .on_tick(Duration::from_millis(10), move |_| {
+            // the first tick kills the poller
+            if t.fetch_add(1, Ordering::Relaxed) == 0 {
+                panic!("poller panic");
+            }
+        });
+
+    go!(|| {}).join().unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(ticks.load(Ordering::Relaxed) > 1);
+
+    // the restarted poller still serves the io
+    let rx = may::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = rx.local_addr().unwrap();
+    let j = go!(move || {
+        let mut buf = [0; 4];
+        rx.recv_from(&mut buf).unwrap().0
+    });
+    std::thread::sleep(Duration::from_millis(10));
+    let tx = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    tx.send_to(b"ping", addr).unwrap();
+    assert_eq!(j.join().unwrap(), 4);
+}
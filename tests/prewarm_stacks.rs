@@ -0,0 +1,34 @@
+use may::coroutine;
+
+#[test]
+fn prewarm_stacks() {
+    // the size is in words
+    let size = 0x8000;
+    assert_eq!(coroutine::prewarm_stacks(16, 0), 16);
+    assert_eq!(coroutine::prewarm_stacks(4, size), 4);
+
+    // spawn more than prewarmed, the rest are allocated as usual
+    for _ in 0..2 {
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let builder = coroutine::Builder::new();
+                let builder = if i % 2 == 0 {
+                    builder.stack_size(size)
+                } else {
+                    builder
+                };
+                let h = unsafe { builder.spawn(move || (i, coroutine::current().stack_size())) };
+                h.unwrap()
+            })
+            .collect();
+        for (i, h) in handles.into_iter().enumerate() {
+            let (id, stack) = h.join().unwrap();
+            assert_eq!(id, i);
+            if i % 2 == 0 {
+                assert_eq!(stack, size);
+            } else {
+                assert_eq!(stack, may::config().get_stack_size());
+            }
+        }
+    }
+}
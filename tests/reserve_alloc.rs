@@ -0,0 +1,60 @@
+extern crate may;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use may::sync::mpsc::channel;
+
+// count the allocations to check the effect of `reserve`
+struct CountingAlloc;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// return the number of the allocations made by `f`
+fn count_allocs<F: FnOnce()>(f: F) -> usize {
+    let start = ALLOCS.load(Ordering::Relaxed);
+    f();
+    ALLOCS.load(Ordering::Relaxed) - start
+}
+
+#[test]
+fn reserve_no_alloc() {
+    let (tx, rx) = channel();
+
+    // each send allocates a node
+    let allocs = count_allocs(|| {
+        for i in 0..1000 {
+            tx.send(i).unwrap();
+        }
+    });
+    assert!(allocs >= 1000);
+    for _ in 0..1000 {
+        rx.recv().unwrap();
+    }
+
+    // the sends take the reserved nodes
+    tx.reserve(1000);
+    let allocs = count_allocs(|| {
+        for i in 0..1000 {
+            tx.send(i).unwrap();
+        }
+    });
+    assert_eq!(allocs, 0);
+    for i in 0..1000 {
+        assert_eq!(rx.recv(), Ok(i));
+    }
+}
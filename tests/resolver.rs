@@ -0,0 +1,69 @@
+#[macro_use]
+extern crate may;
+
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use may::net::{TcpListener, TcpStream};
+
+#[test]
+fn custom_resolver() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls2 = calls.clone();
+    may::net::set_resolver(Box::new(move |host, port| {
+        calls2.fetch_add(1, Ordering::Relaxed);
+        match host {
+            "may.test" => Ok(vec![SocketAddr::new(addr.ip(), port)]),
+            "panic.test" => panic!("resolver panic"),
+            "thread.test" => Err(io::Error::new(
+                ErrorKind::Other,
+                thread::current().name().unwrap_or_default().to_owned(),
+            )),
+            _ => Err(io::Error::new(ErrorKind::NotFound, host.to_owned())),
+        }
+    }));
+
+    // ip address would not go through the resolver
+    assert_eq!(may::net::resolve("127.0.0.1", 80).unwrap()[0].port(), 80);
+    assert_eq!(calls.load(Ordering::Relaxed), 0);
+
+    let server = go!(move || {
+        listener.accept().unwrap();
+    });
+
+    let client = go!(move || {
+        let s = TcpStream::connect_host("may.test", addr.port()).unwrap();
+        assert_eq!(s.peer_addr().unwrap(), addr);
+        let err = TcpStream::connect_host("unknown.test", 80).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    });
+
+    client.join().unwrap();
+    server.join().unwrap();
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+    // the resolver runs on the blocking threads, a panic is reported as an
+    // error and doesn't lose a thread, more lookups than the threads finish
+    let lookups: Vec<_> = (0..32)
+        .map(|_| go!(|| may::net::resolve("panic.test", 80).unwrap_err()))
+        .collect();
+    for h in lookups {
+        assert_eq!(h.join().unwrap().to_string(), "blocking function panicked");
+    }
+    let err = go!(|| may::net::resolve("thread.test", 80).unwrap_err())
+        .join()
+        .unwrap();
+    assert!(err.to_string().starts_with("may-blocking-"), "{}", err);
+
+    // resolve in thread context
+    assert_eq!(
+        may::net::resolve("may.test", addr.port()).unwrap(),
+        vec![addr]
+    );
+}
@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate may;
+
+use std::time::Duration;
+
+use may::coroutine;
+use may::sync::mpsc::channel;
+
+#[test]
+fn scheduler_stats() {
+    const N: u64 = 100;
+
+    let base = coroutine::scheduler_stats();
+    assert_eq!(base.workers, may::config().get_workers());
+    assert_eq!(base.run_queues.len(), base.workers);
+
+    let (tx, rx) = channel();
+    let handles: Vec<_> = (0..N)
+        .map(|_| {
+            let tx = tx.clone();
+            go!(move || {
+                tx.send(()).unwrap();
+                coroutine::park();
+            })
+        })
+        .collect();
+    for _ in 0..N {
+        rx.recv().unwrap();
+    }
+
+    let stats = coroutine::scheduler_stats();
+    assert_eq!(stats.live, N);
+    assert_eq!(stats.spawned - base.spawned, N);
+    assert_eq!(stats.completed, base.completed);
+
+    for h in handles.iter() {
+        h.unpark();
+    }
+    for h in handles {
+        h.join().unwrap();
+    }
+    // the completed count is updated after the join returns
+    let mut stats = coroutine::scheduler_stats();
+    while stats.live != 0 {
+        coroutine::sleep(Duration::from_millis(1));
+        stats = coroutine::scheduler_stats();
+    }
+    assert_eq!(stats.completed - base.completed, N);
+}
@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate may;
+
+use std::sync::Arc;
+
+use may::net::{AcceptAction, TcpListener, TcpStream, UdpSocket};
+
+#[cfg(unix)]
+#[test]
+fn max_open_sockets() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let base = may::net::open_socket_count();
+    assert!(base >= 1);
+    may::net::set_max_open_sockets(base + 2);
+
+    let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+    assert_eq!(may::net::open_socket_count(), base + 2);
+    let err = UdpSocket::bind("127.0.0.1:0").unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EMFILE));
+    drop(a);
+    assert_eq!(may::net::open_socket_count(), base + 1);
+
+    // the accepted stream can't be registered, fail the accept
+    listener.set_accept_backoff(|e| match e.raw_os_error() {
+        Some(libc::EMFILE) => AcceptAction::Fail,
+        _ => AcceptAction::for_error(e),
+    });
+    let listener = Arc::new(listener);
+    let l = listener.clone();
+    let server = go!(move || l.accept().map(|_| ()));
+    // keep the client stream alive until the accept is done
+    let _client = TcpStream::connect(addr).unwrap();
+    let err = server.join().unwrap().unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EMFILE));
+
+    // remove the cap
+    may::net::set_max_open_sockets(0);
+    let _c = UdpSocket::bind("127.0.0.1:0").unwrap();
+    assert_eq!(may::net::open_socket_count(), base + 3);
+    drop(b);
+    assert_eq!(may::net::open_socket_count(), base + 2);
+}
@@ -0,0 +1,25 @@
+#[macro_use]
+extern crate may;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn on_tick() {
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let t = ticks.clone();
+    may::config()
+        .set_workers(2)
+        .on_tick(Duration::from_millis(10), move |id| {
+            assert!(id < 2);
+            t.fetch_add(1, Ordering::Relaxed);
+        });
+
+    // start the scheduler and let the workers idle for a while
+    go!(|| {}).join().unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+
+    // each idle worker should be waked up for the ticks
+    assert!(ticks.load(Ordering::Relaxed) > 10);
+}
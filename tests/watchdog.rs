@@ -0,0 +1,25 @@
+#[macro_use]
+extern crate may;
+
+use std::time::{Duration, Instant};
+
+use may::coroutine;
+use may::WatchdogAction;
+
+#[test]
+fn watchdog_cancel() {
+    may::config().coroutine_watchdog(Duration::from_millis(100), WatchdogAction::Cancel);
+
+    let start = Instant::now();
+    let stuck = go!(|| loop {
+        coroutine::sleep(Duration::from_secs(10));
+    });
+    let quick = go!(|| {
+        coroutine::sleep(Duration::from_millis(10));
+        1
+    });
+    assert_eq!(quick.join().unwrap(), 1);
+    // the stuck coroutine is canceled
+    assert!(stuck.join().is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -0,0 +1,48 @@
+#[macro_use]
+extern crate may;
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use may::coroutine;
+
+// collect the worker threads that the coroutines run on
+fn run_on_workers(n: usize) -> usize {
+    may::test::with_workers(n, || {
+        let threads = Arc::new(Mutex::new(HashSet::new()));
+        let handles: Vec<_> = (0..64)
+            .map(|_| {
+                let threads = threads.clone();
+                go!(move || {
+                    for _ in 0..10 {
+                        threads.lock().unwrap().insert(thread::current().id());
+                        // the nested coroutines are confined too
+                        let t = go!(|| thread::current().id()).join().unwrap();
+                        threads.lock().unwrap().insert(t);
+                        coroutine::yield_now();
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let n = threads.lock().unwrap().len();
+        n
+    })
+}
+
+#[test]
+fn with_workers() {
+    may::config().set_workers(4);
+    assert_eq!(run_on_workers(1), 1);
+    assert!(run_on_workers(2) <= 2);
+}
+
+#[test]
+#[should_panic]
+fn with_workers_panic() {
+    may::config().set_workers(4);
+    may::test::with_workers(1, || panic!("in the group"));
+}